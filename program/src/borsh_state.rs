@@ -2,19 +2,69 @@ use borsh::{BorshDeserialize, BorshSerialize};
 
 use solana_program::{
     account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
-    program_pack::IsInitialized, sysvar::rent::Rent,
+    program_pack::IsInitialized, pubkey::Pubkey, sysvar::rent::Rent,
 };
 
+use crate::error::Error;
+
+/// Version of the account layouts, checked along with the account type
+pub const STATE_VERSION: u8 = 1;
+
+/// Length of the `[account_type, version]` header in front of every state account
+pub const HEADER_LEN: usize = 2;
+
+/// Discriminator of the state account kinds owned by the program
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum AccountType {
+    /// never written by the program, i.e. the header is zeroed
+    Uninitialized = 0,
+    Aggregator = 1,
+    Oracle = 2,
+    Requester = 3,
+    Submissions = 4,
+    AnswerHistory = 5,
+    OracleRegistry = 6,
+    PendingChange = 7,
+}
+
+/// Check that the account is owned by the program before trusting its data
+pub fn check_owner(account: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+    if account.owner != program_id {
+        Err(Error::ProgramOwnerMismatch)?;
+    }
+
+    Ok(())
+}
+
 pub trait BorshState: BorshDeserialize + BorshSerialize {
+    const ACCOUNT_TYPE: AccountType;
+
+    fn header() -> [u8; HEADER_LEN] {
+        [Self::ACCOUNT_TYPE as u8, STATE_VERSION]
+    }
+
     fn load(account: &AccountInfo) -> Result<Self, ProgramError> {
         let data = (*account.data).borrow();
-        Self::try_from_slice(&data).map_err(|_| ProgramError::InvalidAccountData)
+        if data.len() < HEADER_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (header, body) = data.split_at(HEADER_LEN);
+        if header != Self::header() {
+            return Err(Error::AccountTypeMismatch)?;
+        }
+
+        Self::unpack(body)
+    }
+
+    /// deserialize the state from the account data following the header
+    fn unpack(body: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(body).map_err(|_| ProgramError::InvalidAccountData)
     }
 
     fn save(&self, account: &AccountInfo) -> ProgramResult {
-        let data = self
-            .try_to_vec()
-            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let data = self.pack()?;
 
         // FIXME: looks like there is association precedence issue that prevents
         // RefMut from being automatically dereferenced.
@@ -34,9 +84,7 @@ pub trait BorshState: BorshDeserialize + BorshSerialize {
     }
 
     fn save_exempt(&self, account: &AccountInfo, rent: &Rent) -> ProgramResult {
-        let data = self
-            .try_to_vec()
-            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let data = self.pack()?;
 
         if !rent.is_exempt(account.lamports(), data.len()) {
             // FIXME: return a custom error
@@ -52,6 +100,15 @@ pub trait BorshState: BorshDeserialize + BorshSerialize {
 
         Ok(())
     }
+
+    /// serialize the state prefixed with its header
+    fn pack(&self) -> Result<Vec<u8>, ProgramError> {
+        let mut data = Self::header().to_vec();
+        self.serialize(&mut data)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        Ok(data)
+    }
 }
 
 pub trait InitBorshState: BorshState + IsInitialized {
@@ -66,7 +123,22 @@ pub trait InitBorshState: BorshState + IsInitialized {
     }
 
     fn init_uninitialized(account: &AccountInfo) -> Result<Self, ProgramError> {
-        let object = Self::load(account)?;
+        // a freshly allocated account is all zeroes, header included.
+        let is_blank = (*account.data)
+            .borrow()
+            .iter()
+            .all(|b| *b == AccountType::Uninitialized as u8);
+
+        let object = if is_blank {
+            let data = (*account.data).borrow();
+            if data.len() < HEADER_LEN {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Self::unpack(&data[HEADER_LEN..])?
+        } else {
+            Self::load(account)?
+        };
+
         if object.is_initialized() {
             return Err(ProgramError::AccountAlreadyInitialized);
         }
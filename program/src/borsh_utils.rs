@@ -1,4 +1,5 @@
 //! Borsh utils
+use crate::borsh_state::HEADER_LEN;
 use borsh::schema::{BorshSchema, Declaration, Definition, Fields};
 use std::collections::HashMap;
 
@@ -54,3 +55,8 @@ pub fn get_packed_len<S: BorshSchema>() -> usize {
     let schema_container = S::schema_container();
     get_declaration_packed_len(&schema_container.declaration, &schema_container.definitions)
 }
+
+/// Get the account size for the given state type, including the account type header
+pub fn get_account_len<S: BorshSchema>() -> usize {
+    HEADER_LEN + get_packed_len::<S>()
+}
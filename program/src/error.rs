@@ -49,8 +49,130 @@ pub enum Error {
     #[error("Invalid faucet")]
     InvalidFaucet,
 
-    #[error("Unknown error")]
+    #[error("Unknown error")] // 13
     UnknownError,
+
+    // new variants are added below, so that the codes above stay stable
+
+    #[error("Account is not owned by the aggregator program")]
+    ProgramOwnerMismatch,
+
+    #[error("Wrong account type or version")]
+    AccountTypeMismatch,
+
+    #[error("Invalid faucet owner")]
+    InvalidFaucetOwner,
+
+    #[error("Aggregator has no answer history")]
+    NoAnswerHistory,
+
+    #[error("Round not found in answer history")]
+    RoundNotInHistory,
+
+    #[error("Answer history does not cover the TWAP window")]
+    TwapWindowNotCovered,
+
+    #[error("Answer is older than the max age")]
+    StaleAnswer,
+
+    #[error("Answer has fewer submissions than min_submissions")]
+    AnswerBelowMinSubmissions,
+
+    #[error("Current round timed out without resolving an answer")]
+    CurrentRoundTimedOut,
+
+    #[error("Value out of range of the aggregator value type")]
+    ValueOutOfRange,
+
+    #[error("Value type cannot be changed")]
+    ValueTypeImmutable,
+
+    #[error("Aggregator value type mismatch")]
+    ValueTypeMismatch,
+
+    #[error("Round and answer submissions capacities differ")]
+    SubmissionsCapacityMismatch,
+
+    #[error("Max submissions exceeds the submissions capacity")]
+    MaxSubmissionsAboveCapacity,
+
+    #[error("Min submissions must be at least 1")]
+    ZeroMinSubmissions,
+
+    #[error("Min submissions exceeds max submissions")]
+    MinSubmissionsAboveMax,
+
+    #[error("Max submissions exceeds MAX_ORACLES")]
+    MaxSubmissionsAboveMaxOracles,
+
+    #[error("Reward token account cannot be changed")]
+    RewardTokenAccountImmutable,
+
+    #[error("Decimals cannot be changed once answers exist")]
+    DecimalsImmutable,
+
+    #[error("No ownership transfer is pending")]
+    NoPendingOwner,
+
+    #[error("Pending owner mismatch")]
+    PendingOwnerMismatch,
+
+    #[error("Oracle has outstanding withdrawable rewards")]
+    OutstandingWithdrawable,
+
+    #[error("Invalid reward receiver")]
+    InvalidReceiver,
+
+    #[error("Aggregator still has oracles")]
+    AggregatorHasOracles,
+
+    #[error("Max oracles exceeds MAX_ORACLES")]
+    MaxOraclesAboveMaxOracles,
+
+    #[error("Max number of oracles reached")]
+    MaxOraclesReached,
+
+    #[error("Oracle already registered")]
+    OracleAlreadyRegistered,
+
+    #[error("Oracle not registered")]
+    OracleNotRegistered,
+
+    #[error("Feed paused")]
+    AggregatorPaused,
+
+    #[error("Invalid multisig threshold or number of keys")]
+    InvalidMultisig,
+
+    #[error("Duplicate multisig key")]
+    DuplicateSigner,
+
+    #[error("Not enough multisig keys signed")]
+    NotEnoughSigners,
+
+    #[error("Pending change not executable yet")]
+    PendingChangeNotReady,
+
+    #[error("Account does not match the pending change")]
+    PendingChangeMismatch,
+
+    #[error("Invalid feed name")]
+    InvalidFeedName,
+
+    #[error("Aggregator address does not match the feed name")]
+    FeedAddressMismatch,
+
+    #[error("Invalid token program")]
+    InvalidTokenProgram,
+
+    #[error("Aggregator still has requesters")]
+    AggregatorHasRequesters,
+
+    #[error("Aggregator still has pending changes")]
+    AggregatorHasPendingChanges,
+
+    #[error("Oracle weight cannot be 0")]
+    ZeroOracleWeight,
 }
 
 impl From<Error> for ProgramError {
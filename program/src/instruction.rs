@@ -1,15 +1,31 @@
 //! Instruction types
 #![allow(dead_code)]
 
-use crate::state::AggregatorConfig;
+use crate::{
+    find_faucet_owner_address, find_feed_address,
+    state::{AggregatorConfig, AggregatorConfigUpdate, PublicKey},
+};
 
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use solana_program::{
+    instruction::{AccountMeta, Instruction as SolanaInstruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
 
 /// Maximum number of oracles
 ///
-/// change to 14, and it fails with:
-/// Stack offset of -4120 exceeded max offset of -4096 by 24 bytes, please minimize large stack variables
-pub const MAX_ORACLES: usize = 13;
+/// This used to be 13, as a whole `Submissions` deserialized onto the stack
+/// exceeded the 4096 bytes BPF stack frame beyond that. The accounts sized by
+/// it, i.e. `Submissions` & `OracleRegistry`, are now accessed in place one
+/// slot at a time, so the stack no longer depends on it.
+///
+/// Compute: `Submit` decodes each filled slot of the round once, and aggregates
+/// the values on the heap, so its cost grows linearly with the number of
+/// submissions in a round. `tests/submit_compute.rs` checks that a round of
+/// `MAX_ORACLES` submissions fits in the compute budget, with `cargo test-bpf`.
+pub const MAX_ORACLES: usize = 32;
 
 
 /// The amount paid of TOKEN paid to each oracle per submission, in lamports (10e-10 SOL)
@@ -22,20 +38,29 @@ pub enum Instruction {
         config: AggregatorConfig,
     },
 
+    /// Set the config of the aggregator. If the aggregator is timelocked, the
+    /// config is queued in a pending change account instead.
     Configure {
         config: AggregatorConfig,
     },
 
+    /// Add an oracle. If the aggregator is timelocked, the oracle is queued in
+    /// a pending change account instead.
     AddOracle {
         description: [u8; 32],
     },
 
+    /// Remove an oracle, moving its lamports to a destination account. Its
+    /// outstanding rewards, if any, are paid out to a token account of the
+    /// oracle owner, passed along with the reward faucet. If the aggregator is
+    /// timelocked, the removal is queued in a pending change account instead.
     RemoveOracle,
 
     AddRequester {
         description: [u8; 32],
     },
 
+    /// Remove a requester, moving its lamports to a destination account
     RemoveRequester,
 
     RequestRound,
@@ -45,11 +70,795 @@ pub enum Instruction {
         value: u64,
     },
 
-    Withdraw {
-        faucet_owner_seed: Vec<u8>,
+    Withdraw,
+
+    /// Attach an answer history to the aggregator, which then records each
+    /// resolved answer
+    InitializeHistory,
+
+    /// Like `Submit`, for aggregators with a signed `ValueType`
+    SubmitSigned {
+        round_id: u64,
+        value: i128,
+    },
+
+    /// Set the weight of an oracle, used by `AggregationMethod::WeightedMedian`.
+    /// The weight cannot be 0.
+    SetOracleWeight {
+        weight: u32,
+    },
+
+    /// Like `Configure`, changing only the fields set in `update`
+    UpdateConfig {
+        update: AggregatorConfigUpdate,
+    },
+
+    /// Propose a new aggregator owner, who becomes the owner once it accepts
+    ProposeOwner,
+
+    /// Accept the ownership of an aggregator, signed by the pending owner
+    AcceptOwnership,
+
+    /// Cancel a pending ownership transfer
+    CancelOwnershipTransfer,
+
+    /// Hand over an oracle to a new owner, co-signed by the new owner. The
+    /// oracle keeps its withdrawable rewards and its round restart state.
+    SetOracleOwner,
+
+    /// Close an aggregator, along with its submissions & answer history
+    /// accounts, moving their lamports to a destination account. All the
+    /// oracles & requesters must have been removed, and the pending changes
+    /// executed or cancelled first. The remaining tokens of the reward faucet
+    /// go to a receiver, and the faucet is closed too.
+    CloseAggregator,
+
+    /// Freeze the feed: `Submit` & `RequestRound` are rejected, and reading
+    /// the answer fails with `Error::AggregatorPaused`
+    Pause,
+
+    /// Resume a paused feed
+    Unpause,
+
+    /// Hand the administration of the aggregator to `threshold` of `keys`,
+    /// which then sign the owner's instructions in place of the owner, with
+    /// their accounts appended to the instruction. An empty `keys` with a 0
+    /// `threshold` hands it back to the owner.
+    SetAdminMultisig {
+        threshold: u8,
+        keys: Vec<PublicKey>,
+    },
+
+    /// Apply a change queued on a timelocked aggregator, once its delay has
+    /// elapsed, and close its pending change account
+    ExecutePending,
+
+    /// Drop a change queued on a timelocked aggregator, closing its pending
+    /// change account
+    CancelPending,
+
+    /// Like `Initialize`, for a feed named `feed_name`, like `btc:usd`. The
+    /// aggregator account is created by the program at the address derived
+    /// from the owner & the name, see `find_feed_address`, funded by a payer.
+    InitializeFeed {
+        feed_name: String,
+        config: AggregatorConfig,
     },
 }
 
+impl Instruction {
+    fn pack(&self) -> Result<Vec<u8>, ProgramError> {
+        self.try_to_vec()
+            .map_err(|_| ProgramError::InvalidInstructionData)
+    }
+}
+
+/// Creates an `Initialize` instruction. The submissions accounts must be
+/// allocated with the same `Submissions::account_len`, for a capacity of at
+/// least `config.max_submissions`, and the oracle registry account to hold an
+/// `OracleRegistry`.
+pub fn initialize(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    round_submissions: &Pubkey,
+    answer_submissions: &Pubkey,
+    oracle_registry: &Pubkey,
+    config: AggregatorConfig,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*round_submissions, false),
+            AccountMeta::new(*answer_submissions, false),
+            AccountMeta::new(*oracle_registry, false),
+        ],
+        data: Instruction::Initialize { config }.pack()?,
+    })
+}
+
+/// Creates an `InitializeFeed` instruction, with the submissions & oracle
+/// registry accounts allocated like for `initialize`
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_feed(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    aggregator_owner: &Pubkey,
+    round_submissions: &Pubkey,
+    answer_submissions: &Pubkey,
+    oracle_registry: &Pubkey,
+    feed_name: &str,
+    config: AggregatorConfig,
+) -> Result<SolanaInstruction, ProgramError> {
+    let (aggregator, _) = find_feed_address(program_id, aggregator_owner, feed_name)?;
+
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*round_submissions, false),
+            AccountMeta::new(*answer_submissions, false),
+            AccountMeta::new(*oracle_registry, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: Instruction::InitializeFeed {
+            feed_name: feed_name.to_string(),
+            config,
+        }
+        .pack()?,
+    })
+}
+
+/// Creates a `Configure` instruction. `with_pending_change` adds the accounts
+/// required if the aggregator is timelocked.
+pub fn configure(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    config: AggregatorConfig,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+        ],
+        data: Instruction::Configure { config }.pack()?,
+    })
+}
+
+/// Creates an `UpdateConfig` instruction. The updated config is queued like
+/// by `Configure` if the aggregator is timelocked.
+pub fn update_config(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    update: AggregatorConfigUpdate,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+        ],
+        data: Instruction::UpdateConfig { update }.pack()?,
+    })
+}
+
+/// Creates an `AddOracle` instruction. `with_pending_change` adds the accounts
+/// required if the aggregator is timelocked.
+pub fn add_oracle(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    oracle: &Pubkey,
+    oracle_owner: &Pubkey,
+    oracle_registry: &Pubkey,
+    description: [u8; 32],
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*oracle, false),
+            AccountMeta::new_readonly(*oracle_owner, false),
+            AccountMeta::new(*oracle_registry, false),
+        ],
+        data: Instruction::AddOracle { description }.pack()?,
+    })
+}
+
+/// Creates a `RemoveOracle` instruction, for an oracle without outstanding
+/// rewards, or to queue the removal with `with_pending_change` if the
+/// aggregator is timelocked
+pub fn remove_oracle(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    oracle: &Pubkey,
+    oracle_registry: &Pubkey,
+    destination: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*oracle, false),
+            AccountMeta::new(*oracle_registry, false),
+            AccountMeta::new(*destination, false),
+        ],
+        data: Instruction::RemoveOracle.pack()?,
+    })
+}
+
+/// Creates a `RemoveOracle` instruction that pays the outstanding rewards of
+/// the oracle to `receiver`, a token account of the oracle owner
+#[allow(clippy::too_many_arguments)]
+pub fn remove_oracle_with_rewards(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    oracle: &Pubkey,
+    oracle_registry: &Pubkey,
+    destination: &Pubkey,
+    faucet: &Pubkey,
+    receiver: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    let mut inx = remove_oracle(
+        program_id,
+        aggregator,
+        aggregator_owner,
+        oracle,
+        oracle_registry,
+        destination,
+    )?;
+    inx.accounts
+        .extend(reward_accounts(program_id, aggregator, faucet, receiver));
+
+    Ok(inx)
+}
+
+/// The accounts paying out reward tokens from the faucet of an aggregator, as
+/// an oracle is removed or the aggregator closed
+fn reward_accounts(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    faucet: &Pubkey,
+    receiver: &Pubkey,
+) -> Vec<AccountMeta> {
+    let (faucet_owner, _) = find_faucet_owner_address(program_id, aggregator);
+
+    vec![
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*faucet, false),
+        AccountMeta::new_readonly(faucet_owner, false),
+        AccountMeta::new(*receiver, false),
+    ]
+}
+
+/// Creates an `AddRequester` instruction
+pub fn add_requester(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    requester: &Pubkey,
+    requester_owner: &Pubkey,
+    description: [u8; 32],
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*requester, false),
+            AccountMeta::new_readonly(*requester_owner, false),
+        ],
+        data: Instruction::AddRequester { description }.pack()?,
+    })
+}
+
+/// Creates a `RemoveRequester` instruction
+pub fn remove_requester(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    requester: &Pubkey,
+    destination: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*requester, false),
+            AccountMeta::new(*destination, false),
+        ],
+        data: Instruction::RemoveRequester.pack()?,
+    })
+}
+
+/// Creates a `RequestRound` instruction
+pub fn request_round(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    round_submissions: &Pubkey,
+    requester: &Pubkey,
+    requester_owner: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new(*round_submissions, false),
+            AccountMeta::new(*requester, false),
+            AccountMeta::new_readonly(*requester_owner, true),
+        ],
+        data: Instruction::RequestRound.pack()?,
+    })
+}
+
+fn submit_accounts(
+    aggregator: &Pubkey,
+    round_submissions: &Pubkey,
+    answer_submissions: &Pubkey,
+    oracle: &Pubkey,
+    oracle_owner: &Pubkey,
+    answer_history: Option<&Pubkey>,
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*aggregator, false),
+        AccountMeta::new(*round_submissions, false),
+        AccountMeta::new(*answer_submissions, false),
+        AccountMeta::new(*oracle, false),
+        AccountMeta::new_readonly(*oracle_owner, true),
+    ];
+    if let Some(answer_history) = answer_history {
+        accounts.push(AccountMeta::new(*answer_history, false));
+    }
+
+    accounts
+}
+
+/// Creates a `Submit` instruction. `answer_history` is required if the aggregator has one.
+#[allow(clippy::too_many_arguments)]
+pub fn submit(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    round_submissions: &Pubkey,
+    answer_submissions: &Pubkey,
+    oracle: &Pubkey,
+    oracle_owner: &Pubkey,
+    answer_history: Option<&Pubkey>,
+    round_id: u64,
+    value: u64,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: submit_accounts(
+            aggregator,
+            round_submissions,
+            answer_submissions,
+            oracle,
+            oracle_owner,
+            answer_history,
+        ),
+        data: Instruction::Submit { round_id, value }.pack()?,
+    })
+}
+
+/// Creates a `SubmitSigned` instruction. `answer_history` is required if the aggregator has one.
+#[allow(clippy::too_many_arguments)]
+pub fn submit_signed(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    round_submissions: &Pubkey,
+    answer_submissions: &Pubkey,
+    oracle: &Pubkey,
+    oracle_owner: &Pubkey,
+    answer_history: Option<&Pubkey>,
+    round_id: u64,
+    value: i128,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: submit_accounts(
+            aggregator,
+            round_submissions,
+            answer_submissions,
+            oracle,
+            oracle_owner,
+            answer_history,
+        ),
+        data: Instruction::SubmitSigned { round_id, value }.pack()?,
+    })
+}
+
+/// Creates a `Withdraw` instruction. The faucet owner is derived from the aggregator.
+pub fn withdraw(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    faucet: &Pubkey,
+    oracle: &Pubkey,
+    oracle_owner: &Pubkey,
+    receiver: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    let (faucet_owner, _) = find_faucet_owner_address(program_id, aggregator);
+
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(*aggregator, false),
+            AccountMeta::new(*faucet, false),
+            AccountMeta::new_readonly(faucet_owner, false),
+            AccountMeta::new(*oracle, false),
+            AccountMeta::new_readonly(*oracle_owner, true),
+            AccountMeta::new(*receiver, false),
+        ],
+        data: Instruction::Withdraw.pack()?,
+    })
+}
+
+/// Creates an `InitializeHistory` instruction
+pub fn initialize_history(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    answer_history: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*answer_history, false),
+        ],
+        data: Instruction::InitializeHistory.pack()?,
+    })
+}
+
+/// Creates a `SetOracleWeight` instruction
+pub fn set_oracle_weight(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    oracle: &Pubkey,
+    weight: u32,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*oracle, false),
+        ],
+        data: Instruction::SetOracleWeight { weight }.pack()?,
+    })
+}
+
+/// Creates a `ProposeOwner` instruction
+pub fn propose_owner(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    pending_owner: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new_readonly(*pending_owner, false),
+        ],
+        data: Instruction::ProposeOwner.pack()?,
+    })
+}
+
+/// Creates an `AcceptOwnership` instruction
+pub fn accept_ownership(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    pending_owner: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*pending_owner, true),
+        ],
+        data: Instruction::AcceptOwnership.pack()?,
+    })
+}
+
+/// Creates a `CancelOwnershipTransfer` instruction
+pub fn cancel_ownership_transfer(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+        ],
+        data: Instruction::CancelOwnershipTransfer.pack()?,
+    })
+}
+
+/// Creates a `SetOracleOwner` instruction
+pub fn set_oracle_owner(
+    program_id: &Pubkey,
+    oracle: &Pubkey,
+    oracle_owner: &Pubkey,
+    new_owner: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*oracle, false),
+            AccountMeta::new_readonly(*oracle_owner, true),
+            AccountMeta::new_readonly(*new_owner, true),
+        ],
+        data: Instruction::SetOracleOwner.pack()?,
+    })
+}
+
+/// Creates a `CloseAggregator` instruction. `answer_history` must be given
+/// if the aggregator has one, and `reward`, the faucet & the receiver of its
+/// remaining tokens, if the aggregator has a reward token account.
+#[allow(clippy::too_many_arguments)]
+pub fn close_aggregator(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    round_submissions: &Pubkey,
+    answer_submissions: &Pubkey,
+    oracle_registry: &Pubkey,
+    destination: &Pubkey,
+    answer_history: Option<&Pubkey>,
+    reward: Option<(&Pubkey, &Pubkey)>,
+) -> Result<SolanaInstruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*aggregator, false),
+        AccountMeta::new_readonly(*aggregator_owner, true),
+        AccountMeta::new(*round_submissions, false),
+        AccountMeta::new(*answer_submissions, false),
+        AccountMeta::new(*oracle_registry, false),
+        AccountMeta::new(*destination, false),
+    ];
+    if let Some(answer_history) = answer_history {
+        accounts.push(AccountMeta::new(*answer_history, false));
+    }
+    if let Some((faucet, receiver)) = reward {
+        accounts.extend(reward_accounts(program_id, aggregator, faucet, receiver));
+    }
+
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts,
+        data: Instruction::CloseAggregator.pack()?,
+    })
+}
+
+/// Creates a `Pause` instruction
+pub fn pause(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+        ],
+        data: Instruction::Pause.pack()?,
+    })
+}
+
+/// Creates an `Unpause` instruction
+pub fn unpause(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+        ],
+        data: Instruction::Unpause.pack()?,
+    })
+}
+
+/// Creates a `SetAdminMultisig` instruction
+pub fn set_admin_multisig(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    threshold: u8,
+    keys: &[Pubkey],
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+        ],
+        data: Instruction::SetAdminMultisig {
+            threshold,
+            keys: keys.iter().map(|key| PublicKey(key.to_bytes())).collect(),
+        }
+        .pack()?,
+    })
+}
+
+/// Appends the signers of a multisig admin to an instruction signed by the
+/// aggregator owner
+pub fn with_signers(mut instruction: SolanaInstruction, signers: &[&Pubkey]) -> SolanaInstruction {
+    instruction.accounts.extend(
+        signers
+            .iter()
+            .map(|signer| AccountMeta::new_readonly(**signer, true)),
+    );
+    instruction
+}
+
+/// Appends the accounts queuing a change of a timelocked aggregator to a
+/// `Configure`, `UpdateConfig`, `AddOracle`, `RemoveOracle` or
+/// `SetOracleWeight` instruction.
+/// `pending_change` must be allocated to hold a `PendingChange`.
+pub fn with_pending_change(
+    mut instruction: SolanaInstruction,
+    pending_change: &Pubkey,
+) -> SolanaInstruction {
+    instruction.accounts.extend(vec![
+        AccountMeta::new(*pending_change, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ]);
+    instruction
+}
+
+/// Creates an `ExecutePending` instruction for a queued config
+pub fn execute_pending(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    pending_change: &Pubkey,
+    destination: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*pending_change, false),
+            AccountMeta::new(*destination, false),
+        ],
+        data: Instruction::ExecutePending.pack()?,
+    })
+}
+
+/// Creates an `ExecutePending` instruction for a queued `AddOracle`
+pub fn execute_pending_add_oracle(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    pending_change: &Pubkey,
+    destination: &Pubkey,
+    oracle: &Pubkey,
+    oracle_registry: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    let mut inx = execute_pending(
+        program_id,
+        aggregator,
+        aggregator_owner,
+        pending_change,
+        destination,
+    )?;
+    inx.accounts.extend(vec![
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(*oracle, false),
+        AccountMeta::new(*oracle_registry, false),
+    ]);
+
+    Ok(inx)
+}
+
+/// Creates an `ExecutePending` instruction for a queued `RemoveOracle`, moving
+/// the lamports of the oracle to `oracle_destination`. `reward` is the faucet
+/// & receiver of the outstanding rewards of the oracle, if any, like in
+/// `remove_oracle_with_rewards`.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_pending_remove_oracle(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    pending_change: &Pubkey,
+    destination: &Pubkey,
+    oracle: &Pubkey,
+    oracle_registry: &Pubkey,
+    oracle_destination: &Pubkey,
+    reward: Option<(&Pubkey, &Pubkey)>,
+) -> Result<SolanaInstruction, ProgramError> {
+    let mut inx = execute_pending(
+        program_id,
+        aggregator,
+        aggregator_owner,
+        pending_change,
+        destination,
+    )?;
+    inx.accounts.extend(vec![
+        AccountMeta::new(*oracle, false),
+        AccountMeta::new(*oracle_registry, false),
+        AccountMeta::new(*oracle_destination, false),
+    ]);
+    if let Some((faucet, receiver)) = reward {
+        inx.accounts
+            .extend(reward_accounts(program_id, aggregator, faucet, receiver));
+    }
+
+    Ok(inx)
+}
+
+/// Creates an `ExecutePending` instruction for a queued `SetOracleWeight`
+pub fn execute_pending_set_oracle_weight(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    pending_change: &Pubkey,
+    destination: &Pubkey,
+    oracle: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    let mut inx = execute_pending(
+        program_id,
+        aggregator,
+        aggregator_owner,
+        pending_change,
+        destination,
+    )?;
+    inx.accounts.push(AccountMeta::new(*oracle, false));
+
+    Ok(inx)
+}
+
+/// Creates a `CancelPending` instruction
+pub fn cancel_pending(
+    program_id: &Pubkey,
+    aggregator: &Pubkey,
+    aggregator_owner: &Pubkey,
+    pending_change: &Pubkey,
+    destination: &Pubkey,
+) -> Result<SolanaInstruction, ProgramError> {
+    Ok(SolanaInstruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*aggregator, false),
+            AccountMeta::new_readonly(*aggregator_owner, true),
+            AccountMeta::new(*pending_change, false),
+            AccountMeta::new(*destination, false),
+        ],
+        data: Instruction::CancelPending.pack()?,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::convert::TryFrom;
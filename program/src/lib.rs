@@ -10,20 +10,44 @@ pub mod processor;
 pub mod state;
 
 use crate::error::Error;
-use borsh_state::InitBorshState;
+use borsh_state::{check_owner, InitBorshState};
 use solana_program::{
-    account_info::AccountInfo, program_error::ProgramError, program_pack::IsInitialized,
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult,
+    program_error::ProgramError, program_pack::IsInitialized, pubkey::Pubkey,
 };
-use state::{Aggregator, Answer};
+use state::{Aggregator, Answer, Dispersion, ValueType};
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 
-/// Read resolved median value from the aggregator answer submissions
+/// load an aggregator for the answer readers, checking that it is an account
+/// of `program_id`, and that a named feed is at the address derived from its
+/// name
+fn load_aggregator(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+) -> Result<Aggregator, ProgramError> {
+    check_owner(aggregator_info, program_id)?;
+    let aggregator = Aggregator::load_initialized(aggregator_info)?;
+    aggregator.check_feed_address(program_id, aggregator_info)?;
+
+    Ok(aggregator)
+}
+
+/// Read resolved median value from the aggregator answer submissions.
+///
+/// The answer readers take the aggregator program id, and reject an aggregator
+/// account the program doesn't own. They fail with `Error::AggregatorPaused`
+/// while the feed is paused, so that consumers fail safe.
 pub fn read_median(
+    program_id: &Pubkey,
     aggregator_info: &AccountInfo,
 ) -> Result<Answer, ProgramError> {
-    let aggregator = Aggregator::load_initialized(&aggregator_info)?;
+    let aggregator = load_aggregator(program_id, aggregator_info)?;
+    aggregator.check_not_paused()?;
+    if aggregator.config.value_type != ValueType::U64 {
+        Err(Error::ValueTypeMismatch)?;
+    }
 
     if !aggregator.answer.is_initialized() {
         return Err(Error::NoResolvedAnswer)?;
@@ -32,9 +56,21 @@ pub fn read_median(
     Ok(aggregator.answer)
 }
 
-/// Read resolved median value from the aggregator answer submissions, and decimal
-pub fn read_price(aggregator_info: &AccountInfo,) -> Result<(Answer, u8), ProgramError> {
-    let aggregator = Aggregator::load_initialized(&aggregator_info)?;
+/// Read resolved median value from the aggregator answer submissions, and decimal.
+/// `Answer::method` identifies the aggregation method the value is computed with.
+///
+/// Use `check_feed_address` first to check that it is the feed of a given
+/// authority & name.
+pub fn read_price(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+) -> Result<(Answer, u8), ProgramError> {
+    let aggregator = load_aggregator(program_id, aggregator_info)?;
+    aggregator.check_not_paused()?;
+    if aggregator.config.value_type != ValueType::U64 {
+        Err(Error::ValueTypeMismatch)?;
+    }
+
     if !aggregator.answer.is_initialized() {
         return Err(Error::NoResolvedAnswer)?;
     }
@@ -42,5 +78,207 @@ pub fn read_price(aggregator_info: &AccountInfo,) -> Result<(Answer, u8), Progra
     Ok((aggregator.answer, aggregator.config.decimals))
 }
 
+/// Read resolved signed median value from the aggregator answer submissions, and decimal.
+/// Works for aggregators of any value type.
+pub fn read_signed_price(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+) -> Result<(i128, u8), ProgramError> {
+    let aggregator = load_aggregator(program_id, aggregator_info)?;
+    aggregator.check_not_paused()?;
+    if !aggregator.answer.is_initialized() {
+        Err(Error::NoResolvedAnswer)?;
+    }
+
+    Ok((aggregator.answer.signed_median, aggregator.config.decimals))
+}
+
+/// Read resolved signed median value, the spread of the submissions it is
+/// computed from, and decimal. Works for aggregators of any value type.
+pub fn read_price_with_confidence(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+) -> Result<(i128, Dispersion, u8), ProgramError> {
+    let aggregator = load_aggregator(program_id, aggregator_info)?;
+    aggregator.check_not_paused()?;
+    let answer = aggregator.answer;
+    if !answer.is_initialized() {
+        Err(Error::NoResolvedAnswer)?;
+    }
+
+    Ok((answer.signed_median, answer.dispersion, aggregator.config.decimals))
+}
+
+/// Read resolved median value and decimal like `read_price`, rejecting an
+/// answer that consumers should not act on:
+///
+/// * it was last updated more than `max_age` slots ago
+/// * it has fewer submissions than the currently configured `min_submissions`
+/// * a newer round had started, and timed out without resolving
+pub fn read_price_checked(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+    clock: &Clock,
+    max_age: u64,
+) -> Result<(Answer, u8), ProgramError> {
+    let aggregator = load_aggregator(program_id, aggregator_info)?;
+    aggregator.check_not_paused()?;
+    if aggregator.config.value_type != ValueType::U64 {
+        Err(Error::ValueTypeMismatch)?;
+    }
+
+    let answer = aggregator.answer;
+    if !answer.is_initialized() {
+        Err(Error::NoResolvedAnswer)?;
+    }
+
+    let now = clock.slot;
+    if now.saturating_sub(answer.updated_at) > max_age {
+        Err(Error::StaleAnswer)?;
+    }
+
+    if answer.submissions < aggregator.config.min_submissions {
+        Err(Error::AnswerBelowMinSubmissions)?;
+    }
+
+    if aggregator.round.id > answer.round_id && aggregator.is_round_timed_out(clock) {
+        Err(Error::CurrentRoundTimedOut)?;
+    }
+
+    Ok((answer, aggregator.config.decimals))
+}
+
+/// Read the resolved answers recorded in the aggregator answer history, oldest first
+pub fn read_history(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+    answer_history_info: &AccountInfo,
+) -> Result<Vec<Answer>, ProgramError> {
+    let aggregator = load_aggregator(program_id, aggregator_info)?;
+    let answer_history = aggregator.answer_history(answer_history_info)?;
+
+    answer_history.answers()
+}
+
+/// Read the answer a round had resolved to from the aggregator answer history
+pub fn read_answer_at_round(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+    answer_history_info: &AccountInfo,
+    round_id: u64,
+) -> Result<Answer, ProgramError> {
+    let aggregator = load_aggregator(program_id, aggregator_info)?;
+    let answer_history = aggregator.answer_history(answer_history_info)?;
+
+    let answer = answer_history
+        .answer_at_round(round_id)?
+        .ok_or(Error::RoundNotInHistory)?;
+
+    Ok(answer)
+}
+
+/// Read the time-weighted average price over the last `window_seconds`,
+/// integrating the answers recorded in the aggregator answer history
+pub fn read_twap(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+    answer_history_info: &AccountInfo,
+    clock: &Clock,
+    window_seconds: u64,
+) -> Result<u64, ProgramError> {
+    let aggregator = load_aggregator(program_id, aggregator_info)?;
+    aggregator.check_not_paused()?;
+    if aggregator.config.value_type != ValueType::U64 {
+        Err(Error::ValueTypeMismatch)?;
+    }
+    let answer_history = aggregator.answer_history(answer_history_info)?;
+
+    answer_history.twap(clock.unix_timestamp, window_seconds)
+}
+
+/// List the oracle accounts of the aggregator, from its oracle registry
+pub fn list_oracles(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+    oracle_registry_info: &AccountInfo,
+) -> Result<Vec<Pubkey>, ProgramError> {
+    let aggregator = load_aggregator(program_id, aggregator_info)?;
+    let oracle_registry = aggregator.oracle_registry(oracle_registry_info)?;
+
+    Ok(oracle_registry
+        .oracles()
+        .into_iter()
+        .map(|key| Pubkey::new_from_array(key.0))
+        .collect())
+}
+
+/// Seed of the program derived address that owns the reward faucet of an aggregator
+pub const FAUCET_OWNER_SEED: &[u8] = b"faucet_owner";
+
+/// Derive the reward faucet owner of an aggregator, returning the address and its bump seed
+pub fn find_faucet_owner_address(program_id: &Pubkey, aggregator: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[aggregator.as_ref(), FAUCET_OWNER_SEED], program_id)
+}
+
+/// Seed of the program derived aggregator address of a named feed
+pub const FEED_SEED: &[u8] = b"feed";
+
+/// Max length of a feed name, which is a seed of the feed address
+pub const MAX_FEED_NAME_LEN: usize = 32;
+
+/// Check that a feed name is a namespace & a name separated by a colon, like
+/// `btc:usd`, of lowercase ASCII letters, digits, `-`, `_`, `.` or `/`
+pub fn validate_feed_name(feed_name: &str) -> ProgramResult {
+    let is_valid_part = |part: &str| {
+        !part.is_empty()
+            && part
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"-_./".contains(&b))
+    };
+
+    let mut parts = feed_name.split(':');
+    let is_valid = match (parts.next(), parts.next(), parts.next()) {
+        (Some(namespace), Some(name), None) => is_valid_part(namespace) && is_valid_part(name),
+        _ => false,
+    };
+    if !is_valid || feed_name.len() > MAX_FEED_NAME_LEN {
+        Err(Error::InvalidFeedName)?;
+    }
+
+    Ok(())
+}
+
+/// Derive the aggregator address of the feed that `authority` named
+/// `feed_name`, returning the address and its bump seed. Names are scoped by
+/// their authority, so that nobody else can take a name first.
+pub fn find_feed_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    feed_name: &str,
+) -> Result<(Pubkey, u8), ProgramError> {
+    validate_feed_name(feed_name)?;
+
+    Ok(Pubkey::find_program_address(
+        &[FEED_SEED, authority.as_ref(), feed_name.as_bytes()],
+        program_id,
+    ))
+}
+
+/// Check that an aggregator account is the feed that `authority` named
+/// `feed_name` in the program, for consumers that locate feeds by name
+pub fn check_feed_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    feed_name: &str,
+    aggregator_info: &AccountInfo,
+) -> ProgramResult {
+    let (address, _) = find_feed_address(program_id, authority, feed_name)?;
+    if *aggregator_info.key != address || aggregator_info.owner != program_id {
+        Err(Error::FeedAddressMismatch)?;
+    }
+
+    Ok(())
+}
+
 // Export current sdk types for downstream users building with a different
 pub use solana_program;
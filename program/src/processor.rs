@@ -2,8 +2,13 @@
 
 use crate::{
     error::Error,
+    find_faucet_owner_address, find_feed_address, FAUCET_OWNER_SEED, FEED_SEED,
     instruction::{self, Instruction},
-    state::{Aggregator, AggregatorConfig, Authority, Oracle, Requester, Round, Submissions},
+    state::{
+        self, Aggregator, AggregatorConfig, AggregatorConfigUpdate, AnswerHistory, Authority,
+        Dispersion, Multisig, Oracle, OracleRegistry, PendingAction, PendingChange, PublicKey,
+        Requester, Round, Submission, SubmissionStatus, Submissions,
+    },
 };
 
 // use spl_token::state;
@@ -12,24 +17,44 @@ use solana_program::{
     clock::Clock,
     entrypoint::ProgramResult,
     msg,
-    program::invoke_signed,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
+    system_instruction,
     sysvar::{rent::Rent, Sysvar},
 };
 
-use crate::borsh_state::{BorshState, InitBorshState};
+use crate::borsh_state::{check_owner, BorshState, InitBorshState};
 
 use borsh::BorshDeserialize;
 
-struct Accounts<'a>(&'a [AccountInfo<'a>]);
+struct Accounts<'a> {
+    program_id: Pubkey,
+    infos: &'a [AccountInfo<'a>],
+}
 
 impl<'a> Accounts<'a> {
     fn get(&self, i: usize) -> Result<&'a AccountInfo<'a>, ProgramError> {
         // fn get(&self, i: usize) -> Result<&AccountInfo, ProgramError> {
         // &accounts[input.token.account as usize]
-        self.0.get(i).ok_or(ProgramError::NotEnoughAccountKeys)
+        self.infos.get(i).ok_or(ProgramError::NotEnoughAccountKeys)
+    }
+
+    /// get a state account (aggregator, oracle, requester, submissions), which
+    /// must be owned by this program
+    fn get_owned(&self, i: usize) -> Result<&'a AccountInfo<'a>, ProgramError> {
+        let info = self.get(i)?;
+        check_owner(info, &self.program_id)?;
+        Ok(info)
+    }
+
+    /// like `get_owned`, but the account may be omitted
+    fn get_owned_optional(&self, i: usize) -> Result<Option<&'a AccountInfo<'a>>, ProgramError> {
+        match self.infos.get(i) {
+            Some(_) => self.get_owned(i).map(Some),
+            None => Ok(None),
+        }
     }
 
     fn get_rent(&self, i: usize) -> Result<Rent, ProgramError> {
@@ -39,14 +64,47 @@ impl<'a> Accounts<'a> {
     fn get_clock(&self, i: usize) -> Result<Clock, ProgramError> {
         Clock::from_account_info(self.get(i)?)
     }
+
+    /// the reward faucet & receiver from `i`, if the token program is at `i`.
+    /// Otherwise the accounts from `i` may be for another purpose.
+    fn get_reward(
+        &self,
+        i: usize,
+    ) -> Result<Option<(RewardFaucet<'a>, &'a AccountInfo<'a>)>, ProgramError> {
+        match self.infos.get(i) {
+            Some(info) if *info.key == spl_token::id() => Ok(Some((
+                RewardFaucet {
+                    token_program: info,
+                    faucet: self.get(i + 1)?,
+                    faucet_owner: self.get(i + 2)?,
+                },
+                self.get(i + 3)?,
+            ))),
+            _ => Ok(None),
+        }
+    }
+
+    /// the accounts queuing a change from `i`, see `ChangeQueue`
+    fn get_change_queue(&self, i: usize) -> ChangeQueue<'a> {
+        ChangeQueue {
+            program_id: self.program_id,
+            pending_change: self.infos.get(i),
+            rent: self.infos.get(i + 1),
+            clock: self.infos.get(i + 2),
+        }
+    }
 }
 
 struct InitializeContext<'a> {
+    program_id: Pubkey,
     rent: Rent,
     aggregator: &'a AccountInfo<'a>,
     aggregator_owner: &'a AccountInfo<'a>,   // signed
     round_submissions: &'a AccountInfo<'a>,  // belongs_to: aggregator
     answer_submissions: &'a AccountInfo<'a>, // belongs_to: aggregator
+    oracle_registry: &'a AccountInfo<'a>,    // belongs_to: aggregator
+    /// creates the aggregator account of a named feed
+    feed: Option<FeedAccount<'a>>,
 
     config: AggregatorConfig,
 }
@@ -57,8 +115,28 @@ impl<'a> InitializeContext<'a> {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        self.init_submissions(self.round_submissions)?;
-        self.init_submissions(self.answer_submissions)?;
+        self.config.validate(None)?;
+
+        let feed_bump_seed = match &self.feed {
+            Some(feed) => feed.create(
+                &self.program_id,
+                &self.rent,
+                self.aggregator,
+                self.aggregator_owner.key,
+            )?,
+            None => 0,
+        };
+
+        // the submissions accounts are allocated with room for `max_submissions`
+        let round_submissions = Submissions::init(self.round_submissions, &self.rent)?;
+        let answer_submissions = Submissions::init(self.answer_submissions, &self.rent)?;
+        let capacity = round_submissions.capacity();
+        if answer_submissions.capacity() != capacity {
+            return Err(Error::SubmissionsCapacityMismatch)?;
+        }
+        if self.config.max_submissions as usize > capacity {
+            return Err(Error::MaxSubmissionsAboveCapacity)?;
+        }
 
         let mut aggregator = Aggregator::init_uninitialized(self.aggregator)?;
         aggregator.is_initialized = true;
@@ -68,23 +146,93 @@ impl<'a> InitializeContext<'a> {
         aggregator.round_submissions = self.round_submissions.into();
         aggregator.answer_submissions = self.answer_submissions.into();
 
+        OracleRegistry::init(self.oracle_registry, &self.rent, self.aggregator.into())?;
+        aggregator.oracle_registry = self.oracle_registry.into();
+
+        let (_, faucet_bump_seed) =
+            find_faucet_owner_address(&self.program_id, self.aggregator.key);
+        aggregator.faucet_bump_seed = faucet_bump_seed;
+        aggregator.submissions_capacity = capacity as u8;
+
+        if let Some(feed) = &self.feed {
+            aggregator.feed_name[..feed.name.len()].copy_from_slice(feed.name.as_bytes());
+            aggregator.feed_bump_seed = feed_bump_seed;
+            aggregator.feed_authority = self.aggregator_owner.into();
+        }
+
         aggregator.save_exempt(self.aggregator, &self.rent)?;
 
         Ok(())
     }
+}
 
-    fn init_submissions(&self, account: &AccountInfo) -> ProgramResult {
-        let mut submissions = Submissions::init_uninitialized(account)?;
-        submissions.is_initialized = true;
-        submissions.save_exempt(account, &self.rent)?;
+/// The program derived aggregator account of a named feed, to be created
+struct FeedAccount<'a> {
+    name: String,
+    payer: &'a AccountInfo<'a>, // signed, write
+    system_program: &'a AccountInfo<'a>,
+}
 
-        Ok(())
+impl<'a> FeedAccount<'a> {
+    /// create the rent exempt aggregator account at the address derived from
+    /// the authority & the feed name, returning its bump seed
+    fn create(
+        &self,
+        program_id: &Pubkey,
+        rent: &Rent,
+        aggregator: &'a AccountInfo<'a>,
+        authority: &Pubkey,
+    ) -> Result<u8, ProgramError> {
+        let (address, bump_seed) = find_feed_address(program_id, authority, &self.name)?;
+        if address != *aggregator.key {
+            return Err(Error::FeedAddressMismatch)?;
+        }
+
+        let space = Aggregator::default().pack()?.len();
+        let seeds: &[&[u8]] = &[
+            FEED_SEED,
+            authority.as_ref(),
+            self.name.as_bytes(),
+            &[bump_seed],
+        ];
+
+        // `create_account` fails if the address holds lamports already, so that
+        // anyone could block a feed by sending some. top them up instead.
+        let lamports = rent
+            .minimum_balance(space)
+            .saturating_sub(aggregator.lamports());
+        if lamports > 0 {
+            invoke(
+                &system_instruction::transfer(self.payer.key, aggregator.key, lamports),
+                &[
+                    self.payer.clone(),
+                    aggregator.clone(),
+                    self.system_program.clone(),
+                ],
+            )?;
+        }
+
+        invoke_signed(
+            &system_instruction::allocate(aggregator.key, space as u64),
+            &[aggregator.clone(), self.system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(aggregator.key, program_id),
+            &[aggregator.clone(), self.system_program.clone()],
+            &[seeds],
+        )?;
+        check_owner(aggregator, program_id)?;
+
+        Ok(bump_seed)
     }
 }
 
 struct ConfigureContext<'a> {
     aggregator: &'a AccountInfo<'a>,
     aggregator_owner: &'a AccountInfo<'a>,
+    signers: &'a [AccountInfo<'a>],
+    queue: ChangeQueue<'a>,
 
     config: AggregatorConfig,
 }
@@ -92,8 +240,46 @@ struct ConfigureContext<'a> {
 impl<'a> ConfigureContext<'a> {
     fn process(&self) -> ProgramResult {
         let mut aggregator = Aggregator::load_initialized(&self.aggregator)?;
-        aggregator.authorize(self.aggregator_owner)?;
-        aggregator.config = self.config.clone();
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
+
+        if aggregator.is_timelocked() {
+            return self.queue.enqueue_config(
+                self.aggregator,
+                &mut aggregator,
+                self.config.clone(),
+            );
+        }
+
+        aggregator.set_config(self.config.clone())?;
+        aggregator.save(self.aggregator)?;
+
+        Ok(())
+    }
+}
+
+struct UpdateConfigContext<'a> {
+    aggregator: &'a AccountInfo<'a>,
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
+    queue: ChangeQueue<'a>,
+
+    update: AggregatorConfigUpdate,
+}
+
+impl<'a> UpdateConfigContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
+
+        if aggregator.is_timelocked() {
+            return self.queue.enqueue_update(
+                self.aggregator,
+                &mut aggregator,
+                self.update.clone(),
+            );
+        }
+
+        aggregator.set_config(self.update.apply(&aggregator.config))?;
         aggregator.save(self.aggregator)?;
 
         Ok(())
@@ -104,845 +290,5289 @@ struct AddOracleContext<'a> {
     rent: Rent,
     aggregator: &'a AccountInfo<'a>,
     aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
     oracle: &'a AccountInfo<'a>,
     oracle_owner: &'a AccountInfo<'a>,
+    oracle_registry: &'a AccountInfo<'a>, // write
+    queue: ChangeQueue<'a>,
 
     description: [u8; 32],
 }
 
 impl<'a> AddOracleContext<'a> {
     fn process(&self) -> ProgramResult {
-        // Note: there can in fact be more oracles than max_submissions
-        let aggregator = Aggregator::load_initialized(self.aggregator)?;
+        // Note: there can in fact be more oracles than max_submissions, up to
+        // `max_oracles`
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
         msg!("loaded aggregator");
-        aggregator.authorize(self.aggregator_owner)?;
-
-        let mut oracle = Oracle::init_uninitialized(self.oracle)?;
-        msg!("loaded oracle");
-        oracle.is_initialized = true;
-        oracle.description = self.description;
-        oracle.owner = self.oracle_owner.into();
-        oracle.aggregator = self.aggregator.into();
-        oracle.save_exempt(self.oracle, &self.rent)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
+
+        if aggregator.is_timelocked() {
+            // the oracle is initialized once the change is executed
+            Oracle::init_uninitialized(self.oracle)?;
+            let change = PendingChange {
+                action: PendingAction::AddOracle,
+                oracle: self.oracle.into(),
+                oracle_owner: self.oracle_owner.into(),
+                description: self.description,
+                ..PendingChange::default()
+            };
+            return self.queue.enqueue(self.aggregator, &mut aggregator, change);
+        }
 
-        Ok(())
+        add_oracle(
+            &self.rent,
+            self.aggregator,
+            &aggregator,
+            self.oracle,
+            self.oracle_owner.into(),
+            self.description,
+            self.oracle_registry,
+        )
     }
 }
 
+/// initialize an oracle of the aggregator, and register it
+fn add_oracle<'a>(
+    rent: &Rent,
+    aggregator_info: &'a AccountInfo<'a>,
+    aggregator: &Aggregator,
+    oracle_info: &'a AccountInfo<'a>,
+    oracle_owner: PublicKey,
+    description: [u8; 32],
+    oracle_registry_info: &AccountInfo,
+) -> ProgramResult {
+    let mut oracle = Oracle::init_uninitialized(oracle_info)?;
+    msg!("loaded oracle");
+    oracle.is_initialized = true;
+    oracle.description = description;
+    oracle.owner = oracle_owner;
+    oracle.aggregator = aggregator_info.into();
+    oracle.weight = 1;
+    oracle.save_exempt(oracle_info, rent)?;
+
+    aggregator
+        .oracle_registry(oracle_registry_info)?
+        .add(oracle_info.into(), aggregator.config.oracles_cap())?;
+
+    Ok(())
+}
+
 struct RemoveOracleContext<'a> {
+    program_id: Pubkey,
     aggregator: &'a AccountInfo<'a>,
     aggregator_owner: &'a AccountInfo<'a>, // signed
-    oracle: &'a AccountInfo<'a>,
+    signers: &'a [AccountInfo<'a>],
+    oracle: &'a AccountInfo<'a>,          // write
+    oracle_registry: &'a AccountInfo<'a>, // write
+    destination: &'a AccountInfo<'a>,     // write
+
+    /// settles the outstanding rewards of the oracle, if any
+    reward: Option<(RewardFaucet<'a>, &'a AccountInfo<'a>)>,
+    queue: ChangeQueue<'a>,
 }
 
 impl<'a> RemoveOracleContext<'a> {
     fn process(&self) -> ProgramResult {
-        let aggregator = Aggregator::load_initialized(self.aggregator)?;
-        aggregator.authorize(self.aggregator_owner)?;
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
+
+        if aggregator.is_timelocked() {
+            let oracle = Oracle::load_initialized(self.oracle)?;
+            oracle.check_aggregator(self.aggregator)?;
+            let change = PendingChange {
+                action: PendingAction::RemoveOracle,
+                oracle: self.oracle.into(),
+                ..PendingChange::default()
+            };
+            return self.queue.enqueue(self.aggregator, &mut aggregator, change);
+        }
 
-        let oracle = Oracle::load_initialized(self.oracle)?;
-        if oracle.aggregator.0 != self.aggregator.key.to_bytes() {
-            return Err(Error::AggregatorMismatch)?;
+        remove_oracle(
+            &self.program_id,
+            self.aggregator,
+            &aggregator,
+            self.oracle,
+            self.oracle_registry,
+            self.destination,
+            self.reward.as_ref(),
+        )
+    }
+}
+
+/// unregister & close an oracle of the aggregator, settling its outstanding
+/// rewards
+fn remove_oracle<'a>(
+    program_id: &Pubkey,
+    aggregator_info: &AccountInfo,
+    aggregator: &Aggregator,
+    oracle_info: &'a AccountInfo<'a>,
+    oracle_registry_info: &AccountInfo,
+    destination: &AccountInfo,
+    reward: Option<&(RewardFaucet<'a>, &'a AccountInfo<'a>)>,
+) -> ProgramResult {
+    let oracle = Oracle::load_initialized(oracle_info)?;
+    if oracle.aggregator.0 != aggregator_info.key.to_bytes() {
+        Err(Error::AggregatorMismatch)?;
+    }
+
+    if oracle.withdrawable > 0 {
+        let (faucet, receiver) = reward.ok_or(Error::OutstandingWithdrawable)?;
+        faucet.check(program_id, aggregator_info, aggregator)?;
+
+        // the rewards are owed to the oracle owner, so they may only go to
+        // one of its token accounts.
+        let receiver_account = spl_token::state::Account::unpack(&receiver.data.borrow())
+            .map_err(|_| Error::InvalidReceiver)?;
+        if receiver_account.owner.to_bytes() != oracle.owner.0 {
+            Err(Error::InvalidReceiver)?;
         }
 
-        // Zero out the oracle account memory. This allows reuse or reclaim.
-        // Note: will wipe out withdrawable balance on this oracle. Too bad.
-        Oracle::default().save(self.oracle)?;
+        faucet.transfer(aggregator_info, aggregator, receiver, oracle.withdrawable)?;
+    }
+
+    aggregator
+        .oracle_registry(oracle_registry_info)?
+        .remove(&oracle_info.into())?;
+
+    close_account(oracle_info, destination)?;
+
+    Ok(())
+}
+
+/// The accounts queuing a change of a timelocked aggregator, passed after the
+/// accounts of the change. They may be omitted if the aggregator isn't
+/// timelocked.
+struct ChangeQueue<'a> {
+    program_id: Pubkey,
+    pending_change: Option<&'a AccountInfo<'a>>, // write
+    rent: Option<&'a AccountInfo<'a>>,
+    clock: Option<&'a AccountInfo<'a>>,
+}
+
+impl<'a> ChangeQueue<'a> {
+    /// initialize the pending change account with `change`, executable once
+    /// the timelock delay of the aggregator has elapsed
+    fn enqueue(
+        &self,
+        aggregator_info: &'a AccountInfo<'a>,
+        aggregator: &mut Aggregator,
+        mut change: PendingChange,
+    ) -> ProgramResult {
+        let (pending_change, rent, clock) = match (self.pending_change, self.rent, self.clock) {
+            (Some(pending_change), Some(rent), Some(clock)) => (pending_change, rent, clock),
+            _ => return Err(ProgramError::NotEnoughAccountKeys),
+        };
+        check_owner(pending_change, &self.program_id)?;
+        let rent = Rent::from_account_info(rent)?;
+        let clock = Clock::from_account_info(clock)?;
+
+        PendingChange::init_uninitialized(pending_change)?;
+        change.is_initialized = true;
+        change.aggregator = aggregator_info.into();
+        change.eta = clock.slot.saturating_add(aggregator.config.timelock_delay);
+        change.save_exempt(pending_change, &rent)?;
+
+        aggregator.pending_changes = aggregator
+            .pending_changes
+            .checked_add(1)
+            .ok_or(ProgramError::InvalidArgument)?;
+        aggregator.save(aggregator_info)?;
 
         Ok(())
     }
+
+    /// queue a new config, failing early if it could not be set
+    fn enqueue_config(
+        &self,
+        aggregator_info: &'a AccountInfo<'a>,
+        aggregator: &mut Aggregator,
+        config: AggregatorConfig,
+    ) -> ProgramResult {
+        aggregator.clone().set_config(config.clone())?;
+
+        let change = PendingChange {
+            action: PendingAction::Configure,
+            config,
+            ..PendingChange::default()
+        };
+        self.enqueue(aggregator_info, aggregator, change)
+    }
+
+    /// queue a config update, applied to the config as it is at execution,
+    /// failing early if it could not be applied to the current one
+    fn enqueue_update(
+        &self,
+        aggregator_info: &'a AccountInfo<'a>,
+        aggregator: &mut Aggregator,
+        update: AggregatorConfigUpdate,
+    ) -> ProgramResult {
+        aggregator
+            .clone()
+            .set_config(update.apply(&aggregator.config))?;
+
+        let change = PendingChange {
+            action: PendingAction::UpdateConfig,
+            update,
+            ..PendingChange::default()
+        };
+        self.enqueue(aggregator_info, aggregator, change)
+    }
 }
 
-struct AddRequesterContext<'a> {
-    rent: Rent,
-    aggregator: &'a AccountInfo<'a>,
+struct ExecutePendingContext<'a> {
+    clock: Clock,
+    aggregator: &'a AccountInfo<'a>,       // write
     aggregator_owner: &'a AccountInfo<'a>, // signed
-    requester: &'a AccountInfo<'a>,
-    requester_owner: &'a AccountInfo<'a>,
+    signers: &'a [AccountInfo<'a>],
+    pending_change: &'a AccountInfo<'a>, // write
+    destination: &'a AccountInfo<'a>,    // write
 
-    description: [u8; 32],
+    /// the instruction's accounts, from which those of the change are taken
+    accounts: Accounts<'a>,
 }
 
-impl<'a> AddRequesterContext<'a> {
+impl<'a> ExecutePendingContext<'a> {
     fn process(&self) -> ProgramResult {
-        let aggregator = Aggregator::load_initialized(self.aggregator)?;
-        msg!("loaded aggregator");
-        aggregator.authorize(self.aggregator_owner)?;
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
 
-        let mut requester = Requester::init_uninitialized(self.requester)?;
-        msg!("loaded requester");
-        requester.is_initialized = true;
-        requester.description = self.description;
-        requester.owner = self.requester_owner.into();
-        requester.aggregator = self.aggregator.into();
-        requester.save_exempt(self.requester, &self.rent)?;
+        let change = PendingChange::load_initialized(self.pending_change)?;
+        change.check_aggregator(self.aggregator)?;
+        change.check_ready(self.clock.slot)?;
+
+        match change.action {
+            PendingAction::Configure => {
+                aggregator.set_config(change.config.clone())?;
+            }
+            PendingAction::AddOracle => {
+                let oracle = self.accounts.get_owned(6)?;
+                change.check_oracle(oracle)?;
+                add_oracle(
+                    &self.accounts.get_rent(5)?,
+                    self.aggregator,
+                    &aggregator,
+                    oracle,
+                    change.oracle_owner,
+                    change.description,
+                    self.accounts.get_owned(7)?,
+                )?;
+            }
+            PendingAction::RemoveOracle => {
+                let oracle = self.accounts.get_owned(5)?;
+                change.check_oracle(oracle)?;
+                remove_oracle(
+                    &self.accounts.program_id,
+                    self.aggregator,
+                    &aggregator,
+                    oracle,
+                    self.accounts.get_owned(6)?,
+                    self.accounts.get(7)?,
+                    self.accounts.get_reward(8)?.as_ref(),
+                )?;
+            }
+            PendingAction::SetOracleWeight => {
+                let oracle = self.accounts.get_owned(5)?;
+                change.check_oracle(oracle)?;
+                set_oracle_weight(self.aggregator, oracle, change.weight)?;
+            }
+            PendingAction::UpdateConfig => {
+                aggregator.set_config(change.update.apply(&aggregator.config))?;
+            }
+        }
+
+        aggregator.pending_changes = aggregator.pending_changes.saturating_sub(1);
+        aggregator.save(self.aggregator)?;
+
+        close_account(self.pending_change, self.destination)?;
 
         Ok(())
     }
 }
 
-struct RemoveRequesterContext<'a> {
-    aggregator: &'a AccountInfo<'a>,
+struct CancelPendingContext<'a> {
+    aggregator: &'a AccountInfo<'a>,       // write
     aggregator_owner: &'a AccountInfo<'a>, // signed
-    requester: &'a AccountInfo<'a>,
+    signers: &'a [AccountInfo<'a>],
+    pending_change: &'a AccountInfo<'a>, // write
+    destination: &'a AccountInfo<'a>,    // write
 }
 
-impl<'a> RemoveRequesterContext<'a> {
+impl<'a> CancelPendingContext<'a> {
     fn process(&self) -> ProgramResult {
-        let aggregator = Aggregator::load_initialized(self.aggregator)?;
-        aggregator.authorize(self.aggregator_owner)?;
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
 
-        let requester = Requester::load_initialized(self.requester)?;
-        if requester.aggregator.0 != self.aggregator.key.to_bytes() {
-            return Err(Error::AggregatorMismatch)?;
-        }
+        let change = PendingChange::load_initialized(self.pending_change)?;
+        change.check_aggregator(self.aggregator)?;
+
+        aggregator.pending_changes = aggregator.pending_changes.saturating_sub(1);
+        aggregator.save(self.aggregator)?;
 
-        // Zero out the requester account memory. This allows reuse or reclaim.
-        Requester::default().save(self.requester)?;
+        close_account(self.pending_change, self.destination)?;
 
         Ok(())
     }
 }
 
-struct RequestRoundContext<'a> {
-    clock: Clock,
+struct SetOracleWeightContext<'a> {
     aggregator: &'a AccountInfo<'a>,
-    round_submissions: &'a AccountInfo<'a>,
-    requester: &'a AccountInfo<'a>,
-    requester_owner: &'a AccountInfo<'a>, // signed
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
+    oracle: &'a AccountInfo<'a>, // write
+    queue: ChangeQueue<'a>,
+
+    weight: u32,
 }
 
-impl<'a> RequestRoundContext<'a> {
+impl<'a> SetOracleWeightContext<'a> {
     fn process(&self) -> ProgramResult {
-        let now = self.clock.slot;
         let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
-        let mut requester = Requester::load_initialized(self.requester)?;
-        requester.authorize(self.requester_owner)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
 
-        if requester.aggregator.0 != self.aggregator.key.to_bytes() {
-            return Err(Error::AggregatorMismatch)?;
+        // a weighted median over weights that are all 0 has no answer
+        if self.weight == 0 {
+            return Err(Error::ZeroOracleWeight.into());
         }
 
-        // TODO: do we need to pass RoundId from FluxAggregator.ts?
-        if aggregator.round.id < requester.allow_start_round {
-            return Err(Error::RequesterNewRoundCooldown)?;
+        if aggregator.is_timelocked() {
+            let oracle = Oracle::load_initialized(self.oracle)?;
+            oracle.check_aggregator(self.aggregator)?;
+            let change = PendingChange {
+                action: PendingAction::SetOracleWeight,
+                oracle: self.oracle.into(),
+                weight: self.weight,
+                ..PendingChange::default()
+            };
+            return self.queue.enqueue(self.aggregator, &mut aggregator, change);
         }
 
-        // request a new round and update the aggregator
-        aggregator.round = Round {
-            id: aggregator.round.id + 1, // increment to next round
-            created_at: now,
-            updated_at: 0,
-        };
+        set_oracle_weight(self.aggregator, self.oracle, self.weight)
+    }
+}
 
-        // zero the submissions of the current round
-        let submissions = Submissions {
-            is_initialized: true,
-            data: Default::default(),
-        };
-        submissions.save(self.round_submissions)?;
+fn set_oracle_weight(
+    aggregator_info: &AccountInfo,
+    oracle_info: &AccountInfo,
+    weight: u32,
+) -> ProgramResult {
+    let mut oracle = Oracle::load_initialized(oracle_info)?;
+    oracle.check_aggregator(aggregator_info)?;
 
-        // requester can start new round after `requester_restart_delay` rounds
-        requester.allow_start_round = aggregator.round.id + (aggregator.config.requester_restart_delay as u64);
+    oracle.weight = weight;
+    oracle.save(oracle_info)?;
 
+    Ok(())
+}
+
+struct ProposeOwnerContext<'a> {
+    aggregator: &'a AccountInfo<'a>,
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
+    pending_owner: &'a AccountInfo<'a>,
+}
+
+impl<'a> ProposeOwnerContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
+
+        aggregator.pending_owner = self.pending_owner.into();
         aggregator.save(self.aggregator)?;
-        requester.save(self.requester)?;
 
         Ok(())
     }
 }
 
-struct SubmitContext<'a> {
-    clock: Clock,
-    aggregator: &'a AccountInfo<'a>, // write
-    round_submissions: &'a AccountInfo<'a>, // write
-    answer_submissions: &'a AccountInfo<'a>, // write
-    oracle: &'a AccountInfo<'a>, // write
-    oracle_owner: &'a AccountInfo<'a>, // signed
-
-    // NOTE: 5.84942*10^11 years even if 1 sec per round. don't bother with handling wrapparound.
-    round_id: u64,
-    value: u64,
+struct AcceptOwnershipContext<'a> {
+    aggregator: &'a AccountInfo<'a>,
+    pending_owner: &'a AccountInfo<'a>, // signed
 }
 
-impl<'a> SubmitContext<'a> {
+impl<'a> AcceptOwnershipContext<'a> {
     fn process(&self) -> ProgramResult {
         let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
-        let mut oracle = Oracle::load_initialized(self.oracle)?;
-        oracle.authorize(self.oracle_owner)?;
 
-        if oracle.aggregator.0 != self.aggregator.key.to_bytes() {
-            return Err(Error::AggregatorMismatch)?;
+        if !self.pending_owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
         }
 
-        // oracle starts a new round
-        if self.round_id == aggregator.round.id + 1 {
-            self.start_new_round(&mut aggregator, &mut oracle)?;
+        if !aggregator.has_pending_owner() {
+            return Err(Error::NoPendingOwner)?;
         }
 
-        // only allowed to submit in the current round (or a new round that just
-        // got started)
-        if self.round_id != aggregator.round.id {
-            return Err(Error::InvalidRoundID)?;
+        if aggregator.pending_owner.0 != self.pending_owner.key.to_bytes() {
+            return Err(Error::PendingOwnerMismatch)?;
         }
 
-        self.submit(&mut aggregator)?;
-
-        // credit oracle for submission
-        oracle.withdrawable = oracle
-            .withdrawable
-            .checked_add(aggregator.config.reward_amount)
-            .ok_or(Error::RewardsOverflow)?;
-
+        aggregator.owner = aggregator.pending_owner;
+        aggregator.pending_owner = PublicKey::default();
+        // the new owner takes over from any multisig admin
+        aggregator.admin = Multisig::default();
         aggregator.save(self.aggregator)?;
-        oracle.save(self.oracle)?;
 
         Ok(())
     }
+}
 
-    /// push oracle answer to the current round. update answer if min submissions
-    /// had been satisfied.
-    fn submit(&self, aggregator: &mut Aggregator) -> ProgramResult {
-        let now = self.clock.slot;
+struct CancelOwnershipTransferContext<'a> {
+    aggregator: &'a AccountInfo<'a>,
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
+}
 
-        let mut round_submissions = aggregator.round_submissions(self.round_submissions)?;
+impl<'a> CancelOwnershipTransferContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
 
-        let (i, submission) = round_submissions
-            .data
-            .iter_mut()
-            .enumerate()
-            .find(|(_i, s)| {
-                // either finds a new spot to put the submission, or find a spot
-                // that the oracle previously submitted to.
-                return !s.is_initialized() || s.oracle == self.oracle.key.to_bytes();
-            })
-            .ok_or(Error::MaxSubmissionsReached)?;
+        if !aggregator.has_pending_owner() {
+            return Err(Error::NoPendingOwner)?;
+        }
 
-        let count = i + 1;
+        aggregator.pending_owner = PublicKey::default();
+        aggregator.save(self.aggregator)?;
 
-        if count > aggregator.config.max_submissions as usize {
-            return Err(Error::MaxSubmissionsReached)?;
-        }
+        Ok(())
+    }
+}
 
-        if submission.is_initialized() {
-            return Err(Error::OracleAlreadySubmitted)?;
-        }
+struct SetPausedContext<'a> {
+    aggregator: &'a AccountInfo<'a>,
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
 
-        if aggregator.round.created_at == 0 {
-            aggregator.round.created_at = now;
-        }
-        aggregator.round.updated_at = now;
+    paused: bool,
+}
 
-        submission.updated_at = now;
-        submission.value = self.value;
-        submission.oracle = self.oracle.key.to_bytes();
+impl<'a> SetPausedContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
 
-        // this line is for later, but put here to deal with borrow check...
-        let new_submission = *submission;
+        aggregator.paused = self.paused;
+        aggregator.save(self.aggregator)?;
 
-        round_submissions.save(self.round_submissions)?;
+        Ok(())
+    }
+}
 
-        if count < aggregator.config.min_submissions as usize {
-            // not enough submissions to update answer. return now.
-            return Ok(());
-        }
+struct SetAdminMultisigContext<'a> {
+    aggregator: &'a AccountInfo<'a>,
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
 
-        // update answer if the new round reached min_submissions
-        let mut answer_submissions = aggregator.answer_submissions(self.answer_submissions)?;
-        let round = &aggregator.round;
-        let answer = &mut aggregator.answer;
+    admin: Multisig,
+}
 
-        if !answer.is_initialized() || round.id > answer.round_id {
-            // a new round had just been resolved. copy the current round's submissions over
-            answer.round_id = round.id;
-            answer.created_at = now;
-            answer.updated_at = now;
-            answer_submissions.data = round_submissions.data;
-        } else {
-            answer.updated_at = now;
-            answer_submissions.data[i] = new_submission;
-        }
+impl<'a> SetAdminMultisigContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
 
-        answer.median = answer_submissions.median()?;
-        answer_submissions.save(self.answer_submissions)?;
+        aggregator.admin = self.admin.clone();
+        aggregator.save(self.aggregator)?;
 
         Ok(())
     }
+}
 
-    fn start_new_round(&self, aggregator: &mut Aggregator, oracle: &mut Oracle) -> ProgramResult {
-        let now = self.clock.slot;
+struct SetOracleOwnerContext<'a> {
+    oracle: &'a AccountInfo<'a>,       // write
+    oracle_owner: &'a AccountInfo<'a>, // signed
+    new_owner: &'a AccountInfo<'a>,    // signed
+}
 
-        if aggregator.round.id < oracle.allow_start_round {
-            return Err(Error::OracleNewRoundCooldown)?;
-        }
+impl<'a> SetOracleOwnerContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let mut oracle = Oracle::load_initialized(self.oracle)?;
+        oracle.authorize(self.oracle_owner, &[])?;
 
-        // oracle should not be able to start a new round if current round’s created_at has not yet reached the number of round_timeout required
-        if now < aggregator.round.created_at + (aggregator.config.round_timeout as u64)  {
-            return Err(Error::OracleRoundTimeout)?;
+        // the new owner signs to prove it holds the key
+        if !self.new_owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
         }
 
-        aggregator.round = Round {
-            id: self.round_id,
-            created_at: now,
-            updated_at: 0,
-        };
-
-        // zero the submissions of the current round
-        let submissions = Submissions {
-            is_initialized: true,
-            data: Default::default(),
-        };
-        submissions.save(self.round_submissions)?;
-
-        // oracle can start new round after `restart_delay` rounds
-        oracle.allow_start_round = self.round_id + (aggregator.config.restart_delay as u64);
+        oracle.owner = self.new_owner.into();
+        oracle.save(self.oracle)?;
 
         Ok(())
     }
 }
 
-// Withdraw token from reward faucet to receiver account, deducting oracle's withdrawable credit.
-struct WithdrawContext<'a, 'b> {
-    token_program: &'a AccountInfo<'a>,
-    aggregator: &'a AccountInfo<'a>,
-    faucet: &'a AccountInfo<'a>,
-    faucet_owner: &'a AccountInfo<'a>, // program signed
-    oracle: &'a AccountInfo<'a>,
-    oracle_owner: &'a AccountInfo<'a>, // signed
-    receiver: &'a AccountInfo<'a>,
+struct AddRequesterContext<'a> {
+    rent: Rent,
+    aggregator: &'a AccountInfo<'a>,       // write
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
+    requester: &'a AccountInfo<'a>,
+    requester_owner: &'a AccountInfo<'a>,
 
-    faucet_owner_seed: &'b [u8],
+    description: [u8; 32],
 }
 
-impl<'a, 'b> WithdrawContext<'a, 'b> {
+impl<'a> AddRequesterContext<'a> {
     fn process(&self) -> ProgramResult {
-        let aggregator = Aggregator::load_initialized(self.aggregator)?;
-        let mut oracle = Oracle::load_initialized(self.oracle)?;
-        oracle.authorize(&self.oracle_owner)?;
-        oracle.check_aggregator(self.aggregator)?;
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        msg!("loaded aggregator");
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
 
-        if !aggregator
-            .config
-            .reward_token_account
-            .is_account(self.faucet)
-        {
-            return Err(Error::InvalidFaucet)?;
-        }
+        let mut requester = Requester::init_uninitialized(self.requester)?;
+        msg!("loaded requester");
+        requester.is_initialized = true;
+        requester.description = self.description;
+        requester.owner = self.requester_owner.into();
+        requester.aggregator = self.aggregator.into();
+        requester.save_exempt(self.requester, &self.rent)?;
 
-        if oracle.withdrawable == 0 {
-            return Err(Error::InsufficientWithdrawable)?;
-        }
+        aggregator.requesters = aggregator
+            .requesters
+            .checked_add(1)
+            .ok_or(ProgramError::InvalidArgument)?;
+        aggregator.save(self.aggregator)?;
 
-        let amount = oracle.withdrawable;
+        Ok(())
+    }
+}
 
-        oracle.withdrawable = 0;
-        oracle.save(self.oracle)?;
+struct RemoveRequesterContext<'a> {
+    aggregator: &'a AccountInfo<'a>,       // write
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
+    requester: &'a AccountInfo<'a>,   // write
+    destination: &'a AccountInfo<'a>, // write
+}
 
-        // The SPL Token ensures that faucet and receiver are the same type of token
-        let inx = spl_token::instruction::transfer(
-            self.token_program.key,
-            self.faucet.key,
-            self.receiver.key,
-            self.faucet_owner.key,
-            &[],
-            amount,
-        )?;
+impl<'a> RemoveRequesterContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
 
-        invoke_signed(
-            &inx,
-            &[
-                self.token_program.clone(),
-                self.faucet.clone(),
-                self.faucet_owner.clone(),
-                self.receiver.clone(),
-            ],
-            &[&[self.faucet_owner_seed]],
-        )?;
+        let requester = Requester::load_initialized(self.requester)?;
+        if requester.aggregator.0 != self.aggregator.key.to_bytes() {
+            return Err(Error::AggregatorMismatch)?;
+        }
+
+        aggregator.requesters = aggregator.requesters.saturating_sub(1);
+        aggregator.save(self.aggregator)?;
+
+        close_account(self.requester, self.destination)?;
 
         Ok(())
     }
 }
 
-/// Program state handler.
-pub struct Processor {}
+struct CloseAggregatorContext<'a> {
+    aggregator: &'a AccountInfo<'a>,       // write
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
+    round_submissions: &'a AccountInfo<'a>,  // write
+    answer_submissions: &'a AccountInfo<'a>, // write
+    oracle_registry: &'a AccountInfo<'a>,    // write
+    destination: &'a AccountInfo<'a>,        // write
 
-impl Processor {
-    pub fn process<'a>(
-        _program_id: &Pubkey,
-        accounts: &'a [AccountInfo<'a>],
-        input: &[u8],
-    ) -> ProgramResult {
-        let accounts = Accounts(accounts);
-        let instruction =
-            Instruction::try_from_slice(input).map_err(|_| ProgramError::InvalidInstructionData)?;
+    /// the instruction's accounts, from which the answer history & reward
+    /// faucet are taken, if the aggregator has them
+    accounts: Accounts<'a>,
+}
 
-        // match branches would increase stack frame, and hit the hard 4096
-        // frame limit. break the other branches into another function call, and
-        // mark it as never inline.
-        match instruction {
-            Instruction::Submit { round_id, value } => SubmitContext {
-                clock: accounts.get_clock(0)?,
-                aggregator: accounts.get(1)?,
-                round_submissions: accounts.get(2)?,
-                answer_submissions: accounts.get(3)?,
-                oracle: accounts.get(4)?,
-                oracle_owner: accounts.get(5)?,
-                round_id,
-                value,
-            }
-            .process(),
-            Instruction::Withdraw { faucet_owner_seed } => WithdrawContext {
-                token_program: accounts.get(0)?,
-                aggregator: accounts.get(1)?,
-                faucet: accounts.get(2)?,       // write
-                faucet_owner: accounts.get(3)?, // program signed
-                oracle: accounts.get(4)?,       // write
-                oracle_owner: accounts.get(5)?, // signed
-                receiver: accounts.get(6)?,     // write
+impl<'a> CloseAggregatorContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
 
-                faucet_owner_seed: &faucet_owner_seed[..],
-            }
-            .process(),
-            instruction => process2(instruction, accounts),
+        // oracles hold their own rewards & lamports, which are settled as they
+        // are removed.
+        let oracle_registry = aggregator.oracle_registry(self.oracle_registry)?;
+        if !oracle_registry.is_empty() {
+            return Err(Error::AggregatorHasOracles)?;
         }
-    }
-}
 
-#[inline(never)]
-fn process2(instruction: Instruction, accounts: Accounts) -> ProgramResult {
-    match instruction {
-        Instruction::Initialize { config } => InitializeContext {
-            rent: accounts.get_rent(0)?,
-            aggregator: accounts.get(1)?,
-            aggregator_owner: accounts.get(2)?,
-            round_submissions: accounts.get(3)?,
-            answer_submissions: accounts.get(4)?,
-            config,
+        // as would requesters & pending changes, which have no other way to
+        // be closed once the aggregator is gone.
+        if aggregator.requesters > 0 {
+            return Err(Error::AggregatorHasRequesters)?;
         }
-        .process(),
-        Instruction::Configure { config } => ConfigureContext {
-            aggregator: accounts.get(0)?,
-            aggregator_owner: accounts.get(1)?,
-            config,
+        if aggregator.pending_changes > 0 {
+            return Err(Error::AggregatorHasPendingChanges)?;
         }
-        .process(),
-        Instruction::AddOracle { description } => AddOracleContext {
-            rent: accounts.get_rent(0)?,
-            aggregator: accounts.get(1)?,
-            aggregator_owner: accounts.get(2)?,
-            oracle: accounts.get(3)?,
-            oracle_owner: accounts.get(4)?,
-            description,
+
+        aggregator.round_submissions(self.round_submissions)?;
+        aggregator.answer_submissions(self.answer_submissions)?;
+        let mut i = 6;
+        if aggregator.has_answer_history() {
+            let account = self.accounts.get(i)?;
+            aggregator.answer_history(account)?;
+            close_account(account, self.destination)?;
+            i += 1;
         }
-        .process(),
-        Instruction::RemoveOracle => RemoveOracleContext {
-            aggregator: accounts.get(0)?,
-            aggregator_owner: accounts.get(1)?,
-            oracle: accounts.get(2)?,
+
+        // the faucet is owned by the aggregator, so its remaining tokens would
+        // be locked once the aggregator is gone.
+        if aggregator.has_reward_faucet() {
+            let (faucet, receiver) = self
+                .accounts
+                .get_reward(i)?
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            faucet.check(&self.accounts.program_id, self.aggregator, &aggregator)?;
+            faucet.close(self.aggregator, &aggregator, receiver, self.destination)?;
         }
-        .process(),
-        instruction => process3(instruction, accounts),
+
+        close_account(self.round_submissions, self.destination)?;
+        close_account(self.answer_submissions, self.destination)?;
+        close_account(self.oracle_registry, self.destination)?;
+        close_account(self.aggregator, self.destination)?;
+
+        Ok(())
     }
 }
 
-#[inline(never)]
-fn process3(instruction: Instruction, accounts: Accounts) -> ProgramResult {
-    match instruction {
-        Instruction::RequestRound => RequestRoundContext {
-            clock: accounts.get_clock(0)?,
-            aggregator: accounts.get(1)?,
-            round_submissions: accounts.get(2)?,
-            requester: accounts.get(3)?,
-            requester_owner: accounts.get(4)?,
-        }
-        .process(),
-        Instruction::AddRequester { description } => AddRequesterContext {
-            rent: accounts.get_rent(0)?,
-            aggregator: accounts.get(1)?,
-            aggregator_owner: accounts.get(2)?,
-            requester: accounts.get(3)?,
-            requester_owner: accounts.get(4)?,
+struct RequestRoundContext<'a> {
+    clock: Clock,
+    aggregator: &'a AccountInfo<'a>,
+    round_submissions: &'a AccountInfo<'a>,
+    requester: &'a AccountInfo<'a>,
+    requester_owner: &'a AccountInfo<'a>, // signed
+}
 
-            description,
+impl<'a> RequestRoundContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        let mut requester = Requester::load_initialized(self.requester)?;
+        requester.authorize(self.requester_owner, &[])?;
+        aggregator.check_not_paused()?;
+
+        if requester.aggregator.0 != self.aggregator.key.to_bytes() {
+            return Err(Error::AggregatorMismatch)?;
         }
-        .process(),
-        Instruction::RemoveRequester => RemoveRequesterContext {
-            aggregator: accounts.get(0)?,
-            aggregator_owner: accounts.get(1)?,
-            requester: accounts.get(2)?,
+
+        // TODO: do we need to pass RoundId from FluxAggregator.ts?
+        if aggregator.round.id < requester.allow_start_round {
+            return Err(Error::RequesterNewRoundCooldown)?;
         }
-        .process(),
-        _ => Err(ProgramError::InvalidInstructionData),
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // request a new round and update the aggregator
+        // increment to next round
+        aggregator.round = Round::new(aggregator.round.id + 1, &self.clock);
 
-    use crate::instruction;
-    use crate::{borsh_utils, state::Submission};
-    use borsh::BorshSerialize;
-    use solana_program::sysvar;
+        // zero the submissions of the current round
+        aggregator.round_submissions(self.round_submissions)?.clear();
 
-    use solana_sdk::account::{create_account, Account};
+        // requester can start new round after `requester_restart_delay` rounds
+        requester.allow_start_round = aggregator.round.id + (aggregator.config.requester_restart_delay as u64);
 
-    fn process<'a>(
-        program_id: &Pubkey,
-        ix: instruction::Instruction,
-        accounts: &'a [AccountInfo<'a>],
-    ) -> ProgramResult {
-        let input = ix
-            .try_to_vec()
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-        Processor::process(&program_id, accounts, &input)
-    }
+        aggregator.save(self.aggregator)?;
+        requester.save(self.requester)?;
 
-    fn rent_sysvar() -> TSysAccount {
-        TSysAccount(sysvar::rent::id(), create_account(&Rent::default(), 42))
+        Ok(())
     }
+}
 
-    fn sysclock(time: i64) -> TSysAccount {
-        let mut clock = Clock::default();
-        clock.slot = time as u64;
-        TSysAccount(sysvar::clock::id(), create_account(&clock, 42))
-    }
+struct SubmitContext<'a> {
+    clock: Clock,
+    aggregator: &'a AccountInfo<'a>, // write
+    round_submissions: &'a AccountInfo<'a>, // write
+    answer_submissions: &'a AccountInfo<'a>, // write
+    oracle: &'a AccountInfo<'a>, // write
+    oracle_owner: &'a AccountInfo<'a>, // signed
+    answer_history: Option<&'a AccountInfo<'a>>, // write, if the aggregator has one
 
-    fn rent_exempt_balance(space: usize) -> u64 {
-        Rent::default().minimum_balance(space)
-    }
+    // NOTE: 5.84942*10^11 years even if 1 sec per round. don't bother with handling wrapparound.
+    round_id: u64,
+    value: i128,
+}
 
-    struct TAccount {
-        is_signer: bool,
-        pubkey: Pubkey,
-        account: Account,
+impl<'a> SubmitContext<'a> {
+    fn new(accounts: &Accounts<'a>, round_id: u64, value: i128) -> Result<Self, ProgramError> {
+        Ok(SubmitContext {
+            clock: accounts.get_clock(0)?,
+            aggregator: accounts.get_owned(1)?,
+            round_submissions: accounts.get_owned(2)?,
+            answer_submissions: accounts.get_owned(3)?,
+            oracle: accounts.get_owned(4)?,
+            oracle_owner: accounts.get(5)?,
+            answer_history: accounts.get_owned_optional(6)?,
+            round_id,
+            value,
+        })
     }
 
-    impl TAccount {
-        fn new(program_id: &Pubkey, is_signer: bool) -> TAccount {
-            TAccount {
-                is_signer,
-                pubkey: Pubkey::new_unique(),
-                account: Account::new(0, 0, &program_id),
-            }
+    fn process(&self) -> ProgramResult {
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        let mut oracle = Oracle::load_initialized(self.oracle)?;
+        oracle.authorize(self.oracle_owner, &[])?;
+
+        if oracle.aggregator.0 != self.aggregator.key.to_bytes() {
+            return Err(Error::AggregatorMismatch)?;
         }
 
-        fn new_rent_exempt(program_id: &Pubkey, space: usize, is_signer: bool) -> TAccount {
-            TAccount {
-                is_signer,
-                pubkey: Pubkey::new_unique(),
-                account: Account::new(rent_exempt_balance(space), space, &program_id),
-            }
+        aggregator.check_not_paused()?;
+        aggregator.config.value_type.check(self.value)?;
+
+        // oracle starts a new round
+        if self.round_id == aggregator.round.id + 1 {
+            self.start_new_round(&mut aggregator, &mut oracle)?;
         }
 
-        fn info(&mut self) -> AccountInfo {
-            AccountInfo::new(
-                &self.pubkey,
-                self.is_signer,
-                false,
-                &mut self.account.lamports,
-                &mut self.account.data,
-                &self.account.owner,
-                self.account.executable,
-                self.account.rent_epoch,
-            )
+        // only allowed to submit in the current round (or a new round that just
+        // got started)
+        if self.round_id != aggregator.round.id {
+            return Err(Error::InvalidRoundID)?;
+        }
+
+        let (status, updated) = self.submit(&mut aggregator, &oracle)?;
+        if updated {
+            self.record_answer(&aggregator)?;
+        }
+
+        // credit oracle for submission, unless it got excluded as an outlier
+        if status != SubmissionStatus::Excluded {
+            oracle.withdrawable = oracle
+                .withdrawable
+                .checked_add(aggregator.config.reward_amount)
+                .ok_or(Error::RewardsOverflow)?;
         }
+
+        aggregator.save(self.aggregator)?;
+        oracle.save(self.oracle)?;
+
+        Ok(())
     }
 
-    impl<'a> Into<AccountInfo<'a>> for &'a mut TAccount {
-        fn into(self) -> AccountInfo<'a> {
-            self.info()
+    /// push oracle answer to the current round. update answer if min submissions
+    /// had been satisfied, returning the submission's deviation status and
+    /// whether the answer got updated.
+    fn submit(
+        &self,
+        aggregator: &mut Aggregator,
+        oracle: &Oracle,
+    ) -> Result<(SubmissionStatus, bool), ProgramError> {
+        let now = self.clock.slot;
+        let now_timestamp = self.clock.unix_timestamp;
+
+        let round_submissions = aggregator.round_submissions(self.round_submissions)?;
+        let submissions = round_submissions.submissions()?;
+        let mut accepted = state::accepted(&submissions);
+        let status = aggregator.submission_status(self.value, &submissions);
+
+        // either finds the spot that the oracle previously submitted to, or the
+        // next new spot to put the submission.
+        let oracle_key = self.oracle.key.to_bytes();
+        let previous = submissions.iter().position(|s| s.oracle == oracle_key);
+        let i = previous.unwrap_or(submissions.len());
+
+        let count = i + 1;
+
+        if i >= round_submissions.capacity() || count > aggregator.config.max_submissions as usize {
+            return Err(Error::MaxSubmissionsReached)?;
+        }
+
+        if previous.is_some() {
+            return Err(Error::OracleAlreadySubmitted)?;
+        }
+
+        if aggregator.round.created_at == 0 {
+            aggregator.round.created_at = now;
+            aggregator.round.created_at_timestamp = now_timestamp;
+        }
+        aggregator.round.updated_at = now;
+        aggregator.round.updated_at_timestamp = now_timestamp;
+
+        let submission = Submission {
+            updated_at: now,
+            updated_at_timestamp: now_timestamp,
+            value: self.value,
+            oracle: oracle_key,
+            weight: oracle.weight,
+            status,
+        };
+        round_submissions.set(i, &submission)?;
+
+        match status {
+            SubmissionStatus::Accepted => {}
+            SubmissionStatus::Flagged => msg!("outlier submission flagged"),
+            SubmissionStatus::Excluded => {
+                // excluded values leave the answer as is
+                msg!("outlier submission excluded");
+                return Ok((status, false));
+            }
+        }
+
+        accepted.push(submission);
+        if accepted.len() < aggregator.config.min_submissions as usize {
+            // not enough submissions to update answer. return now.
+            return Ok((status, false));
+        }
+
+        // update answer if the new round reached min_submissions. the answer
+        // always covers the current round's submissions.
+        let answer_submissions = aggregator.answer_submissions(self.answer_submissions)?;
+
+        let value = aggregator.config.aggregation().aggregate(&accepted)?;
+        let dispersion = Dispersion::of(&accepted)?;
+        if aggregator.config.exceeds_max_dispersion(value, &dispersion) {
+            msg!("submissions too dispersed to resolve the round");
+            return Ok((status, false));
+        }
+
+        let round = &aggregator.round;
+        let answer = &mut aggregator.answer;
+
+        if !answer.is_initialized() || round.id > answer.round_id {
+            // a new round had just been resolved
+            answer.round_id = round.id;
+            answer.created_at = now;
+            answer.created_at_timestamp = now_timestamp;
         }
+        answer.updated_at = now;
+        answer.updated_at_timestamp = now_timestamp;
+
+        answer.submissions = accepted.len() as u8;
+        answer.method = aggregator.config.aggregation_method;
+        answer.signed_median = value;
+        answer.dispersion = dispersion;
+        answer.median = if aggregator.config.value_type.is_signed() {
+            0
+        } else {
+            answer.signed_median as u64
+        };
+        answer_submissions.copy_from(&round_submissions)?;
+
+        Ok((status, true))
     }
 
-    struct TSysAccount(Pubkey, Account);
-    impl<'a> Into<AccountInfo<'a>> for &'a mut TSysAccount {
-        fn into(self) -> AccountInfo<'a> {
-            AccountInfo::new(
-                &self.0,
-                false,
-                false,
-                &mut self.1.lamports,
-                &mut self.1.data,
-                &self.1.owner,
-                self.1.executable,
-                self.1.rent_epoch,
-            )
+    /// append the updated answer to the answer history, if the aggregator keeps one
+    #[inline(never)]
+    fn record_answer(&self, aggregator: &Aggregator) -> ProgramResult {
+        if !aggregator.has_answer_history() {
+            return Ok(());
         }
+
+        let account = self
+            .answer_history
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        aggregator
+            .answer_history(account)?
+            .record(&aggregator.answer)
     }
 
-    struct TAggregator {
-        aggregator: TAccount,
-        aggregator_owner: TAccount,
-        round_submissions: TAccount,
-        answer_submissions: TAccount,
-        config: AggregatorConfig,
+    fn start_new_round(&self, aggregator: &mut Aggregator, oracle: &mut Oracle) -> ProgramResult {
+        if aggregator.round.id < oracle.allow_start_round {
+            return Err(Error::OracleNewRoundCooldown)?;
+        }
+
+        // oracle should not be able to start a new round if current round’s created_at has not yet reached the number of round_timeout required
+        if !aggregator.is_round_timed_out(&self.clock) {
+            return Err(Error::OracleRoundTimeout)?;
+        }
+
+        aggregator.round = Round::new(self.round_id, &self.clock);
+
+        // zero the submissions of the current round
+        aggregator.round_submissions(self.round_submissions)?.clear();
+
+        // oracle can start new round after `restart_delay` rounds
+        oracle.allow_start_round = self.round_id + (aggregator.config.restart_delay as u64);
+
+        Ok(())
+    }
+}
+
+struct InitializeHistoryContext<'a> {
+    rent: Rent,
+    aggregator: &'a AccountInfo<'a>,       // write
+    aggregator_owner: &'a AccountInfo<'a>, // signed
+    signers: &'a [AccountInfo<'a>],
+    answer_history: &'a AccountInfo<'a>,   // write
+}
+
+impl<'a> InitializeHistoryContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let mut aggregator = Aggregator::load_initialized(self.aggregator)?;
+        aggregator.authorize(self.aggregator_owner, self.signers)?;
+
+        if aggregator.has_answer_history() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        AnswerHistory::init(self.answer_history, &self.rent, self.aggregator.into())?;
+
+        aggregator.answer_history = self.answer_history.into();
+        aggregator.save(self.aggregator)?;
+
+        Ok(())
+    }
+}
+
+/// The reward token account of an aggregator, and its program derived owner
+struct RewardFaucet<'a> {
+    token_program: &'a AccountInfo<'a>,
+    faucet: &'a AccountInfo<'a>,       // write
+    faucet_owner: &'a AccountInfo<'a>, // program signed
+}
+
+impl<'a> RewardFaucet<'a> {
+    fn check(
+        &self,
+        program_id: &Pubkey,
+        aggregator_info: &AccountInfo,
+        aggregator: &Aggregator,
+    ) -> ProgramResult {
+        // the faucet owner signs whatever program is invoked with it
+        if *self.token_program.key != spl_token::id() {
+            return Err(Error::InvalidTokenProgram)?;
+        }
+
+        if !aggregator
+            .config
+            .reward_token_account
+            .is_account(self.faucet)
+        {
+            return Err(Error::InvalidFaucet)?;
+        }
+
+        // the faucet owner is derived from the aggregator, so that an oracle
+        // cannot sign for the faucet of another aggregator.
+        let faucet_owner = Pubkey::create_program_address(
+            &[
+                aggregator_info.key.as_ref(),
+                FAUCET_OWNER_SEED,
+                &[aggregator.faucet_bump_seed],
+            ],
+            program_id,
+        )
+        .map_err(|_| Error::InvalidFaucetOwner)?;
+        if faucet_owner != *self.faucet_owner.key {
+            return Err(Error::InvalidFaucetOwner)?;
+        }
+
+        Ok(())
+    }
+
+    /// transfer `amount` of reward tokens to `receiver`, assuming `check` passed
+    fn transfer(
+        &self,
+        aggregator_info: &AccountInfo,
+        aggregator: &Aggregator,
+        receiver: &AccountInfo<'a>,
+        amount: u64,
+    ) -> ProgramResult {
+        let faucet_owner_seeds: &[&[u8]] = &[
+            aggregator_info.key.as_ref(),
+            FAUCET_OWNER_SEED,
+            &[aggregator.faucet_bump_seed],
+        ];
+
+        // The SPL Token ensures that faucet and receiver are the same type of token
+        let inx = spl_token::instruction::transfer(
+            self.token_program.key,
+            self.faucet.key,
+            receiver.key,
+            self.faucet_owner.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &inx,
+            &[
+                self.token_program.clone(),
+                self.faucet.clone(),
+                self.faucet_owner.clone(),
+                receiver.clone(),
+            ],
+            &[faucet_owner_seeds],
+        )?;
+
+        Ok(())
+    }
+
+    /// transfer the remaining reward tokens to `receiver`, and close the faucet
+    /// to `destination`, assuming `check` passed. A faucet that isn't a token
+    /// account of the faucet owner has nothing to close.
+    fn close(
+        &self,
+        aggregator_info: &AccountInfo,
+        aggregator: &Aggregator,
+        receiver: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        if *self.faucet.owner != spl_token::id() {
+            return Ok(());
+        }
+        let amount = match spl_token::state::Account::unpack(&self.faucet.data.borrow()) {
+            Ok(faucet) if faucet.owner == *self.faucet_owner.key => faucet.amount,
+            _ => return Ok(()),
+        };
+
+        if amount > 0 {
+            self.transfer(aggregator_info, aggregator, receiver, amount)?;
+        }
+
+        let faucet_owner_seeds: &[&[u8]] = &[
+            aggregator_info.key.as_ref(),
+            FAUCET_OWNER_SEED,
+            &[aggregator.faucet_bump_seed],
+        ];
+
+        let inx = spl_token::instruction::close_account(
+            self.token_program.key,
+            self.faucet.key,
+            destination.key,
+            self.faucet_owner.key,
+            &[],
+        )?;
+
+        invoke_signed(
+            &inx,
+            &[
+                self.token_program.clone(),
+                self.faucet.clone(),
+                destination.clone(),
+                self.faucet_owner.clone(),
+            ],
+            &[faucet_owner_seeds],
+        )?;
+
+        Ok(())
+    }
+}
+
+/// move the lamports of a program account to `destination` and zero its data,
+/// so that the account is reclaimed by the runtime.
+fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    if account.key == destination.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let lamports = account.lamports();
+    **destination.lamports.borrow_mut() = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::InvalidArgument)?;
+    **account.lamports.borrow_mut() = 0;
+
+    let mut data = account.data.borrow_mut();
+    for byte in data.iter_mut() {
+        *byte = 0;
+    }
+
+    Ok(())
+}
+
+// Withdraw token from reward faucet to receiver account, deducting oracle's withdrawable credit.
+struct WithdrawContext<'a> {
+    program_id: Pubkey,
+    aggregator: &'a AccountInfo<'a>,
+    faucet: RewardFaucet<'a>,
+    oracle: &'a AccountInfo<'a>,
+    oracle_owner: &'a AccountInfo<'a>, // signed
+    receiver: &'a AccountInfo<'a>,
+}
+
+impl<'a> WithdrawContext<'a> {
+    fn process(&self) -> ProgramResult {
+        let aggregator = Aggregator::load_initialized(self.aggregator)?;
+        let mut oracle = Oracle::load_initialized(self.oracle)?;
+        oracle.authorize(self.oracle_owner, &[])?;
+        oracle.check_aggregator(self.aggregator)?;
+
+        self.faucet
+            .check(&self.program_id, self.aggregator, &aggregator)?;
+
+        if oracle.withdrawable == 0 {
+            return Err(Error::InsufficientWithdrawable)?;
+        }
+
+        let amount = oracle.withdrawable;
+
+        oracle.withdrawable = 0;
+        oracle.save(self.oracle)?;
+
+        self.faucet
+            .transfer(self.aggregator, &aggregator, self.receiver, amount)?;
+
+        Ok(())
+    }
+}
+
+/// Program state handler.
+pub struct Processor {}
+
+impl Processor {
+    pub fn process<'a>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        input: &[u8],
+    ) -> ProgramResult {
+        let accounts = Accounts {
+            program_id: *program_id,
+            infos: accounts,
+        };
+        let instruction =
+            Instruction::try_from_slice(input).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+        // match branches would increase stack frame, and hit the hard 4096
+        // frame limit. break the other branches into another function call, and
+        // mark it as never inline.
+        match instruction {
+            Instruction::Submit { round_id, value } => {
+                SubmitContext::new(&accounts, round_id, value as i128)?.process()
+            }
+            Instruction::SubmitSigned { round_id, value } => {
+                SubmitContext::new(&accounts, round_id, value)?.process()
+            }
+            Instruction::Withdraw => WithdrawContext {
+                program_id: accounts.program_id,
+                aggregator: accounts.get_owned(1)?,
+                faucet: RewardFaucet {
+                    token_program: accounts.get(0)?,
+                    faucet: accounts.get(2)?,
+                    faucet_owner: accounts.get(3)?,
+                },
+                oracle: accounts.get_owned(4)?, // write
+                oracle_owner: accounts.get(5)?, // signed
+                receiver: accounts.get(6)?,     // write
+            }
+            .process(),
+            instruction => process2(instruction, accounts),
+        }
     }
+}
+
+#[inline(never)]
+fn process2(instruction: Instruction, accounts: Accounts) -> ProgramResult {
+    match instruction {
+        Instruction::Initialize { config } => InitializeContext {
+            program_id: accounts.program_id,
+            rent: accounts.get_rent(0)?,
+            aggregator: accounts.get_owned(1)?,
+            aggregator_owner: accounts.get(2)?,
+            round_submissions: accounts.get_owned(3)?,
+            answer_submissions: accounts.get_owned(4)?,
+            oracle_registry: accounts.get_owned(5)?,
+            feed: None,
+            config,
+        }
+        .process(),
+        Instruction::InitializeFeed { feed_name, config } => InitializeContext {
+            program_id: accounts.program_id,
+            rent: accounts.get_rent(0)?,
+            // owned by the program once created
+            aggregator: accounts.get(1)?,
+            aggregator_owner: accounts.get(2)?,
+            round_submissions: accounts.get_owned(3)?,
+            answer_submissions: accounts.get_owned(4)?,
+            oracle_registry: accounts.get_owned(5)?,
+            feed: Some(FeedAccount {
+                name: feed_name,
+                payer: accounts.get(6)?,
+                system_program: accounts.get(7)?,
+            }),
+            config,
+        }
+        .process(),
+        Instruction::Configure { config } => ConfigureContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            queue: accounts.get_change_queue(2),
+            config,
+        }
+        .process(),
+        Instruction::AddOracle { description } => AddOracleContext {
+            rent: accounts.get_rent(0)?,
+            aggregator: accounts.get_owned(1)?,
+            aggregator_owner: accounts.get(2)?,
+            signers: accounts.infos,
+            oracle: accounts.get_owned(3)?,
+            oracle_owner: accounts.get(4)?,
+            oracle_registry: accounts.get_owned(5)?,
+            queue: accounts.get_change_queue(6),
+            description,
+        }
+        .process(),
+        Instruction::RemoveOracle => RemoveOracleContext {
+            program_id: accounts.program_id,
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            oracle: accounts.get_owned(2)?,
+            oracle_registry: accounts.get_owned(3)?,
+            destination: accounts.get(4)?,
+            // the reward accounts, or those queuing the removal, may be followed
+            // by the signers of a multisig admin
+            reward: accounts.get_reward(5)?,
+            queue: accounts.get_change_queue(5),
+        }
+        .process(),
+        instruction => process3(instruction, accounts),
+    }
+}
+
+#[inline(never)]
+fn process3(instruction: Instruction, accounts: Accounts) -> ProgramResult {
+    match instruction {
+        Instruction::RequestRound => RequestRoundContext {
+            clock: accounts.get_clock(0)?,
+            aggregator: accounts.get_owned(1)?,
+            round_submissions: accounts.get_owned(2)?,
+            requester: accounts.get_owned(3)?,
+            requester_owner: accounts.get(4)?,
+        }
+        .process(),
+        Instruction::AddRequester { description } => AddRequesterContext {
+            rent: accounts.get_rent(0)?,
+            aggregator: accounts.get_owned(1)?,
+            aggregator_owner: accounts.get(2)?,
+            signers: accounts.infos,
+            requester: accounts.get_owned(3)?,
+            requester_owner: accounts.get(4)?,
+
+            description,
+        }
+        .process(),
+        Instruction::RemoveRequester => RemoveRequesterContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            requester: accounts.get_owned(2)?,
+            destination: accounts.get(3)?,
+        }
+        .process(),
+        Instruction::InitializeHistory => InitializeHistoryContext {
+            rent: accounts.get_rent(0)?,
+            aggregator: accounts.get_owned(1)?,
+            aggregator_owner: accounts.get(2)?,
+            signers: accounts.infos,
+            answer_history: accounts.get_owned(3)?,
+        }
+        .process(),
+        Instruction::UpdateConfig { update } => UpdateConfigContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            queue: accounts.get_change_queue(2),
+            update,
+        }
+        .process(),
+        Instruction::SetOracleWeight { weight } => SetOracleWeightContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            oracle: accounts.get_owned(2)?,
+            queue: accounts.get_change_queue(3),
+            weight,
+        }
+        .process(),
+        Instruction::ProposeOwner => ProposeOwnerContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            pending_owner: accounts.get(2)?,
+        }
+        .process(),
+        Instruction::AcceptOwnership => AcceptOwnershipContext {
+            aggregator: accounts.get_owned(0)?,
+            pending_owner: accounts.get(1)?,
+        }
+        .process(),
+        Instruction::CancelOwnershipTransfer => CancelOwnershipTransferContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+        }
+        .process(),
+        Instruction::SetOracleOwner => SetOracleOwnerContext {
+            oracle: accounts.get_owned(0)?,
+            oracle_owner: accounts.get(1)?,
+            new_owner: accounts.get(2)?,
+        }
+        .process(),
+        Instruction::CloseAggregator => CloseAggregatorContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            round_submissions: accounts.get_owned(2)?,
+            answer_submissions: accounts.get_owned(3)?,
+            oracle_registry: accounts.get_owned(4)?,
+            destination: accounts.get(5)?,
+            accounts: Accounts {
+                program_id: accounts.program_id,
+                infos: accounts.infos,
+            },
+        }
+        .process(),
+        Instruction::Pause => SetPausedContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            paused: true,
+        }
+        .process(),
+        Instruction::Unpause => SetPausedContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            paused: false,
+        }
+        .process(),
+        Instruction::SetAdminMultisig { threshold, keys } => SetAdminMultisigContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            admin: Multisig::new(&keys, threshold)?,
+        }
+        .process(),
+        Instruction::ExecutePending => ExecutePendingContext {
+            clock: accounts.get_clock(0)?,
+            aggregator: accounts.get_owned(1)?,
+            aggregator_owner: accounts.get(2)?,
+            signers: accounts.infos,
+            pending_change: accounts.get_owned(3)?,
+            destination: accounts.get(4)?,
+            accounts: Accounts {
+                program_id: accounts.program_id,
+                infos: accounts.infos,
+            },
+        }
+        .process(),
+        Instruction::CancelPending => CancelPendingContext {
+            aggregator: accounts.get_owned(0)?,
+            aggregator_owner: accounts.get(1)?,
+            signers: accounts.infos,
+            pending_change: accounts.get_owned(2)?,
+            destination: accounts.get(3)?,
+        }
+        .process(),
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::instruction::{self, MAX_ORACLES};
+    use crate::{
+        borsh_utils,
+        state::{AggregationMethod, Answer, OutlierAction, TimeUnit, ValueType},
+    };
+    use borsh::BorshSerialize;
+    use solana_program::sysvar;
+
+    use solana_sdk::account::{create_account, Account};
+
+    fn process<'a>(
+        program_id: &Pubkey,
+        ix: instruction::Instruction,
+        accounts: &'a [AccountInfo<'a>],
+    ) -> ProgramResult {
+        let input = ix
+            .try_to_vec()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        Processor::process(&program_id, accounts, &input)
+    }
+
+    /// process an instruction built by the `instruction` module, with signer
+    /// and writable flags taken from its account metas
+    fn process_instruction<'a>(
+        ix: solana_program::instruction::Instruction,
+        accounts: &'a mut [AccountInfo<'a>],
+    ) -> ProgramResult {
+        assert_eq!(ix.accounts.len(), accounts.len());
+        for (meta, info) in ix.accounts.iter().zip(accounts.iter_mut()) {
+            assert_eq!(&meta.pubkey, info.key);
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+        }
+
+        Processor::process(&ix.program_id, accounts, &ix.data)
+    }
+
+    fn rent_sysvar() -> TSysAccount {
+        TSysAccount(sysvar::rent::id(), create_account(&Rent::default(), 42))
+    }
+
+    /// unix timestamp at slot 0 of the test clock
+    const GENESIS_TIMESTAMP: i64 = 1_600_000_000;
+
+    fn sysclock(time: i64) -> TSysAccount {
+        sysclock_at(time as u64, GENESIS_TIMESTAMP + time)
+    }
+
+    fn sysclock_at(slot: u64, unix_timestamp: i64) -> TSysAccount {
+        let clock = Clock {
+            slot,
+            unix_timestamp,
+            ..Clock::default()
+        };
+        TSysAccount(sysvar::clock::id(), create_account(&clock, 42))
+    }
+
+    fn rent_exempt_balance(space: usize) -> u64 {
+        Rent::default().minimum_balance(space)
+    }
+
+    struct TAccount {
+        is_signer: bool,
+        pubkey: Pubkey,
+        account: Account,
+    }
+
+    impl TAccount {
+        fn new(program_id: &Pubkey, is_signer: bool) -> TAccount {
+            TAccount {
+                is_signer,
+                pubkey: Pubkey::new_unique(),
+                account: Account::new(0, 0, &program_id),
+            }
+        }
+
+        fn new_rent_exempt(program_id: &Pubkey, space: usize, is_signer: bool) -> TAccount {
+            TAccount {
+                is_signer,
+                pubkey: Pubkey::new_unique(),
+                account: Account::new(rent_exempt_balance(space), space, &program_id),
+            }
+        }
+
+        /// an initialized SPL token account held by `owner`
+        fn new_token_account(owner: &Pubkey) -> TAccount {
+            let mut account = TAccount::new_rent_exempt(
+                &spl_token::id(),
+                spl_token::state::Account::LEN,
+                false,
+            );
+            spl_token::state::Account {
+                owner: *owner,
+                state: spl_token::state::AccountState::Initialized,
+                ..spl_token::state::Account::default()
+            }
+            .pack_into_slice(&mut account.account.data);
+            account
+        }
+
+        /// a look-alike of this account, with the same key and data, but owned
+        /// by some other program
+        fn foreign_copy(&self) -> TAccount {
+            TAccount {
+                is_signer: self.is_signer,
+                pubkey: self.pubkey,
+                account: Account {
+                    owner: Pubkey::new_unique(),
+                    ..self.account.clone()
+                },
+            }
+        }
+
+        fn info(&mut self) -> AccountInfo {
+            AccountInfo::new(
+                &self.pubkey,
+                self.is_signer,
+                false,
+                &mut self.account.lamports,
+                &mut self.account.data,
+                &self.account.owner,
+                self.account.executable,
+                self.account.rent_epoch,
+            )
+        }
+    }
+
+    impl<'a> Into<AccountInfo<'a>> for &'a mut TAccount {
+        fn into(self) -> AccountInfo<'a> {
+            self.info()
+        }
+    }
+
+    struct TSysAccount(Pubkey, Account);
+    impl<'a> Into<AccountInfo<'a>> for &'a mut TSysAccount {
+        fn into(self) -> AccountInfo<'a> {
+            AccountInfo::new(
+                &self.0,
+                false,
+                false,
+                &mut self.1.lamports,
+                &mut self.1.data,
+                &self.1.owner,
+                self.1.executable,
+                self.1.rent_epoch,
+            )
+        }
+    }
+
+    struct TAggregator {
+        aggregator: TAccount,
+        aggregator_owner: TAccount,
+        round_submissions: TAccount,
+        answer_submissions: TAccount,
+        oracle_registry: TAccount,
+        config: AggregatorConfig,
+    }
+
+    fn create_aggregator(program_id: &Pubkey) -> Result<TAggregator, ProgramError> {
+        create_aggregator_with_config(
+            program_id,
+            AggregatorConfig {
+                decimals: 8,
+                description: [0u8; 32],
+                min_submissions: 2,
+                max_submissions: 2,
+                round_timeout: 1,
+                restart_delay: 1,
+                requester_restart_delay: 0,
+                reward_amount: 10,
+                ..AggregatorConfig::default()
+            },
+        )
+    }
+
+    fn create_aggregator_with_config(
+        program_id: &Pubkey,
+        config: AggregatorConfig,
+    ) -> Result<TAggregator, ProgramError> {
+        let mut rent_sysvar = rent_sysvar();
+        let mut aggregator = TAccount::new_rent_exempt(
+            &program_id,
+            borsh_utils::get_account_len::<Aggregator>(),
+            false,
+        );
+        let mut aggregator_owner = TAccount::new(&program_id, true);
+        let mut round_submissions =
+            TAccount::new_rent_exempt(&program_id, Submissions::account_len(MAX_ORACLES), false);
+        let mut answer_submissions =
+            TAccount::new_rent_exempt(&program_id, Submissions::account_len(MAX_ORACLES), false);
+        let mut oracle_registry =
+            TAccount::new_rent_exempt(&program_id, OracleRegistry::ACCOUNT_LEN, false);
+
+        process(
+            &program_id,
+            instruction::Instruction::Initialize {
+                config: config.clone(),
+            },
+            vec![
+                (&mut rent_sysvar).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut round_submissions).into(),
+                (&mut answer_submissions).into(),
+                (&mut oracle_registry).into(),
+            ]
+            .as_slice(),
+        )?;
+
+        Ok(TAggregator {
+            config,
+            aggregator,
+            aggregator_owner,
+            round_submissions,
+            answer_submissions,
+            oracle_registry,
+        })
+    }
+
+    fn create_oracle(
+        program_id: &Pubkey,
+        aggregator: &mut TAccount,
+        aggregator_owner: &mut TAccount,
+        oracle_registry: &mut TAccount,
+    ) -> Result<(TAccount, TAccount), ProgramError> {
+        let mut rent_sysvar = rent_sysvar();
+        let mut oracle =
+            TAccount::new_rent_exempt(&program_id, borsh_utils::get_account_len::<Oracle>(), false);
+        let mut oracle_owner = TAccount::new(&program_id, true);
+
+        process(
+            &program_id,
+            instruction::Instruction::AddOracle {
+                description: [0xab; 32],
+            },
+            vec![
+                (&mut rent_sysvar).into(),
+                aggregator.into(),
+                aggregator_owner.into(),
+                (&mut oracle).into(),
+                (&mut oracle_owner).into(),
+                oracle_registry.into(),
+            ]
+            .as_slice(),
+        )?;
+
+        Ok((oracle, oracle_owner))
+    }
+
+    fn create_requester(
+        program_id: &Pubkey,
+        aggregator: &mut TAccount,
+        aggregator_owner: &mut TAccount,
+    ) -> Result<(TAccount, TAccount), ProgramError> {
+        let mut rent_sysvar = rent_sysvar();
+        let mut requester =
+            TAccount::new_rent_exempt(&program_id, borsh_utils::get_account_len::<Requester>(), false);
+        let mut requester_owner = TAccount::new(&program_id, true);
+
+        process(
+            &program_id,
+            instruction::Instruction::AddRequester {
+                description: [0xab; 32],
+            },
+            vec![
+                (&mut rent_sysvar).into(),
+                aggregator.into(),
+                aggregator_owner.into(),
+                (&mut requester).into(),
+                (&mut requester_owner).into(),
+            ]
+            .as_slice(),
+        )?;
+
+        Ok((requester, requester_owner))
+    }
+
+    #[test]
+    fn test_intialize() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        create_aggregator(&program_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_configure() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            config,
+            ..
+        } = create_aggregator(&program_id)?;
+
+        process(
+            &program_id,
+            instruction::Instruction::Configure {
+                config: AggregatorConfig {
+                    reward_amount: 1000,
+                    ..config.clone()
+                },
+            },
+            vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
+        )?;
+
+        let aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(aggregator_state.config.reward_amount, 1000);
+
+        // test: an invalid config is rejected, leaving the config as is
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::Configure {
+                    config: AggregatorConfig {
+                        min_submissions: 3,
+                        ..config
+                    },
+                },
+                vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::MinSubmissionsAboveMax)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_config() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            config,
+            ..
+        } = create_aggregator(&program_id)?;
+
+        let mut update_config = |update| {
+            process(
+                &program_id,
+                instruction::Instruction::UpdateConfig { update },
+                vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        update_config(AggregatorConfigUpdate {
+            reward_amount: Some(1000),
+            ..AggregatorConfigUpdate::default()
+        })?;
+
+        // test: an invalid patch is rejected
+        assert_eq!(
+            update_config(AggregatorConfigUpdate {
+                min_submissions: Some(3),
+                ..AggregatorConfigUpdate::default()
+            }),
+            Err(Error::MinSubmissionsAboveMax)
+        );
+
+        // decimals can change as long as there is no answer
+        update_config(AggregatorConfigUpdate {
+            decimals: Some(6),
+            ..AggregatorConfigUpdate::default()
+        })?;
+
+        let mut aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(
+            aggregator_state.config,
+            AggregatorConfig {
+                reward_amount: 1000,
+                decimals: 6,
+                ..config
+            }
+        );
+
+        aggregator_state.answer.created_at = 1;
+        aggregator_state.save(&aggregator.info())?;
+
+        let mut update_config = |update| {
+            process(
+                &program_id,
+                instruction::Instruction::UpdateConfig { update },
+                vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        // test: decimals are fixed once answers exist
+        assert_eq!(
+            update_config(AggregatorConfigUpdate {
+                decimals: Some(8),
+                ..AggregatorConfigUpdate::default()
+            }),
+            Err(Error::DecimalsImmutable)
+        );
+
+        // setting decimals to their current value is fine
+        update_config(AggregatorConfigUpdate {
+            decimals: Some(6),
+            reward_amount: Some(2000),
+            ..AggregatorConfigUpdate::default()
+        })?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ownership_transfer() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            ..
+        } = create_aggregator(&program_id)?;
+        let mut new_owner = TAccount::new(&program_id, true);
+        let mut other = TAccount::new(&program_id, true);
+
+        let propose = |tt: &mut TAccount, owner: &mut TAccount, pending_owner: &mut TAccount| {
+            process(
+                &program_id,
+                instruction::Instruction::ProposeOwner,
+                vec![tt.into(), owner.into(), pending_owner.into()].as_slice(),
+            )
+            .map_err(Error::from)
+        };
+        let accept = |tt: &mut TAccount, pending_owner: &mut TAccount| {
+            process(
+                &program_id,
+                instruction::Instruction::AcceptOwnership,
+                vec![tt.into(), pending_owner.into()].as_slice(),
+            )
+            .map_err(Error::from)
+        };
+        let cancel = |tt: &mut TAccount, owner: &mut TAccount| {
+            process(
+                &program_id,
+                instruction::Instruction::CancelOwnershipTransfer,
+                vec![tt.into(), owner.into()].as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        // test: nothing to accept or cancel before a proposal
+        assert_eq!(
+            accept(&mut aggregator, &mut new_owner),
+            Err(Error::NoPendingOwner)
+        );
+        assert_eq!(
+            cancel(&mut aggregator, &mut aggregator_owner),
+            Err(Error::NoPendingOwner)
+        );
+
+        // test: only the owner can propose
+        assert_eq!(
+            propose(&mut aggregator, &mut other, &mut new_owner),
+            Err(Error::OwnerMismatch)
+        );
+
+        propose(&mut aggregator, &mut aggregator_owner, &mut new_owner)?;
+
+        // test: only the pending owner can accept
+        assert_eq!(
+            accept(&mut aggregator, &mut other),
+            Err(Error::PendingOwnerMismatch)
+        );
+        assert_eq!(
+            accept(&mut aggregator, &mut aggregator_owner),
+            Err(Error::PendingOwnerMismatch)
+        );
+
+        // test: the pending owner must sign
+        new_owner.is_signer = false;
+        assert_eq!(
+            accept(&mut aggregator, &mut new_owner),
+            Err(Error::from(ProgramError::MissingRequiredSignature))
+        );
+        new_owner.is_signer = true;
+
+        // test: a cancelled transfer cannot be accepted
+        cancel(&mut aggregator, &mut aggregator_owner)?;
+        assert_eq!(
+            accept(&mut aggregator, &mut new_owner),
+            Err(Error::NoPendingOwner)
+        );
+
+        propose(&mut aggregator, &mut aggregator_owner, &mut new_owner)?;
+        accept(&mut aggregator, &mut new_owner)?;
+
+        let aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(aggregator_state.owner.0, new_owner.pubkey.to_bytes());
+        assert!(!aggregator_state.has_pending_owner());
+
+        // test: the previous owner lost its authority
+        assert_eq!(
+            cancel(&mut aggregator, &mut aggregator_owner),
+            Err(Error::OwnerMismatch)
+        );
+        propose(&mut aggregator, &mut new_owner, &mut other)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_and_remove_oracle() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            mut oracle_registry,
+            ..
+        } = create_aggregator(&program_id)?;
+        let (mut oracle, _oracle_owner) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        let mut destination = TAccount::new(&program_id, false);
+        let oracle_lamports = oracle.account.lamports;
+        assert_eq!(
+            crate::list_oracles(&program_id, &aggregator.info(), &oracle_registry.info())?,
+            vec![oracle.pubkey]
+        );
+
+        // test: the oracles of an aggregator of another program are not listed
+        let mut foreign = aggregator.foreign_copy();
+        assert_eq!(
+            crate::list_oracles(&program_id, &foreign.info(), &oracle_registry.info())
+                .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch)
+        );
+
+        process(
+            &program_id,
+            instruction::Instruction::RemoveOracle {},
+            vec![
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle).into(),
+                (&mut oracle_registry).into(),
+                (&mut destination).into(),
+            ]
+            .as_slice(),
+        )?;
+
+        // test: the oracle account is closed, its lamports reclaimed
+        assert_eq!(oracle.account.lamports, 0);
+        assert_eq!(destination.account.lamports, oracle_lamports);
+        assert!(oracle.account.data.iter().all(|b| *b == 0));
+        assert!(
+            crate::list_oracles(&program_id, &aggregator.info(), &oracle_registry.info())?
+                .is_empty()
+        );
+
+        // println!("{}", hex::encode(oracle.account.data));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_oracles() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            mut oracle_registry,
+            config,
+            ..
+        } = create_aggregator(&program_id)?;
+
+        process(
+            &program_id,
+            instruction::Instruction::Configure {
+                config: AggregatorConfig {
+                    max_oracles: 2,
+                    ..config
+                },
+            },
+            vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
+        )?;
+
+        let (mut oracle, _) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        let (oracle2, _) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+
+        // test: no more than `max_oracles` oracles
+        assert_eq!(
+            create_oracle(
+                &program_id,
+                &mut aggregator,
+                &mut aggregator_owner,
+                &mut oracle_registry,
+            )
+            .map(|_| ())
+            .map_err(Error::from),
+            Err(Error::MaxOraclesReached)
+        );
+
+        process(
+            &program_id,
+            instruction::Instruction::RemoveOracle,
+            vec![
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle).into(),
+                (&mut oracle_registry).into(),
+                (&mut TAccount::new(&program_id, false)).into(),
+            ]
+            .as_slice(),
+        )?;
+
+        let (oracle3, _) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        assert_eq!(
+            crate::list_oracles(&program_id, &aggregator.info(), &oracle_registry.info())?,
+            vec![oracle2.pubkey, oracle3.pubkey]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_oracle_settles_rewards() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut token_program = TAccount::new(&spl_token::id(), false);
+        token_program.pubkey = spl_token::id();
+        let mut faucet = TAccount::new(&spl_token::id(), false);
+
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            mut oracle_registry,
+            ..
+        } = create_aggregator_with_config(
+            &program_id,
+            AggregatorConfig {
+                min_submissions: 1,
+                max_submissions: 1,
+                reward_token_account: PublicKey(faucet.pubkey.to_bytes()),
+                ..AggregatorConfig::default()
+            },
+        )?;
+        let (mut oracle, oracle_owner) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        let mut destination = TAccount::new(&program_id, false);
+
+        let mut oracle_state = Oracle::load_initialized(&oracle.info())?;
+        oracle_state.withdrawable = 10;
+        oracle_state.save(&oracle.info())?;
+
+        let (faucet_owner_key, _) = find_faucet_owner_address(&program_id, &aggregator.pubkey);
+        let mut faucet_owner = TAccount::new(&program_id, false);
+        faucet_owner.pubkey = faucet_owner_key;
+
+        // test: outstanding rewards must be settled
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::RemoveOracle,
+                vec![
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut oracle).into(),
+                    (&mut oracle_registry).into(),
+                    (&mut destination).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::OutstandingWithdrawable)
+        );
+
+        let mut remove = |receiver: &mut TAccount| {
+            process(
+                &program_id,
+                instruction::Instruction::RemoveOracle,
+                vec![
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut oracle).into(),
+                    (&mut oracle_registry).into(),
+                    (&mut destination).into(),
+                    (&mut token_program).into(),
+                    (&mut faucet).into(),
+                    (&mut faucet_owner).into(),
+                    receiver.into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        // test: the rewards only go to a token account of the oracle owner
+        let mut receiver = TAccount::new(&spl_token::id(), false);
+        assert_eq!(remove(&mut receiver), Err(Error::InvalidReceiver));
+        let mut receiver = TAccount::new_token_account(&Pubkey::new_unique());
+        assert_eq!(remove(&mut receiver), Err(Error::InvalidReceiver));
+
+        let mut receiver = TAccount::new_token_account(&oracle_owner.pubkey);
+        remove(&mut receiver)?;
+
+        assert_eq!(oracle.account.lamports, 0);
+        assert!(oracle.account.data.iter().all(|b| *b == 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_oracle_owner() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            mut oracle_registry,
+            ..
+        } = create_aggregator(&program_id)?;
+        let (mut oracle, mut oracle_owner) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        let mut new_owner = TAccount::new(&program_id, true);
+
+        let mut oracle_state = Oracle::load_initialized(&oracle.info())?;
+        oracle_state.withdrawable = 100;
+        oracle_state.allow_start_round = 3;
+        oracle_state.save(&oracle.info())?;
+
+        let set_owner = |oracle: &mut TAccount, owner: &mut TAccount, new_owner: &mut TAccount| {
+            process(
+                &program_id,
+                instruction::Instruction::SetOracleOwner,
+                vec![oracle.into(), owner.into(), new_owner.into()].as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        // test: only the current owner can hand over the oracle
+        assert_eq!(
+            set_owner(&mut oracle, &mut aggregator_owner, &mut new_owner),
+            Err(Error::OwnerMismatch)
+        );
+
+        // test: the new owner must co-sign
+        new_owner.is_signer = false;
+        assert_eq!(
+            set_owner(&mut oracle, &mut oracle_owner, &mut new_owner),
+            Err(Error::from(ProgramError::MissingRequiredSignature))
+        );
+        new_owner.is_signer = true;
+
+        set_owner(&mut oracle, &mut oracle_owner, &mut new_owner)?;
+
+        let rotated = Oracle::load_initialized(&oracle.info())?;
+        assert_eq!(
+            rotated,
+            Oracle {
+                owner: PublicKey(new_owner.pubkey.to_bytes()),
+                ..oracle_state
+            }
+        );
+
+        // test: the previous key lost its authority
+        assert_eq!(
+            set_owner(&mut oracle, &mut oracle_owner, &mut new_owner),
+            Err(Error::OwnerMismatch)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_and_remove_requester() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            ..
+        } = create_aggregator(&program_id)?;
+        let (mut requester, _requester_owner) =
+            create_requester(&program_id, &mut aggregator, &mut aggregator_owner)?;
+        let mut destination = TAccount::new(&program_id, false);
+        let requester_lamports = requester.account.lamports;
+
+        process(
+            &program_id,
+            instruction::Instruction::RemoveRequester {},
+            vec![
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut requester).into(),
+                (&mut destination).into(),
+            ]
+            .as_slice(),
+        )?;
+
+        // test: the requester account is closed, its lamports reclaimed
+        assert_eq!(requester.account.lamports, 0);
+        assert_eq!(destination.account.lamports, requester_lamports);
+        assert!(requester.account.data.iter().all(|b| *b == 0));
+
+        println!("{}", hex::encode(aggregator.account.data));
+        Ok(())
+    }
+
+    #[test]
+    fn test_close_aggregator() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut tt = create_aggregator(&program_id)?;
+        let (mut oracle, _oracle_owner) = create_oracle(
+            &program_id,
+            &mut tt.aggregator,
+            &mut tt.aggregator_owner,
+            &mut tt.oracle_registry,
+        )?;
+        let (mut requester, _requester_owner) =
+            create_requester(&program_id, &mut tt.aggregator, &mut tt.aggregator_owner)?;
+        let mut destination = TAccount::new(&program_id, false);
+
+        let lamports = tt.aggregator.account.lamports
+            + tt.round_submissions.account.lamports
+            + tt.answer_submissions.account.lamports
+            + tt.oracle_registry.account.lamports;
+
+        let close = |tt: &mut TAggregator, destination: &mut TAccount| {
+            process(
+                &program_id,
+                instruction::Instruction::CloseAggregator,
+                vec![
+                    (&mut tt.aggregator).into(),
+                    (&mut tt.aggregator_owner).into(),
+                    (&mut tt.round_submissions).into(),
+                    (&mut tt.answer_submissions).into(),
+                    (&mut tt.oracle_registry).into(),
+                    destination.into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        // test: all the oracles must be removed first
+        assert_eq!(
+            close(&mut tt, &mut destination),
+            Err(Error::AggregatorHasOracles)
+        );
+
+        process(
+            &program_id,
+            instruction::Instruction::RemoveOracle,
+            vec![
+                (&mut tt.aggregator).into(),
+                (&mut tt.aggregator_owner).into(),
+                (&mut oracle).into(),
+                (&mut tt.oracle_registry).into(),
+                (&mut TAccount::new(&program_id, false)).into(),
+            ]
+            .as_slice(),
+        )?;
+
+        // test: all the requesters must be removed first
+        assert_eq!(
+            close(&mut tt, &mut destination),
+            Err(Error::AggregatorHasRequesters)
+        );
+
+        process(
+            &program_id,
+            instruction::Instruction::RemoveRequester,
+            vec![
+                (&mut tt.aggregator).into(),
+                (&mut tt.aggregator_owner).into(),
+                (&mut requester).into(),
+                (&mut TAccount::new(&program_id, false)).into(),
+            ]
+            .as_slice(),
+        )?;
+
+        // test: all the pending changes must be executed or cancelled first
+        let config = AggregatorConfig {
+            timelock_delay: 10,
+            ..tt.config.clone()
+        };
+        process(
+            &program_id,
+            instruction::Instruction::Configure {
+                config: config.clone(),
+            },
+            vec![
+                (&mut tt.aggregator).into(),
+                (&mut tt.aggregator_owner).into(),
+            ]
+            .as_slice(),
+        )?;
+        let mut pending_change = TAccount::new_rent_exempt(
+            &program_id,
+            borsh_utils::get_account_len::<PendingChange>(),
+            false,
+        );
+        process(
+            &program_id,
+            instruction::Instruction::Configure { config },
+            vec![
+                (&mut tt.aggregator).into(),
+                (&mut tt.aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut rent_sysvar()).into(),
+                (&mut sysclock(100)).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(
+            close(&mut tt, &mut destination),
+            Err(Error::AggregatorHasPendingChanges)
+        );
+
+        process(
+            &program_id,
+            instruction::Instruction::CancelPending,
+            vec![
+                (&mut tt.aggregator).into(),
+                (&mut tt.aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut TAccount::new(&program_id, false)).into(),
+            ]
+            .as_slice(),
+        )?;
+
+        close(&mut tt, &mut destination)?;
+
+        assert_eq!(destination.account.lamports, lamports);
+        let closed = [
+            &tt.aggregator,
+            &tt.round_submissions,
+            &tt.answer_submissions,
+            &tt.oracle_registry,
+        ];
+        for account in closed.iter() {
+            assert_eq!(account.account.lamports, 0);
+            assert!(account.account.data.iter().all(|b| *b == 0));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_close_aggregator_requires_faucet() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut token_program = TAccount::new(&spl_token::id(), false);
+        token_program.pubkey = spl_token::id();
+        let mut faucet = TAccount::new(&spl_token::id(), false);
+        let mut tt = create_aggregator_with_config(
+            &program_id,
+            AggregatorConfig {
+                min_submissions: 1,
+                max_submissions: 1,
+                reward_token_account: PublicKey(faucet.pubkey.to_bytes()),
+                ..AggregatorConfig::default()
+            },
+        )?;
+        let (faucet_owner_key, _) = find_faucet_owner_address(&program_id, &tt.aggregator.pubkey);
+        let mut faucet_owner = TAccount::new(&program_id, false);
+        faucet_owner.pubkey = faucet_owner_key;
+        let mut receiver = TAccount::new_token_account(&Pubkey::new_unique());
+        let mut destination = TAccount::new(&program_id, false);
+
+        // test: the faucet must be closed along with the aggregator
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::CloseAggregator,
+                vec![
+                    (&mut tt.aggregator).into(),
+                    (&mut tt.aggregator_owner).into(),
+                    (&mut tt.round_submissions).into(),
+                    (&mut tt.answer_submissions).into(),
+                    (&mut tt.oracle_registry).into(),
+                    (&mut destination).into(),
+                ]
+                .as_slice(),
+            ),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // test: the faucet is the aggregator's
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::CloseAggregator,
+                vec![
+                    (&mut tt.aggregator).into(),
+                    (&mut tt.aggregator_owner).into(),
+                    (&mut tt.round_submissions).into(),
+                    (&mut tt.answer_submissions).into(),
+                    (&mut tt.oracle_registry).into(),
+                    (&mut destination).into(),
+                    (&mut token_program).into(),
+                    (&mut TAccount::new(&spl_token::id(), false)).into(),
+                    (&mut faucet_owner).into(),
+                    (&mut receiver).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::InvalidFaucet)
+        );
+
+        process(
+            &program_id,
+            instruction::Instruction::CloseAggregator,
+            vec![
+                (&mut tt.aggregator).into(),
+                (&mut tt.aggregator_owner).into(),
+                (&mut tt.round_submissions).into(),
+                (&mut tt.answer_submissions).into(),
+                (&mut tt.oracle_registry).into(),
+                (&mut destination).into(),
+                (&mut token_program).into(),
+                (&mut faucet).into(),
+                (&mut faucet_owner).into(),
+                (&mut receiver).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(tt.aggregator.account.lamports, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_initialize_rejects_foreign_accounts() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        let foreign_program_id = Pubkey::new_unique();
+
+        let mut rent_sysvar = rent_sysvar();
+        let mut aggregator_owner = TAccount::new(&program_id, true);
+        let mut aggregator = TAccount::new_rent_exempt(
+            &program_id,
+            borsh_utils::get_account_len::<Aggregator>(),
+            false,
+        );
+        let mut round_submissions =
+            TAccount::new_rent_exempt(&program_id, Submissions::account_len(MAX_ORACLES), false);
+        let mut answer_submissions =
+            TAccount::new_rent_exempt(&program_id, Submissions::account_len(MAX_ORACLES), false);
+        let mut oracle_registry =
+            TAccount::new_rent_exempt(&program_id, OracleRegistry::ACCOUNT_LEN, false);
+        let mut foreign_aggregator = TAccount::new_rent_exempt(
+            &foreign_program_id,
+            borsh_utils::get_account_len::<Aggregator>(),
+            false,
+        );
+        let mut foreign_submissions = TAccount::new_rent_exempt(
+            &foreign_program_id,
+            Submissions::account_len(MAX_ORACLES),
+            false,
+        );
+
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::Initialize {
+                    config: AggregatorConfig::default(),
+                },
+                vec![
+                    (&mut rent_sysvar).into(),
+                    (&mut foreign_aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut round_submissions).into(),
+                    (&mut answer_submissions).into(),
+                    (&mut oracle_registry).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+            "should not initialize an aggregator owned by another program"
+        );
+
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::Initialize {
+                    config: AggregatorConfig::default(),
+                },
+                vec![
+                    (&mut rent_sysvar).into(),
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut round_submissions).into(),
+                    (&mut foreign_submissions).into(),
+                    (&mut oracle_registry).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+            "should not link submissions owned by another program"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_foreign_owned_accounts() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            mut oracle_registry,
+            mut round_submissions,
+            mut answer_submissions,
+            ..
+        } = create_aggregator(&program_id)?;
+        let (mut oracle, mut oracle_owner) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        let (requester, mut requester_owner) =
+            create_requester(&program_id, &mut aggregator, &mut aggregator_owner)?;
+
+        // test: configure a look-alike aggregator
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::Configure {
+                    config: AggregatorConfig::default(),
+                },
+                vec![
+                    (&mut aggregator.foreign_copy()).into(),
+                    (&mut aggregator_owner).into()
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+        );
+
+        // test: add oracle to a look-alike aggregator
+        let mut rent_sysvar = rent_sysvar();
+        let mut new_oracle =
+            TAccount::new_rent_exempt(&program_id, borsh_utils::get_account_len::<Oracle>(), false);
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::AddOracle {
+                    description: [0xab; 32],
+                },
+                vec![
+                    (&mut rent_sysvar).into(),
+                    (&mut aggregator.foreign_copy()).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut new_oracle).into(),
+                    (&mut oracle_owner).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+        );
+
+        // test: submit with look-alike oracle, aggregator, and submissions
+        let submit = |aggregator: &mut TAccount,
+                      round_submissions: &mut TAccount,
+                      answer_submissions: &mut TAccount,
+                      oracle: &mut TAccount,
+                      oracle_owner: &mut TAccount| {
+            let mut clock = sysclock(100);
+            process(
+                &program_id,
+                instruction::Instruction::Submit {
+                    round_id: 0,
+                    value: 1,
+                },
+                vec![
+                    (&mut clock).into(),
+                    aggregator.into(),
+                    round_submissions.into(),
+                    answer_submissions.into(),
+                    oracle.into(),
+                    oracle_owner.into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        assert_eq!(
+            submit(
+                &mut aggregator,
+                &mut round_submissions,
+                &mut answer_submissions,
+                &mut oracle.foreign_copy(),
+                &mut oracle_owner
+            ),
+            Err(Error::ProgramOwnerMismatch),
+        );
+        assert_eq!(
+            submit(
+                &mut aggregator.foreign_copy(),
+                &mut round_submissions,
+                &mut answer_submissions,
+                &mut oracle,
+                &mut oracle_owner
+            ),
+            Err(Error::ProgramOwnerMismatch),
+        );
+        assert_eq!(
+            submit(
+                &mut aggregator,
+                &mut round_submissions.foreign_copy(),
+                &mut answer_submissions,
+                &mut oracle,
+                &mut oracle_owner
+            ),
+            Err(Error::ProgramOwnerMismatch),
+        );
+        assert_eq!(
+            submit(
+                &mut aggregator,
+                &mut round_submissions,
+                &mut answer_submissions.foreign_copy(),
+                &mut oracle,
+                &mut oracle_owner
+            ),
+            Err(Error::ProgramOwnerMismatch),
+        );
+
+        // test: request round with a look-alike requester
+        let mut clock = sysclock(100);
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::RequestRound,
+                vec![
+                    (&mut clock).into(),
+                    (&mut aggregator).into(),
+                    (&mut round_submissions).into(),
+                    (&mut requester.foreign_copy()).into(),
+                    (&mut requester_owner).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+        );
+
+        // test: withdraw with a look-alike oracle
+        let mut token_program = TAccount::new(&program_id, false);
+        let mut faucet = TAccount::new(&spl_token::id(), false);
+        let mut faucet_owner = TAccount::new(&program_id, false);
+        let mut receiver = TAccount::new(&spl_token::id(), false);
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::Withdraw,
+                vec![
+                    (&mut token_program).into(),
+                    (&mut aggregator).into(),
+                    (&mut faucet).into(),
+                    (&mut faucet_owner).into(),
+                    (&mut oracle.foreign_copy()).into(),
+                    (&mut oracle_owner).into(),
+                    (&mut receiver).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+        );
+
+        // test: remove look-alike oracle and requester
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::RemoveOracle,
+                vec![
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut oracle.foreign_copy()).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+        );
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::RemoveRequester,
+                vec![
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut requester.foreign_copy()).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_wrong_account_type() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            mut oracle_registry,
+            mut round_submissions,
+            mut answer_submissions,
+            ..
+        } = create_aggregator(&program_id)?;
+        let (mut oracle, _oracle_owner) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        let (mut requester, mut requester_owner) =
+            create_requester(&program_id, &mut aggregator, &mut aggregator_owner)?;
+
+        // test: submissions account passed in as an aggregator
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::Configure {
+                    config: AggregatorConfig::default(),
+                },
+                vec![
+                    (&mut round_submissions).into(),
+                    (&mut aggregator_owner).into()
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::AccountTypeMismatch),
+        );
+
+        // test: oracle account passed in as a requester
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::RemoveRequester,
+                vec![
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut oracle).into(),
+                    (&mut TAccount::new(&program_id, false)).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::AccountTypeMismatch),
+        );
+
+        // test: requester account passed in as an oracle
+        let mut clock = sysclock(100);
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::Submit {
+                    round_id: 0,
+                    value: 1,
+                },
+                vec![
+                    (&mut clock).into(),
+                    (&mut aggregator).into(),
+                    (&mut round_submissions).into(),
+                    (&mut answer_submissions).into(),
+                    (&mut requester).into(),
+                    (&mut requester_owner).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::AccountTypeMismatch),
+        );
+
+        // test: an initialized oracle cannot be re-initialized as a requester
+        let mut rent_sysvar = rent_sysvar();
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::AddRequester {
+                    description: [0xab; 32],
+                },
+                vec![
+                    (&mut rent_sysvar).into(),
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut oracle).into(),
+                    (&mut requester_owner).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::AccountTypeMismatch),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_withdraw_faucet_owner() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut token_program = TAccount::new(&spl_token::id(), false);
+        token_program.pubkey = spl_token::id();
+        let mut faucet = TAccount::new(&spl_token::id(), false);
+        let mut receiver = TAccount::new(&spl_token::id(), false);
+
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            mut oracle_registry,
+            ..
+        } = create_aggregator_with_config(
+            &program_id,
+            AggregatorConfig {
+                min_submissions: 1,
+                max_submissions: 1,
+                reward_token_account: PublicKey(faucet.pubkey.to_bytes()),
+                ..AggregatorConfig::default()
+            },
+        )?;
+        let (mut oracle, mut oracle_owner) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+
+        let (faucet_owner_key, bump_seed) =
+            find_faucet_owner_address(&program_id, &aggregator.pubkey);
+        let aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(aggregator_state.faucet_bump_seed, bump_seed);
+
+        let mut withdraw = |token_program: &mut TAccount, faucet_owner: &mut TAccount| {
+            process(
+                &program_id,
+                instruction::Instruction::Withdraw,
+                vec![
+                    token_program.into(),
+                    (&mut aggregator).into(),
+                    (&mut faucet).into(),
+                    faucet_owner.into(),
+                    (&mut oracle).into(),
+                    (&mut oracle_owner).into(),
+                    (&mut receiver).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        // test: the faucet owner would sign for another program
+        let mut faucet_owner = TAccount::new(&program_id, false);
+        faucet_owner.pubkey = faucet_owner_key;
+        let mut other_program = TAccount::new(&program_id, false);
+        assert_eq!(
+            withdraw(&mut other_program, &mut faucet_owner),
+            Err(Error::InvalidTokenProgram)
+        );
+
+        // test: arbitrary faucet owner
+        let mut faucet_owner = TAccount::new(&program_id, false);
+        assert_eq!(
+            withdraw(&mut token_program, &mut faucet_owner),
+            Err(Error::InvalidFaucetOwner)
+        );
+
+        // test: faucet owner derived for another aggregator
+        let (other_faucet_owner_key, _) =
+            find_faucet_owner_address(&program_id, &Pubkey::new_unique());
+        faucet_owner.pubkey = other_faucet_owner_key;
+        assert_eq!(
+            withdraw(&mut token_program, &mut faucet_owner),
+            Err(Error::InvalidFaucetOwner)
+        );
+
+        // test: the derived faucet owner is accepted
+        faucet_owner.pubkey = faucet_owner_key;
+        assert_eq!(
+            withdraw(&mut token_program, &mut faucet_owner),
+            Err(Error::InsufficientWithdrawable)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_instruction_builders() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut rent_sysvar = rent_sysvar();
+        let mut aggregator = TAccount::new_rent_exempt(
+            &program_id,
+            borsh_utils::get_account_len::<Aggregator>(),
+            false,
+        );
+        let mut aggregator_owner = TAccount::new(&program_id, false);
+        let mut round_submissions =
+            TAccount::new_rent_exempt(&program_id, Submissions::account_len(MAX_ORACLES), false);
+        let mut answer_submissions =
+            TAccount::new_rent_exempt(&program_id, Submissions::account_len(MAX_ORACLES), false);
+        let mut oracle_registry =
+            TAccount::new_rent_exempt(&program_id, OracleRegistry::ACCOUNT_LEN, false);
+        let mut oracle =
+            TAccount::new_rent_exempt(&program_id, borsh_utils::get_account_len::<Oracle>(), false);
+        let mut oracle_owner = TAccount::new(&program_id, false);
+        let mut requester = TAccount::new_rent_exempt(
+            &program_id,
+            borsh_utils::get_account_len::<Requester>(),
+            false,
+        );
+        let mut requester_owner = TAccount::new(&program_id, false);
+        let mut faucet = TAccount::new(&spl_token::id(), false);
+        let mut receiver = TAccount::new(&spl_token::id(), false);
+
+        let config = AggregatorConfig {
+            min_submissions: 1,
+            max_submissions: 2,
+            reward_amount: 10,
+            reward_token_account: PublicKey(faucet.pubkey.to_bytes()),
+            ..AggregatorConfig::default()
+        };
+
+        process_instruction(
+            instruction::initialize(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &round_submissions.pubkey,
+                &answer_submissions.pubkey,
+                &oracle_registry.pubkey,
+                config.clone(),
+            )?,
+            &mut [
+                (&mut rent_sysvar).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut round_submissions).into(),
+                (&mut answer_submissions).into(),
+                (&mut oracle_registry).into(),
+            ],
+        )?;
+
+        process_instruction(
+            instruction::configure(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                AggregatorConfig {
+                    description: [0xcd; 32],
+                    ..config
+                },
+            )?,
+            &mut [(&mut aggregator).into(), (&mut aggregator_owner).into()],
+        )?;
+
+        process_instruction(
+            instruction::update_config(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                AggregatorConfigUpdate {
+                    description: Some([0xef; 32]),
+                    ..AggregatorConfigUpdate::default()
+                },
+            )?,
+            &mut [(&mut aggregator).into(), (&mut aggregator_owner).into()],
+        )?;
+
+        process_instruction(
+            instruction::add_oracle(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &oracle.pubkey,
+                &oracle_owner.pubkey,
+                &oracle_registry.pubkey,
+                [0xab; 32],
+            )?,
+            &mut [
+                (&mut rent_sysvar).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle).into(),
+                (&mut oracle_owner).into(),
+                (&mut oracle_registry).into(),
+            ],
+        )?;
+
+        process_instruction(
+            instruction::set_oracle_weight(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &oracle.pubkey,
+                2,
+            )?,
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle).into(),
+            ],
+        )?;
+        assert_eq!(Oracle::load_initialized(&oracle.info())?.weight, 2);
+
+        process_instruction(
+            instruction::add_requester(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &requester.pubkey,
+                &requester_owner.pubkey,
+                [0xab; 32],
+            )?,
+            &mut [
+                (&mut rent_sysvar).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut requester).into(),
+                (&mut requester_owner).into(),
+            ],
+        )?;
+
+        let mut answer_history =
+            TAccount::new_rent_exempt(&program_id, AnswerHistory::ACCOUNT_LEN, false);
+        process_instruction(
+            instruction::initialize_history(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &answer_history.pubkey,
+            )?,
+            &mut [
+                (&mut rent_sysvar).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut answer_history).into(),
+            ],
+        )?;
+
+        // test: nothing to withdraw yet, after the faucet owner is accepted
+        let (faucet_owner_key, _) = find_faucet_owner_address(&program_id, &aggregator.pubkey);
+        let mut faucet_owner = TAccount::new(&program_id, false);
+        faucet_owner.pubkey = faucet_owner_key;
+        let mut token_program = TAccount::new(&program_id, false);
+        token_program.pubkey = spl_token::id();
+        assert_eq!(
+            process_instruction(
+                instruction::withdraw(
+                    &program_id,
+                    &aggregator.pubkey,
+                    &faucet.pubkey,
+                    &oracle.pubkey,
+                    &oracle_owner.pubkey,
+                    &receiver.pubkey,
+                )?,
+                &mut [
+                    (&mut token_program).into(),
+                    (&mut aggregator).into(),
+                    (&mut faucet).into(),
+                    (&mut faucet_owner).into(),
+                    (&mut oracle).into(),
+                    (&mut oracle_owner).into(),
+                    (&mut receiver).into(),
+                ],
+            )
+            .map_err(Error::from),
+            Err(Error::InsufficientWithdrawable),
+        );
+
+        let mut clock = sysclock(100);
+        process_instruction(
+            instruction::request_round(
+                &program_id,
+                &aggregator.pubkey,
+                &round_submissions.pubkey,
+                &requester.pubkey,
+                &requester_owner.pubkey,
+            )?,
+            &mut [
+                (&mut clock).into(),
+                (&mut aggregator).into(),
+                (&mut round_submissions).into(),
+                (&mut requester).into(),
+                (&mut requester_owner).into(),
+            ],
+        )?;
+
+        process_instruction(
+            instruction::submit(
+                &program_id,
+                &aggregator.pubkey,
+                &round_submissions.pubkey,
+                &answer_submissions.pubkey,
+                &oracle.pubkey,
+                &oracle_owner.pubkey,
+                Some(&answer_history.pubkey),
+                1,
+                42,
+            )?,
+            &mut [
+                (&mut clock).into(),
+                (&mut aggregator).into(),
+                (&mut round_submissions).into(),
+                (&mut answer_submissions).into(),
+                (&mut oracle).into(),
+                (&mut oracle_owner).into(),
+                (&mut answer_history).into(),
+            ],
+        )?;
+
+        let aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(aggregator_state.answer.round_id, 1);
+        assert_eq!(aggregator_state.answer.median, 42);
+        let oracle_state = Oracle::load_initialized(&oracle.info())?;
+        assert_eq!(oracle_state.withdrawable, 10);
+
+        process_instruction(
+            instruction::submit_signed(
+                &program_id,
+                &aggregator.pubkey,
+                &round_submissions.pubkey,
+                &answer_submissions.pubkey,
+                &oracle.pubkey,
+                &oracle_owner.pubkey,
+                Some(&answer_history.pubkey),
+                2,
+                44,
+            )?,
+            &mut [
+                (&mut sysclock(110)).into(),
+                (&mut aggregator).into(),
+                (&mut round_submissions).into(),
+                (&mut answer_submissions).into(),
+                (&mut oracle).into(),
+                (&mut oracle_owner).into(),
+                (&mut answer_history).into(),
+            ],
+        )?;
+        let aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(aggregator_state.answer.round_id, 2);
+        assert_eq!(aggregator_state.answer.signed_median, 44);
+
+        process_instruction(
+            instruction::pause(&program_id, &aggregator.pubkey, &aggregator_owner.pubkey)?,
+            &mut [(&mut aggregator).into(), (&mut aggregator_owner).into()],
+        )?;
+        assert!(Aggregator::load_initialized(&aggregator.info())?.paused);
+        process_instruction(
+            instruction::unpause(&program_id, &aggregator.pubkey, &aggregator_owner.pubkey)?,
+            &mut [(&mut aggregator).into(), (&mut aggregator_owner).into()],
+        )?;
+        assert!(!Aggregator::load_initialized(&aggregator.info())?.paused);
+
+        let mut destination = TAccount::new(&program_id, false);
+        process_instruction(
+            instruction::remove_requester(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &requester.pubkey,
+                &destination.pubkey,
+            )?,
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut requester).into(),
+                (&mut destination).into(),
+            ],
+        )?;
+
+        let mut new_oracle_owner = TAccount::new(&program_id, false);
+        process_instruction(
+            instruction::set_oracle_owner(
+                &program_id,
+                &oracle.pubkey,
+                &oracle_owner.pubkey,
+                &new_oracle_owner.pubkey,
+            )?,
+            &mut [
+                (&mut oracle).into(),
+                (&mut oracle_owner).into(),
+                (&mut new_oracle_owner).into(),
+            ],
+        )?;
+
+        // test: the rewards go to a token account of the new oracle owner
+        let mut reward_receiver = TAccount::new_token_account(&new_oracle_owner.pubkey);
+        process_instruction(
+            instruction::remove_oracle_with_rewards(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &oracle.pubkey,
+                &oracle_registry.pubkey,
+                &destination.pubkey,
+                &faucet.pubkey,
+                &reward_receiver.pubkey,
+            )?,
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle).into(),
+                (&mut oracle_registry).into(),
+                (&mut destination).into(),
+                (&mut token_program).into(),
+                (&mut faucet).into(),
+                (&mut faucet_owner).into(),
+                (&mut reward_receiver).into(),
+            ],
+        )?;
+        assert_eq!(
+            crate::list_oracles(&program_id, &aggregator.info(), &oracle_registry.info())?,
+            vec![]
+        );
+
+        // the changes of a timelocked aggregator are queued, then executed or
+        // cancelled
+        process_instruction(
+            instruction::update_config(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                AggregatorConfigUpdate {
+                    timelock_delay: Some(10),
+                    ..AggregatorConfigUpdate::default()
+                },
+            )?,
+            &mut [(&mut aggregator).into(), (&mut aggregator_owner).into()],
+        )?;
+        let new_pending_change = || {
+            TAccount::new_rent_exempt(
+                &program_id,
+                borsh_utils::get_account_len::<PendingChange>(),
+                false,
+            )
+        };
+
+        let mut oracle2 =
+            TAccount::new_rent_exempt(&program_id, borsh_utils::get_account_len::<Oracle>(), false);
+        let mut oracle_owner2 = TAccount::new(&program_id, false);
+        let mut pending_change = new_pending_change();
+        process_instruction(
+            instruction::with_pending_change(
+                instruction::add_oracle(
+                    &program_id,
+                    &aggregator.pubkey,
+                    &aggregator_owner.pubkey,
+                    &oracle2.pubkey,
+                    &oracle_owner2.pubkey,
+                    &oracle_registry.pubkey,
+                    [0xab; 32],
+                )?,
+                &pending_change.pubkey,
+            ),
+            &mut [
+                (&mut rent_sysvar).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle2).into(),
+                (&mut oracle_owner2).into(),
+                (&mut oracle_registry).into(),
+                (&mut pending_change).into(),
+                (&mut self::rent_sysvar()).into(),
+                (&mut sysclock(200)).into(),
+            ],
+        )?;
+        process_instruction(
+            instruction::execute_pending_add_oracle(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &pending_change.pubkey,
+                &destination.pubkey,
+                &oracle2.pubkey,
+                &oracle_registry.pubkey,
+            )?,
+            &mut [
+                (&mut sysclock(210)).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+                (&mut rent_sysvar).into(),
+                (&mut oracle2).into(),
+                (&mut oracle_registry).into(),
+            ],
+        )?;
+
+        let mut pending_change = new_pending_change();
+        process_instruction(
+            instruction::with_pending_change(
+                instruction::set_oracle_weight(
+                    &program_id,
+                    &aggregator.pubkey,
+                    &aggregator_owner.pubkey,
+                    &oracle2.pubkey,
+                    3,
+                )?,
+                &pending_change.pubkey,
+            ),
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle2).into(),
+                (&mut pending_change).into(),
+                (&mut rent_sysvar).into(),
+                (&mut sysclock(210)).into(),
+            ],
+        )?;
+        process_instruction(
+            instruction::execute_pending_set_oracle_weight(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &pending_change.pubkey,
+                &destination.pubkey,
+                &oracle2.pubkey,
+            )?,
+            &mut [
+                (&mut sysclock(220)).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+                (&mut oracle2).into(),
+            ],
+        )?;
+        assert_eq!(Oracle::load_initialized(&oracle2.info())?.weight, 3);
+
+        process_instruction(
+            instruction::submit_signed(
+                &program_id,
+                &aggregator.pubkey,
+                &round_submissions.pubkey,
+                &answer_submissions.pubkey,
+                &oracle2.pubkey,
+                &oracle_owner2.pubkey,
+                Some(&answer_history.pubkey),
+                3,
+                44,
+            )?,
+            &mut [
+                (&mut sysclock(220)).into(),
+                (&mut aggregator).into(),
+                (&mut round_submissions).into(),
+                (&mut answer_submissions).into(),
+                (&mut oracle2).into(),
+                (&mut oracle_owner2).into(),
+                (&mut answer_history).into(),
+            ],
+        )?;
+        let aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(aggregator_state.answer.round_id, 3);
+        assert_eq!(aggregator_state.answer.median, 44);
+
+        let mut pending_change = new_pending_change();
+        process_instruction(
+            instruction::with_pending_change(
+                instruction::configure(
+                    &program_id,
+                    &aggregator.pubkey,
+                    &aggregator_owner.pubkey,
+                    AggregatorConfig {
+                        description: [0x12; 32],
+                        ..aggregator_state.config.clone()
+                    },
+                )?,
+                &pending_change.pubkey,
+            ),
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut rent_sysvar).into(),
+                (&mut sysclock(220)).into(),
+            ],
+        )?;
+        process_instruction(
+            instruction::execute_pending(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &pending_change.pubkey,
+                &destination.pubkey,
+            )?,
+            &mut [
+                (&mut sysclock(230)).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+            ],
+        )?;
+        let aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(aggregator_state.config.description, [0x12; 32]);
+
+        let mut pending_change = new_pending_change();
+        process_instruction(
+            instruction::with_pending_change(
+                instruction::update_config(
+                    &program_id,
+                    &aggregator.pubkey,
+                    &aggregator_owner.pubkey,
+                    AggregatorConfigUpdate {
+                        description: Some([0x34; 32]),
+                        ..AggregatorConfigUpdate::default()
+                    },
+                )?,
+                &pending_change.pubkey,
+            ),
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut rent_sysvar).into(),
+                (&mut sysclock(230)).into(),
+            ],
+        )?;
+        process_instruction(
+            instruction::cancel_pending(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &pending_change.pubkey,
+                &destination.pubkey,
+            )?,
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+            ],
+        )?;
+        let aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(aggregator_state.config.description, [0x12; 32]);
+        assert_eq!(aggregator_state.pending_changes, 0);
+
+        let mut pending_change = new_pending_change();
+        process_instruction(
+            instruction::with_pending_change(
+                instruction::remove_oracle(
+                    &program_id,
+                    &aggregator.pubkey,
+                    &aggregator_owner.pubkey,
+                    &oracle2.pubkey,
+                    &oracle_registry.pubkey,
+                    &destination.pubkey,
+                )?,
+                &pending_change.pubkey,
+            ),
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle2).into(),
+                (&mut oracle_registry).into(),
+                (&mut destination).into(),
+                (&mut pending_change).into(),
+                (&mut rent_sysvar).into(),
+                (&mut sysclock(230)).into(),
+            ],
+        )?;
+        let mut oracle_destination = TAccount::new(&program_id, false);
+        let mut reward_receiver2 = TAccount::new_token_account(&oracle_owner2.pubkey);
+        process_instruction(
+            instruction::execute_pending_remove_oracle(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+                &pending_change.pubkey,
+                &destination.pubkey,
+                &oracle2.pubkey,
+                &oracle_registry.pubkey,
+                &oracle_destination.pubkey,
+                Some((&faucet.pubkey, &reward_receiver2.pubkey)),
+            )?,
+            &mut [
+                (&mut sysclock(240)).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+                (&mut oracle2).into(),
+                (&mut oracle_registry).into(),
+                (&mut oracle_destination).into(),
+                (&mut token_program).into(),
+                (&mut faucet).into(),
+                (&mut faucet_owner).into(),
+                (&mut reward_receiver2).into(),
+            ],
+        )?;
+        assert_eq!(
+            crate::list_oracles(&program_id, &aggregator.info(), &oracle_registry.info())?,
+            vec![]
+        );
+
+        // the ownership is proposed, cancelled, then proposed again & accepted
+        let mut new_owner = TAccount::new(&program_id, false);
+        let propose_owner = instruction::propose_owner(
+            &program_id,
+            &aggregator.pubkey,
+            &aggregator_owner.pubkey,
+            &new_owner.pubkey,
+        )?;
+        process_instruction(
+            propose_owner.clone(),
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut new_owner).into(),
+            ],
+        )?;
+        process_instruction(
+            instruction::cancel_ownership_transfer(
+                &program_id,
+                &aggregator.pubkey,
+                &aggregator_owner.pubkey,
+            )?,
+            &mut [(&mut aggregator).into(), (&mut aggregator_owner).into()],
+        )?;
+        assert!(!Aggregator::load_initialized(&aggregator.info())?.has_pending_owner());
+        process_instruction(
+            propose_owner,
+            &mut [
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut new_owner).into(),
+            ],
+        )?;
+        process_instruction(
+            instruction::accept_ownership(&program_id, &aggregator.pubkey, &new_owner.pubkey)?,
+            &mut [(&mut aggregator).into(), (&mut new_owner).into()],
+        )?;
+        assert_eq!(
+            Aggregator::load_initialized(&aggregator.info())?.owner,
+            PublicKey(new_owner.pubkey.to_bytes())
+        );
+
+        // the new owner hands the aggregator over to a multisig admin, which
+        // closes it
+        let mut signer = TAccount::new(&program_id, false);
+        process_instruction(
+            instruction::set_admin_multisig(
+                &program_id,
+                &aggregator.pubkey,
+                &new_owner.pubkey,
+                1,
+                &[signer.pubkey],
+            )?,
+            &mut [(&mut aggregator).into(), (&mut new_owner).into()],
+        )?;
+        process_instruction(
+            instruction::with_signers(
+                instruction::close_aggregator(
+                    &program_id,
+                    &aggregator.pubkey,
+                    &new_owner.pubkey,
+                    &round_submissions.pubkey,
+                    &answer_submissions.pubkey,
+                    &oracle_registry.pubkey,
+                    &destination.pubkey,
+                    Some(&answer_history.pubkey),
+                    Some((&faucet.pubkey, &reward_receiver2.pubkey)),
+                )?,
+                &[&signer.pubkey],
+            ),
+            &mut [
+                (&mut aggregator).into(),
+                (&mut new_owner).into(),
+                (&mut round_submissions).into(),
+                (&mut answer_submissions).into(),
+                (&mut oracle_registry).into(),
+                (&mut destination).into(),
+                (&mut answer_history).into(),
+                (&mut token_program).into(),
+                (&mut faucet).into(),
+                (&mut faucet_owner).into(),
+                (&mut reward_receiver2).into(),
+                (&mut signer).into(),
+            ],
+        )?;
+        assert_eq!(aggregator.account.lamports, 0);
+
+        // a named feed is created at the address derived from its owner & name
+        let (feed_address, _) =
+            crate::find_feed_address(&program_id, &aggregator_owner.pubkey, "btc:usd")?;
+        let mut feed = TAccount::new_rent_exempt(
+            &program_id,
+            borsh_utils::get_account_len::<Aggregator>(),
+            false,
+        );
+        feed.pubkey = feed_address;
+        let new_account = |len: usize| TAccount::new_rent_exempt(&program_id, len, false);
+        let mut round_submissions = new_account(Submissions::account_len(MAX_ORACLES));
+        let mut answer_submissions = new_account(Submissions::account_len(MAX_ORACLES));
+        let mut oracle_registry = new_account(OracleRegistry::ACCOUNT_LEN);
+        let mut payer = TAccount::new(&program_id, false);
+        let mut system_program = TAccount::new(&program_id, false);
+        system_program.pubkey = solana_program::system_program::id();
+        process_instruction(
+            instruction::initialize_feed(
+                &program_id,
+                &payer.pubkey,
+                &aggregator_owner.pubkey,
+                &round_submissions.pubkey,
+                &answer_submissions.pubkey,
+                &oracle_registry.pubkey,
+                "btc:usd",
+                config,
+            )?,
+            &mut [
+                (&mut rent_sysvar).into(),
+                (&mut feed).into(),
+                (&mut aggregator_owner).into(),
+                (&mut round_submissions).into(),
+                (&mut answer_submissions).into(),
+                (&mut oracle_registry).into(),
+                (&mut payer).into(),
+                (&mut system_program).into(),
+            ],
+        )?;
+        assert_eq!(
+            Aggregator::load_initialized(&feed.info())?.feed_name(),
+            b"btc:usd"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_answer_history() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            mut oracle_registry,
+            mut round_submissions,
+            mut answer_submissions,
+            ..
+        } = create_aggregator(&program_id)?;
+        let (mut oracle, mut oracle_owner) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        let (mut oracle2, mut oracle_owner2) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+
+        let mut rent_sysvar = rent_sysvar();
+        let mut answer_history =
+            TAccount::new_rent_exempt(&program_id, AnswerHistory::ACCOUNT_LEN, false);
+
+        // test: no history attached yet
+        assert_eq!(
+            crate::read_history(&program_id, &aggregator.info(), &answer_history.info())
+                .map_err(Error::from),
+            Err(Error::NoAnswerHistory),
+        );
+
+        process(
+            &program_id,
+            instruction::Instruction::InitializeHistory,
+            vec![
+                (&mut rent_sysvar).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut answer_history).into(),
+            ]
+            .as_slice(),
+        )?;
+
+        let mut submit = |oracle: &mut TAccount,
+                          oracle_owner: &mut TAccount,
+                          answer_history: Option<&mut TAccount>,
+                          time: u64,
+                          round_id: u64,
+                          value: u64| {
+            let mut clock = sysclock(time as i64);
+            let mut accounts: Vec<AccountInfo> = vec![
+                (&mut clock).into(),
+                (&mut aggregator).into(),
+                (&mut round_submissions).into(),
+                (&mut answer_submissions).into(),
+                oracle.into(),
+                oracle_owner.into(),
+            ];
+            if let Some(answer_history) = answer_history {
+                accounts.push(answer_history.into());
+            }
+
+            process(
+                &program_id,
+                instruction::Instruction::Submit { round_id, value },
+                accounts.as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        submit(&mut oracle, &mut oracle_owner, Some(&mut answer_history), 100, 0, 10)?;
+        submit(&mut oracle2, &mut oracle_owner2, Some(&mut answer_history), 200, 0, 20)?;
+        submit(&mut oracle, &mut oracle_owner, Some(&mut answer_history), 300, 1, 30)?;
+        submit(&mut oracle2, &mut oracle_owner2, Some(&mut answer_history), 400, 1, 50)?;
+
+        // test: history must be passed in once the round is resolved
+        submit(&mut oracle2, &mut oracle_owner2, None, 500, 2, 60)?;
+        assert_eq!(
+            submit(&mut oracle, &mut oracle_owner, None, 600, 2, 70),
+            Err(Error::from(ProgramError::NotEnoughAccountKeys)),
+        );
+
+        let history = crate::read_history(&program_id, &aggregator.info(), &answer_history.info())?;
+        assert_eq!(
+            history,
+            vec![
+                Answer {
+                    round_id: 0,
+                    median: 15,
+                    signed_median: 15,
+                    created_at: 200,
+                    updated_at: 200,
+                    created_at_timestamp: GENESIS_TIMESTAMP + 200,
+                    updated_at_timestamp: GENESIS_TIMESTAMP + 200,
+                    submissions: 2,
+                    method: AggregationMethod::Median,
+                    dispersion: Dispersion {
+                        min: 10,
+                        max: 20,
+                        mad: 5,
+                    },
+                },
+                Answer {
+                    round_id: 1,
+                    median: 40,
+                    signed_median: 40,
+                    created_at: 400,
+                    updated_at: 400,
+                    created_at_timestamp: GENESIS_TIMESTAMP + 400,
+                    updated_at_timestamp: GENESIS_TIMESTAMP + 400,
+                    submissions: 2,
+                    method: AggregationMethod::Median,
+                    dispersion: Dispersion {
+                        min: 30,
+                        max: 50,
+                        mad: 10,
+                    },
+                },
+            ]
+        );
+
+        let answer =
+            crate::read_answer_at_round(&program_id, &aggregator.info(), &answer_history.info(), 0)?;
+        assert_eq!(answer.median, 15);
+        assert_eq!(
+            crate::read_answer_at_round(&program_id, &aggregator.info(), &answer_history.info(), 2)
+                .map_err(Error::from),
+            Err(Error::RoundNotInHistory),
+        );
+
+        // test: the history of an aggregator of another program is not read
+        let mut foreign = aggregator.foreign_copy();
+        assert_eq!(
+            crate::read_history(&program_id, &foreign.info(), &answer_history.info())
+                .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+        );
+        assert_eq!(
+            crate::read_answer_at_round(&program_id, &foreign.info(), &answer_history.info(), 0)
+                .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+        );
+
+        // (15 * 200 + 40 * 100) / 300
+        let clock = Clock {
+            slot: 500,
+            unix_timestamp: GENESIS_TIMESTAMP + 500,
+            ..Clock::default()
+        };
+        let twap = crate::read_twap(
+            &program_id,
+            &aggregator.info(),
+            &answer_history.info(),
+            &clock,
+            300,
+        )?;
+        assert_eq!(twap, 23);
+
+        // test: the TWAP of an aggregator of another program, or of a paused
+        // feed, is not read
+        assert_eq!(
+            crate::read_twap(
+                &program_id,
+                &foreign.info(),
+                &answer_history.info(),
+                &clock,
+                300
+            )
+            .map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch),
+        );
+        process(
+            &program_id,
+            instruction::Instruction::Pause,
+            vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
+        )?;
+        assert_eq!(
+            crate::read_twap(
+                &program_id,
+                &aggregator.info(),
+                &answer_history.info(),
+                &clock,
+                300
+            )
+            .map_err(Error::from),
+            Err(Error::AggregatorPaused),
+        );
+
+        Ok(())
+    }
+
+    struct SubmitTestFixture {
+        program_id: Pubkey,
+        t_aggregator: TAggregator,
+    }
+
+    impl SubmitTestFixture {
+        fn submit(
+            &mut self,
+            oracle: &mut TAccount,
+            oracle_owner: &mut TAccount,
+            time: u64,
+            round_id: u64,
+            value: u64,
+        ) -> Result<Aggregator, ProgramError> {
+            let mut clock = sysclock(time as i64);
+
+            process(
+                &self.program_id,
+                instruction::Instruction::Submit { round_id, value },
+                vec![
+                    (&mut clock).into(),
+                    self.t_aggregator.aggregator.info(),
+                    self.t_aggregator.round_submissions.info(),
+                    self.t_aggregator.answer_submissions.info(),
+                    oracle.into(),
+                    oracle_owner.into(),
+                ]
+                .as_slice(),
+            )?;
+
+            Aggregator::load_initialized(&self.t_aggregator.aggregator.info())
+        }
+
+        fn aggregator(&mut self) -> Result<Aggregator, ProgramError> {
+            Aggregator::load_initialized(&self.t_aggregator.aggregator.info())
+        }
+
+        fn create_oracle(&mut self) -> Result<(TAccount, TAccount), ProgramError> {
+            create_oracle(
+                &self.program_id,
+                &mut self.t_aggregator.aggregator,
+                &mut self.t_aggregator.aggregator_owner,
+                &mut self.t_aggregator.oracle_registry,
+            )
+        }
+
+        fn round_submission(&mut self, i: usize) -> Result<Submission, ProgramError> {
+            self.aggregator()?
+                .round_submissions(&self.t_aggregator.round_submissions.info())?
+                .get(i)
+        }
+
+        fn round_submissions(&mut self) -> Result<Vec<Submission>, ProgramError> {
+            self.aggregator()?
+                .round_submissions(&self.t_aggregator.round_submissions.info())?
+                .submissions()
+        }
+
+        fn answer_submission(&mut self, i: usize) -> Result<Submission, ProgramError> {
+            self.aggregator()?
+                .answer_submissions(&self.t_aggregator.answer_submissions.info())?
+                .get(i)
+        }
+
+        fn answer_submissions(&mut self) -> Result<Vec<Submission>, ProgramError> {
+            self.aggregator()?
+                .answer_submissions(&self.t_aggregator.answer_submissions.info())?
+                .submissions()
+        }
+    }
+
+    #[test]
+    fn test_submit() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut tt = SubmitTestFixture {
+            program_id,
+            t_aggregator: create_aggregator(&program_id)?,
+        };
+
+        let (mut oracle, mut oracle_owner) = tt.create_oracle()?;
+        let (mut oracle2, mut oracle_owner2) = tt.create_oracle()?;
+        let (mut oracle3, mut oracle_owner3) = tt.create_oracle()?;
+
+        let time = 100;
+        let agr = tt.submit(&mut oracle, &mut oracle_owner, time, 0, 1)?;
+        let oracle_state = Oracle::load_initialized(&oracle.info())?;
+        let sub = tt.round_submission(0)?;
+        let round = &agr.round;
+        assert_eq!(oracle_state.withdrawable, 10);
+        assert_eq!(round.created_at, time);
+        assert_eq!(round.updated_at, time);
+        assert_eq!(sub.oracle, oracle.pubkey.to_bytes());
+        assert_eq!(sub.value, 1);
+        assert_eq!(sub.updated_at, 100);
+        assert_eq!(agr.answer.is_initialized(), false);
+
+        // test: should fail with repeated submission
+        assert_eq!(
+            tt.submit(&mut oracle, &mut oracle_owner, time + 10, 0, 2)
+                .map_err(Error::from),
+            Err(Error::OracleAlreadySubmitted),
+            "should fail if oracle submits repeatedly in the same round"
+        );
+
+        let old_time = time;
+        let time = 200;
+        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time, 0, 2)?;
+        let oracle_state = Oracle::load_initialized(&oracle.info())?;
+        let sub = tt.round_submission(1)?;
+        let round = &agr.round;
+        assert_eq!(oracle_state.withdrawable, 10);
+        assert_eq!(round.created_at, old_time);
+        assert_eq!(round.updated_at, time);
+        assert_eq!(sub.oracle, oracle2.pubkey.to_bytes());
+        assert_eq!(sub.value, 2);
+        assert_eq!(sub.updated_at, time);
+
+        // // test: answer resolved when min_submissions is reached
+        let answer = &agr.answer;
+        assert_eq!(answer.is_initialized(), true);
+        assert_eq!(answer.updated_at, time);
+        assert_eq!(answer.created_at, time);
+
+        let answer_submissions = tt.answer_submissions()?;
+        let round_submissions = tt.round_submissions()?;
+        assert_eq!(answer_submissions, round_submissions);
+
+        // test: max submission reached
+        assert_eq!(
+            tt.submit(&mut oracle3, &mut oracle_owner3, time + 10, 0, 2)
+                .map_err(Error::from),
+            Err(Error::MaxSubmissionsReached),
+        );
+
+        // test: start new round
+        let time = 300;
+        let agr = tt.submit(&mut oracle, &mut oracle_owner, time, 1, 10)?;
+        let oracle_state = Oracle::load_initialized(&oracle.info())?;
+        let sub = tt.round_submission(0)?;
+        let round = &agr.round;
+        assert_eq!(oracle_state.withdrawable, 20);
+        assert_eq!(round.id, 1);
+        assert_eq!(round.created_at, time);
+        assert_eq!(round.updated_at, time);
+        assert_eq!(sub.oracle, oracle.pubkey.to_bytes());
+        assert_eq!(sub.value, 10);
+        assert_eq!(sub.updated_at, time);
+        assert_eq!(
+            tt.round_submission(1)?.is_initialized(),
+            false,
+            "other submissions should've been zero after starting a new round"
+        );
+
+        // the last round answer should be reserved
+        let answer = &agr.answer;
+        assert_eq!(answer.is_initialized(), true);
+        assert_eq!(answer.round_id, 0);
+        assert_eq!(answer.updated_at, 200);
+        assert_eq!(answer.created_at, 200);
+
+        // test: oracle cannot immediately start a new round
+        assert_eq!(
+            tt.submit(&mut oracle, &mut oracle_owner, time + 10, 2, 2)
+                .map_err(Error::from),
+            Err(Error::OracleNewRoundCooldown),
+        );
+
+        // test: resolve a new round
+        let time = 400;
+        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time, 1, 20)?;
+        let sub = tt.round_submission(1)?;
+
+        assert_eq!(sub.oracle, oracle2.pubkey.to_bytes());
+        assert_eq!(sub.value, 20);
+        assert_eq!(sub.updated_at, time);
+
+        let answer = &agr.answer;
+        assert_eq!(answer.is_initialized(), true);
+        assert_eq!(answer.round_id, 1);
+        assert_eq!(answer.updated_at, time);
+        assert_eq!(answer.created_at, time);
+        assert_eq!(answer.median, 15);
+
+        assert_eq!(tt.answer_submission(0)?.value, 10);
+        assert_eq!(tt.answer_submission(1)?.value, 20);
+
+        let time = 500;
+        // let oracle 2 start a new round
+        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time, 2, 200)?;
+        let round = &agr.round;
+        assert_eq!(round.id, 2);
+
+        let agr = tt.submit(&mut oracle, &mut oracle_owner, time + 2, 3, 200)?;
+        let round = &agr.round;
+        assert_eq!(round.id, 3);
+
+        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time + 4, 4, 200)?;
+        let round = &agr.round;
+        assert_eq!(round.id, 4);
+
+        // InvalidRoundID
+        assert_eq!(
+            tt.submit(&mut oracle, &mut oracle_owner, time + 6, 10, 1000)
+                .map_err(Error::from),
+            Err(Error::InvalidRoundID),
+            "should only be able to start a round with current_round.id + 1"
+        );
+
+        assert_eq!(
+            tt.submit(&mut oracle3, &mut oracle_owner3, time + 8, 3, 1000)
+                .map_err(Error::from),
+            Err(Error::InvalidRoundID),
+            "should not be able to submit answer to previous rounds"
+        );
+
+        // test: round timeout (for round_timeout = 1 and restart_delay = 1)
+        assert_eq!(tt.t_aggregator.config.round_timeout, 1);
+        assert_eq!(tt.t_aggregator.config.restart_delay, 1);
+        let time = 600;
+        let round_id = 5;
+        let agr = tt.submit(&mut oracle, &mut oracle_owner, time, round_id, 200)?;
+        let round = &agr.round;
+        assert_eq!(round.id, round_id);
+        assert_eq!(round.created_at, time);
+        assert_eq!(
+            tt.submit(&mut oracle, &mut oracle_owner, time + 1, round_id + 1, 200)
+                .map_err(Error::from),
+            Err(Error::OracleNewRoundCooldown),
+            "should wait for one round (restart delay)"
+        );
+        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time + 1, round_id + 1, 200)?;
+        let round = &agr.round;
+        assert_eq!(round.id, round_id + 1);
+        assert_eq!(
+            tt.submit(&mut oracle, &mut oracle_owner, time + 1, round_id + 2, 200)
+                .map_err(Error::from),
+            Err(Error::OracleRoundTimeout),
+            "should wait for round timeout (time + 2)"
+        );
+        let agr = tt.submit(&mut oracle, &mut oracle_owner, time + 2, round_id + 2, 200)?;
+        let round = &agr.round;
+        assert_eq!(round.id, round_id + 2); 
+        assert_eq!(round.created_at, time + 2); 
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_admin_multisig() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            ..
+        } = create_aggregator(&program_id)?;
+        let mut signers: Vec<TAccount> = (0..3).map(|_| TAccount::new(&program_id, true)).collect();
+        let keys: Vec<PublicKey> = signers
+            .iter()
+            .map(|s| PublicKey(s.pubkey.to_bytes()))
+            .collect();
+
+        let set_admin = |threshold: u8, keys: Vec<PublicKey>| {
+            instruction::Instruction::SetAdminMultisig { threshold, keys }
+        };
+        let update_config = || instruction::Instruction::UpdateConfig {
+            update: AggregatorConfigUpdate::default(),
+        };
+
+        // test: the keys of a multisig are validated
+        assert_eq!(
+            process(
+                &program_id,
+                set_admin(4, keys.clone()),
+                &[aggregator.info(), aggregator_owner.info()]
+            )
+            .map_err(Error::from),
+            Err(Error::InvalidMultisig)
+        );
+        assert_eq!(
+            process(
+                &program_id,
+                set_admin(2, vec![keys[0], keys[1], keys[0]]),
+                &[aggregator.info(), aggregator_owner.info()]
+            )
+            .map_err(Error::from),
+            Err(Error::DuplicateSigner)
+        );
+
+        process(
+            &program_id,
+            set_admin(2, keys.clone()),
+            &[aggregator.info(), aggregator_owner.info()],
+        )
+        .map_err(Error::from)?;
+
+        // test: the owner alone no longer administers the aggregator
+        assert_eq!(
+            process(
+                &program_id,
+                update_config(),
+                &[aggregator.info(), aggregator_owner.info()]
+            )
+            .map_err(Error::from),
+            Err(Error::NotEnoughSigners)
+        );
+
+        // test: the threshold is not met
+        let (s0, rest) = signers.split_first_mut().unwrap();
+        let (s1, rest) = rest.split_first_mut().unwrap();
+        let s2 = &mut rest[0];
+        assert_eq!(
+            process(
+                &program_id,
+                update_config(),
+                &[aggregator.info(), s0.info()]
+            )
+            .map_err(Error::from),
+            Err(Error::NotEnoughSigners)
+        );
+
+        // test: a signer passed twice counts once
+        let s0_info = s0.info();
+        assert_eq!(
+            process(
+                &program_id,
+                update_config(),
+                &[aggregator.info(), s0_info.clone(), s0_info]
+            )
+            .map_err(Error::from),
+            Err(Error::NotEnoughSigners)
+        );
+
+        // test: a key that did not sign does not count
+        s1.is_signer = false;
+        assert_eq!(
+            process(
+                &program_id,
+                update_config(),
+                &[aggregator.info(), s0.info(), s1.info()]
+            )
+            .map_err(Error::from),
+            Err(Error::NotEnoughSigners)
+        );
+        s1.is_signer = true;
+
+        // test: the threshold is met
+        process(
+            &program_id,
+            update_config(),
+            &[aggregator.info(), s0.info(), s1.info()],
+        )
+        .map_err(Error::from)?;
+        process(
+            &program_id,
+            update_config(),
+            &[
+                aggregator.info(),
+                s2.info(),
+                aggregator_owner.info(),
+                s0.info(),
+            ],
+        )
+        .map_err(Error::from)?;
+
+        // test: the multisig hands the administration back to the owner
+        process(
+            &program_id,
+            set_admin(0, vec![]),
+            &[aggregator.info(), s1.info(), s2.info()],
+        )
+        .map_err(Error::from)?;
+        process(
+            &program_id,
+            update_config(),
+            &[aggregator.info(), aggregator_owner.info()],
+        )
+        .map_err(Error::from)?;
+        assert_eq!(
+            process(
+                &program_id,
+                update_config(),
+                &[aggregator.info(), s0.info(), s1.info()]
+            )
+            .map_err(Error::from),
+            Err(Error::OwnerMismatch)
+        );
+
+        // test: a new owner takes over from the multisig
+        let mut new_owner = TAccount::new(&program_id, true);
+        process(
+            &program_id,
+            set_admin(2, keys.clone()),
+            &[aggregator.info(), aggregator_owner.info()],
+        )
+        .map_err(Error::from)?;
+        process(
+            &program_id,
+            instruction::Instruction::ProposeOwner,
+            &[aggregator.info(), s0.info(), new_owner.info(), s2.info()],
+        )
+        .map_err(Error::from)?;
+        process(
+            &program_id,
+            instruction::Instruction::AcceptOwnership,
+            &[aggregator.info(), new_owner.info()],
+        )
+        .map_err(Error::from)?;
+        process(
+            &program_id,
+            update_config(),
+            &[aggregator.info(), new_owner.info()],
+        )
+        .map_err(Error::from)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_timelock() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+        let TAggregator {
+            mut aggregator,
+            mut aggregator_owner,
+            mut oracle_registry,
+            config,
+            ..
+        } = create_aggregator_with_config(
+            &program_id,
+            AggregatorConfig {
+                min_submissions: 1,
+                max_submissions: 2,
+                timelock_delay: 10,
+                ..AggregatorConfig::default()
+            },
+        )?;
+        let mut rent = rent_sysvar();
+        let mut destination = TAccount::new(&program_id, false);
+        let new_pending_change = || {
+            TAccount::new_rent_exempt(
+                &program_id,
+                borsh_utils::get_account_len::<PendingChange>(),
+                false,
+            )
+        };
+        let new_oracle = || {
+            TAccount::new_rent_exempt(&program_id, borsh_utils::get_account_len::<Oracle>(), false)
+        };
+
+        // test: a timelocked change requires a pending change account
+        let new_config = AggregatorConfig {
+            min_submissions: 2,
+            ..config.clone()
+        };
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::Configure {
+                    config: new_config.clone(),
+                },
+                vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
+            ),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        let mut pending_change = new_pending_change();
+        process(
+            &program_id,
+            instruction::Instruction::Configure {
+                config: new_config.clone(),
+            },
+            vec![
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut rent).into(),
+                (&mut sysclock(100)).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(
+            Aggregator::load_initialized(&aggregator.info())?.config,
+            config
+        );
+
+        // test: the change waits for the timelock delay
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::ExecutePending,
+                vec![
+                    (&mut sysclock(109)).into(),
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut pending_change).into(),
+                    (&mut destination).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::PendingChangeNotReady)
+        );
+        process(
+            &program_id,
+            instruction::Instruction::ExecutePending,
+            vec![
+                (&mut sysclock(110)).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(
+            Aggregator::load_initialized(&aggregator.info())?.config,
+            new_config
+        );
+
+        // test: the pending change is closed once executed
+        assert_eq!(pending_change.account.lamports, 0);
+        assert_eq!(
+            destination.account.lamports,
+            rent_exempt_balance(borsh_utils::get_account_len::<PendingChange>())
+        );
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::ExecutePending,
+                vec![
+                    (&mut sysclock(110)).into(),
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut pending_change).into(),
+                    (&mut destination).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::AccountTypeMismatch)
+        );
+
+        // test: queued updates are each applied to the config at execution, so
+        // that a later one doesn't revert an earlier one
+        let mut pending_changes = [new_pending_change(), new_pending_change()];
+        let updates = [
+            AggregatorConfigUpdate {
+                reward_amount: Some(5),
+                ..AggregatorConfigUpdate::default()
+            },
+            AggregatorConfigUpdate {
+                min_submissions: Some(1),
+                ..AggregatorConfigUpdate::default()
+            },
+        ];
+        for (pending_change, update) in pending_changes.iter_mut().zip(updates.iter()) {
+            process(
+                &program_id,
+                instruction::Instruction::UpdateConfig {
+                    update: update.clone(),
+                },
+                vec![
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    pending_change.into(),
+                    (&mut rent).into(),
+                    (&mut sysclock(150)).into(),
+                ]
+                .as_slice(),
+            )?;
+        }
+        for pending_change in pending_changes.iter_mut() {
+            process(
+                &program_id,
+                instruction::Instruction::ExecutePending,
+                vec![
+                    (&mut sysclock(160)).into(),
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    pending_change.into(),
+                    (&mut destination).into(),
+                ]
+                .as_slice(),
+            )?;
+        }
+        assert_eq!(
+            Aggregator::load_initialized(&aggregator.info())?.config,
+            AggregatorConfig {
+                reward_amount: 5,
+                min_submissions: 1,
+                ..new_config.clone()
+            }
+        );
+
+        // test: an oracle is added once the change is executed
+        let mut oracle = new_oracle();
+        let mut oracle_owner = TAccount::new(&program_id, false);
+        let mut pending_change = new_pending_change();
+        process(
+            &program_id,
+            instruction::Instruction::AddOracle {
+                description: [0xab; 32],
+            },
+            vec![
+                (&mut rent).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle).into(),
+                (&mut oracle_owner).into(),
+                (&mut oracle_registry).into(),
+                (&mut pending_change).into(),
+                (&mut rent_sysvar()).into(),
+                (&mut sysclock(200)).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert!(
+            crate::list_oracles(&program_id, &aggregator.info(), &oracle_registry.info())?
+                .is_empty()
+        );
+
+        // test: the accounts of the change are checked
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::ExecutePending,
+                vec![
+                    (&mut sysclock(210)).into(),
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut pending_change).into(),
+                    (&mut destination).into(),
+                    (&mut rent).into(),
+                    (&mut new_oracle()).into(),
+                    (&mut oracle_registry).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::PendingChangeMismatch)
+        );
+
+        process(
+            &program_id,
+            instruction::Instruction::ExecutePending,
+            vec![
+                (&mut sysclock(210)).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+                (&mut rent).into(),
+                (&mut oracle).into(),
+                (&mut oracle_registry).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(
+            crate::list_oracles(&program_id, &aggregator.info(), &oracle_registry.info())?,
+            vec![oracle.pubkey]
+        );
+        let added = Oracle::load_initialized(&oracle.info())?;
+        assert_eq!(added.owner, PublicKey(oracle_owner.pubkey.to_bytes()));
+        assert_eq!(added.description, [0xab; 32]);
+
+        // test: an oracle weight is set once the change is executed
+        let mut pending_change = new_pending_change();
+        process(
+            &program_id,
+            instruction::Instruction::SetOracleWeight { weight: 3 },
+            vec![
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle).into(),
+                (&mut pending_change).into(),
+                (&mut rent).into(),
+                (&mut sysclock(250)).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(Oracle::load_initialized(&oracle.info())?.weight, 1);
+        process(
+            &program_id,
+            instruction::Instruction::ExecutePending,
+            vec![
+                (&mut sysclock(260)).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+                (&mut oracle).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(Oracle::load_initialized(&oracle.info())?.weight, 3);
+
+        // test: a queued removal can be cancelled, by the owner only
+        let mut pending_change = new_pending_change();
+        process(
+            &program_id,
+            instruction::Instruction::RemoveOracle,
+            vec![
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle).into(),
+                (&mut oracle_registry).into(),
+                (&mut destination).into(),
+                (&mut pending_change).into(),
+                (&mut rent).into(),
+                (&mut sysclock(300)).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::CancelPending,
+                vec![
+                    (&mut aggregator).into(),
+                    (&mut TAccount::new(&program_id, true)).into(),
+                    (&mut pending_change).into(),
+                    (&mut destination).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::OwnerMismatch)
+        );
+        process(
+            &program_id,
+            instruction::Instruction::CancelPending,
+            vec![
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::ExecutePending,
+                vec![
+                    (&mut sysclock(310)).into(),
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut pending_change).into(),
+                    (&mut destination).into(),
+                    (&mut oracle).into(),
+                    (&mut oracle_registry).into(),
+                    (&mut TAccount::new(&program_id, false)).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::AccountTypeMismatch)
+        );
+
+        // test: an oracle is removed once the change is executed
+        let mut pending_change = new_pending_change();
+        process(
+            &program_id,
+            instruction::Instruction::RemoveOracle,
+            vec![
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut oracle).into(),
+                (&mut oracle_registry).into(),
+                (&mut destination).into(),
+                (&mut pending_change).into(),
+                (&mut rent).into(),
+                (&mut sysclock(400)).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert_eq!(
+            crate::list_oracles(&program_id, &aggregator.info(), &oracle_registry.info())?,
+            vec![oracle.pubkey]
+        );
+        let mut oracle_destination = TAccount::new(&program_id, false);
+        process(
+            &program_id,
+            instruction::Instruction::ExecutePending,
+            vec![
+                (&mut sysclock(410)).into(),
+                (&mut aggregator).into(),
+                (&mut aggregator_owner).into(),
+                (&mut pending_change).into(),
+                (&mut destination).into(),
+                (&mut oracle).into(),
+                (&mut oracle_registry).into(),
+                (&mut oracle_destination).into(),
+            ]
+            .as_slice(),
+        )?;
+        assert!(
+            crate::list_oracles(&program_id, &aggregator.info(), &oracle_registry.info())?
+                .is_empty()
+        );
+        assert_eq!(
+            oracle_destination.account.lamports,
+            rent_exempt_balance(borsh_utils::get_account_len::<Oracle>())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_initialize_feed() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut aggregator_owner = TAccount::new(&program_id, true);
+        let authority = aggregator_owner.pubkey;
+
+        // test: feed names are namespaced
+        for feed_name in &["btc:usd", "pyth.crypto:eth/usd", "x:1"] {
+            crate::find_feed_address(&program_id, &authority, feed_name)?;
+        }
+        for feed_name in &["btcusd", "btc:", ":usd", "btc:usd:1", "BTC:USD", "btc: usd"] {
+            assert_eq!(
+                crate::find_feed_address(&program_id, &authority, feed_name).map_err(Error::from),
+                Err(Error::InvalidFeedName)
+            );
+        }
+        assert_eq!(
+            crate::find_feed_address(&program_id, &authority, &format!("btc:{}", "x".repeat(29)))
+                .map_err(Error::from),
+            Err(Error::InvalidFeedName)
+        );
+
+        // test: the same name is another feed for another authority
+        let other_authority = Pubkey::new_unique();
+        assert_ne!(
+            crate::find_feed_address(&program_id, &authority, "btc:usd")?,
+            crate::find_feed_address(&program_id, &other_authority, "btc:usd")?
+        );
+
+        let mut rent_sysvar = rent_sysvar();
+        let mut payer = TAccount::new(&program_id, true);
+        let mut system_program = TAccount::new(&program_id, false);
+        let new_account = |len: usize| TAccount::new_rent_exempt(&program_id, len, false);
+        let mut round_submissions = new_account(Submissions::account_len(MAX_ORACLES));
+        let mut answer_submissions = new_account(Submissions::account_len(MAX_ORACLES));
+        let mut oracle_registry = new_account(OracleRegistry::ACCOUNT_LEN);
+        // the account the program creates at the feed address
+        let mut aggregator = new_account(borsh_utils::get_account_len::<Aggregator>());
+        let (address, bump_seed) = crate::find_feed_address(&program_id, &authority, "btc:usd")?;
+        aggregator.pubkey = address;
+
+        let config = AggregatorConfig {
+            min_submissions: 1,
+            max_submissions: 1,
+            ..AggregatorConfig::default()
+        };
+        let mut initialize_feed =
+            |feed_name: &str, aggregator: &mut TAccount, aggregator_owner: &mut TAccount| {
+                process(
+                    &program_id,
+                    instruction::Instruction::InitializeFeed {
+                        feed_name: feed_name.to_string(),
+                        config: config.clone(),
+                    },
+                    vec![
+                        (&mut rent_sysvar).into(),
+                        aggregator.into(),
+                        aggregator_owner.into(),
+                        (&mut round_submissions).into(),
+                        (&mut answer_submissions).into(),
+                        (&mut oracle_registry).into(),
+                        (&mut payer).into(),
+                        (&mut system_program).into(),
+                    ]
+                    .as_slice(),
+                )
+                .map_err(Error::from)
+            };
+
+        // test: the aggregator must be at the address derived from the owner &
+        // the name
+        assert_eq!(
+            initialize_feed("eth:usd", &mut aggregator, &mut aggregator_owner),
+            Err(Error::FeedAddressMismatch)
+        );
+        assert_eq!(
+            initialize_feed(
+                "btc:usd",
+                &mut new_account(aggregator.account.data.len()),
+                &mut aggregator_owner
+            ),
+            Err(Error::FeedAddressMismatch)
+        );
+        assert_eq!(
+            initialize_feed(
+                "btc:usd",
+                &mut aggregator,
+                &mut TAccount::new(&program_id, true)
+            ),
+            Err(Error::FeedAddressMismatch)
+        );
+        assert_eq!(
+            initialize_feed("BTC:USD", &mut aggregator, &mut aggregator_owner),
+            Err(Error::InvalidFeedName)
+        );
+
+        initialize_feed("btc:usd", &mut aggregator, &mut aggregator_owner)?;
+        let state = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(state.feed_name(), b"btc:usd");
+        assert_eq!(state.feed_bump_seed, bump_seed);
+        assert_eq!(state.feed_authority, PublicKey(authority.to_bytes()));
+
+        // test: consumers can check a feed by authority & name
+        crate::check_feed_address(&program_id, &authority, "btc:usd", &aggregator.info())?;
+        assert_eq!(
+            crate::check_feed_address(&program_id, &authority, "eth:usd", &aggregator.info())
+                .map_err(Error::from),
+            Err(Error::FeedAddressMismatch)
+        );
+        assert_eq!(
+            crate::check_feed_address(&program_id, &other_authority, "btc:usd", &aggregator.info())
+                .map_err(Error::from),
+            Err(Error::FeedAddressMismatch)
+        );
+        let mut foreign = aggregator.foreign_copy();
+        assert_eq!(
+            crate::check_feed_address(&program_id, &authority, "btc:usd", &foreign.info())
+                .map_err(Error::from),
+            Err(Error::FeedAddressMismatch)
+        );
+
+        // test: the price of a named feed is only read at its address
+        assert_eq!(
+            crate::read_price(&program_id, &aggregator.info()).map_err(Error::from),
+            Err(Error::NoResolvedAnswer)
+        );
+        assert_eq!(
+            crate::read_price(&program_id, &foreign.info()).map_err(Error::from),
+            Err(Error::ProgramOwnerMismatch)
+        );
+        let mut copy = TAccount {
+            pubkey: Pubkey::new_unique(),
+            ..aggregator.foreign_copy()
+        };
+        copy.account.owner = program_id;
+        assert_eq!(
+            crate::read_price(&program_id, &copy.info()).map_err(Error::from),
+            Err(Error::FeedAddressMismatch)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pause() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut tt = SubmitTestFixture {
+            program_id,
+            t_aggregator: create_aggregator_with_config(
+                &program_id,
+                AggregatorConfig {
+                    min_submissions: 1,
+                    max_submissions: 1,
+                    ..AggregatorConfig::default()
+                },
+            )?,
+        };
+        let (mut oracle, mut oracle_owner) = tt.create_oracle()?;
+        let (mut requester, mut requester_owner) = create_requester(
+            &program_id,
+            &mut tt.t_aggregator.aggregator,
+            &mut tt.t_aggregator.aggregator_owner,
+        )?;
+        tt.submit(&mut oracle, &mut oracle_owner, 100, 1, 42)?;
+
+        let set_paused = |aggregator: &mut TAccount, owner: &mut TAccount, paused: bool| {
+            let ix = match paused {
+                true => instruction::Instruction::Pause,
+                false => instruction::Instruction::Unpause,
+            };
+            process(
+                &program_id,
+                ix,
+                vec![aggregator.into(), owner.into()].as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        // test: only the owner can pause
+        assert_eq!(
+            set_paused(&mut tt.t_aggregator.aggregator, &mut oracle_owner, true),
+            Err(Error::OwnerMismatch)
+        );
+
+        let t_aggregator = &mut tt.t_aggregator;
+        set_paused(
+            &mut t_aggregator.aggregator,
+            &mut t_aggregator.aggregator_owner,
+            true,
+        )?;
+
+        // test: the feed is frozen
+        assert_eq!(
+            tt.submit(&mut oracle, &mut oracle_owner, 200, 2, 43)
+                .map_err(Error::from),
+            Err(Error::AggregatorPaused)
+        );
+        let mut clock = sysclock(200);
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::RequestRound,
+                vec![
+                    (&mut clock).into(),
+                    tt.t_aggregator.aggregator.info(),
+                    tt.t_aggregator.round_submissions.info(),
+                    (&mut requester).into(),
+                    (&mut requester_owner).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::AggregatorPaused)
+        );
 
-    fn create_aggregator(program_id: &Pubkey) -> Result<TAggregator, ProgramError> {
-        let mut rent_sysvar = rent_sysvar();
-        let mut aggregator = TAccount::new_rent_exempt(
-            &program_id,
-            borsh_utils::get_packed_len::<Aggregator>(),
-            false,
+        // test: consumers can tell a paused feed apart
+        let aggregator_info = tt.t_aggregator.aggregator.info();
+        assert_eq!(
+            crate::read_median(&program_id, &aggregator_info).map_err(Error::from),
+            Err(Error::AggregatorPaused)
         );
-        let mut aggregator_owner = TAccount::new(&program_id, true);
-        let mut round_submissions = TAccount::new_rent_exempt(
-            &program_id,
-            borsh_utils::get_packed_len::<Submissions>(),
-            false,
+        assert_eq!(
+            crate::read_price(&program_id, &aggregator_info).map_err(Error::from),
+            Err(Error::AggregatorPaused)
         );
-        let mut answer_submissions = TAccount::new_rent_exempt(
-            &program_id,
-            borsh_utils::get_packed_len::<Submissions>(),
+
+        let t_aggregator = &mut tt.t_aggregator;
+        set_paused(
+            &mut t_aggregator.aggregator,
+            &mut t_aggregator.aggregator_owner,
             false,
-        );
+        )?;
 
-        let config = &AggregatorConfig {
-            decimals: 8,
-            description: [0u8; 32],
-            min_submissions: 2,
-            max_submissions: 2,
-            round_timeout: 1,
-            restart_delay: 1,
-            requester_restart_delay: 0,
-            reward_amount: 10,
-            ..AggregatorConfig::default()
+        let (answer, _) = crate::read_price(&tt.program_id, &tt.t_aggregator.aggregator.info())?;
+        assert_eq!(answer.median, 42);
+        tt.submit(&mut oracle, &mut oracle_owner, 200, 2, 43)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_price_checked() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut tt = SubmitTestFixture {
+            program_id,
+            t_aggregator: create_aggregator(&program_id)?,
         };
 
-        process(
-            &program_id,
-            instruction::Instruction::Initialize {
-                config: config.clone(),
-            },
-            vec![
-                (&mut rent_sysvar).into(),
-                (&mut aggregator).into(),
-                (&mut aggregator_owner).into(),
-                (&mut round_submissions).into(),
-                (&mut answer_submissions).into(),
-            ]
-            .as_slice(),
-        )?;
+        let (mut oracle, mut oracle_owner) = tt.create_oracle()?;
+        let (mut oracle2, mut oracle_owner2) = tt.create_oracle()?;
 
-        Ok(TAggregator {
-            config: config.clone(),
-            aggregator,
-            aggregator_owner,
-            round_submissions,
-            answer_submissions,
-        })
-    }
+        let read_price_checked = |tt: &mut SubmitTestFixture, time: u64, max_age: u64| {
+            let clock = Clock {
+                slot: time,
+                ..Clock::default()
+            };
+            crate::read_price_checked(
+                &tt.program_id,
+                &tt.t_aggregator.aggregator.info(),
+                &clock,
+                max_age,
+            )
+            .map_err(Error::from)
+        };
 
-    fn create_oracle(
-        program_id: &Pubkey,
-        aggregator: &mut TAccount,
-        aggregator_owner: &mut TAccount,
-    ) -> Result<(TAccount, TAccount), ProgramError> {
-        let mut rent_sysvar = rent_sysvar();
-        let mut oracle =
-            TAccount::new_rent_exempt(&program_id, borsh_utils::get_packed_len::<Oracle>(), false);
-        let mut oracle_owner = TAccount::new(&program_id, true);
+        assert_eq!(
+            read_price_checked(&mut tt, 100, 100),
+            Err(Error::NoResolvedAnswer)
+        );
 
-        process(
-            &program_id,
-            instruction::Instruction::AddOracle {
-                description: [0xab; 32],
-            },
-            vec![
-                (&mut rent_sysvar).into(),
-                aggregator.into(),
-                aggregator_owner.into(),
-                (&mut oracle).into(),
-                (&mut oracle_owner).into(),
-            ]
-            .as_slice(),
-        )?;
+        tt.submit(&mut oracle, &mut oracle_owner, 100, 0, 10)?;
+        tt.submit(&mut oracle2, &mut oracle_owner2, 200, 0, 20)?;
 
-        Ok((oracle, oracle_owner))
-    }
+        let (answer, decimals) = read_price_checked(&mut tt, 250, 100)?;
+        assert_eq!(answer.median, 15);
+        assert_eq!(answer.submissions, 2);
+        assert_eq!(decimals, 8);
 
-    fn create_requester(
-        program_id: &Pubkey,
-        aggregator: &mut TAccount,
-        aggregator_owner: &mut TAccount,
-    ) -> Result<(TAccount, TAccount), ProgramError> {
-        let mut rent_sysvar = rent_sysvar();
-        let mut requester =
-            TAccount::new_rent_exempt(&program_id, borsh_utils::get_packed_len::<Requester>(), false);
-        let mut requester_owner = TAccount::new(&program_id, true);
+        // test: stale answer
+        assert!(read_price_checked(&mut tt, 300, 100).is_ok());
+        assert_eq!(
+            read_price_checked(&mut tt, 301, 100),
+            Err(Error::StaleAnswer)
+        );
+
+        // test: a new round that has not timed out yet doesn't invalidate the answer
+        tt.submit(&mut oracle, &mut oracle_owner, 300, 1, 30)?;
+        assert!(read_price_checked(&mut tt, 300, 1000).is_ok());
+        assert_eq!(
+            read_price_checked(&mut tt, 301, 1000),
+            Err(Error::CurrentRoundTimedOut)
+        );
 
+        // test: min_submissions raised above the answer's submissions
+        let config = AggregatorConfig {
+            min_submissions: 3,
+            max_submissions: 3,
+            round_timeout: 1000,
+            ..tt.t_aggregator.config.clone()
+        };
         process(
             &program_id,
-            instruction::Instruction::AddRequester {
-                description: [0xab; 32],
-            },
+            instruction::Instruction::Configure { config },
             vec![
-                (&mut rent_sysvar).into(),
-                aggregator.into(),
-                aggregator_owner.into(),
-                (&mut requester).into(),
-                (&mut requester_owner).into(),
+                tt.t_aggregator.aggregator.info(),
+                tt.t_aggregator.aggregator_owner.info(),
             ]
             .as_slice(),
         )?;
+        assert_eq!(
+            read_price_checked(&mut tt, 301, 1000),
+            Err(Error::AnswerBelowMinSubmissions)
+        );
 
-        Ok((requester, requester_owner))
-    }
-
-    #[test]
-    fn test_intialize() -> ProgramResult {
-        let program_id = Pubkey::new_unique();
-        create_aggregator(&program_id)?;
         Ok(())
     }
 
     #[test]
-    fn test_configure() -> ProgramResult {
+    fn test_round_timestamps_and_timeout_in_seconds() -> ProgramResult {
         let program_id = Pubkey::new_unique();
+
         let TAggregator {
             mut aggregator,
             mut aggregator_owner,
-            ..
+            mut oracle_registry,
+            mut round_submissions,
+            mut answer_submissions,
+            config,
         } = create_aggregator(&program_id)?;
+        let (mut oracle, mut oracle_owner) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        let (mut oracle2, mut oracle_owner2) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
 
         process(
             &program_id,
             instruction::Instruction::Configure {
                 config: AggregatorConfig {
-                    reward_amount: 1000,
-                    ..AggregatorConfig::default()
+                    round_timeout: 60,
+                    round_timeout_unit: TimeUnit::Seconds,
+                    ..config
                 },
             },
             vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
         )?;
 
+        let mut submit = |oracle: &mut TAccount,
+                          oracle_owner: &mut TAccount,
+                          slot: u64,
+                          unix_timestamp: i64,
+                          round_id: u64| {
+            let mut clock = sysclock_at(slot, unix_timestamp);
+            process(
+                &program_id,
+                instruction::Instruction::Submit {
+                    round_id,
+                    value: 1,
+                },
+                vec![
+                    (&mut clock).into(),
+                    (&mut aggregator).into(),
+                    (&mut round_submissions).into(),
+                    (&mut answer_submissions).into(),
+                    oracle.into(),
+                    oracle_owner.into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        submit(&mut oracle, &mut oracle_owner, 10, 1000, 1)?;
+        submit(&mut oracle2, &mut oracle_owner2, 20, 1010, 1)?;
+
+        // test: plenty of slots passed, but not 60 seconds
+        assert_eq!(
+            submit(&mut oracle2, &mut oracle_owner2, 1000, 1059, 2),
+            Err(Error::OracleRoundTimeout)
+        );
+        submit(&mut oracle2, &mut oracle_owner2, 30, 1060, 2)?;
+
         let aggregator_state = Aggregator::load_initialized(&aggregator.info())?;
-        assert_eq!(aggregator_state.config.reward_amount, 1000);
+        let round = &aggregator_state.round;
+        assert_eq!(round.id, 2);
+        assert_eq!(round.created_at, 30);
+        assert_eq!(round.created_at_timestamp, 1060);
+        assert_eq!(round.updated_at_timestamp, 1060);
+
+        let answer = &aggregator_state.answer;
+        assert_eq!(answer.round_id, 1);
+        assert_eq!(answer.created_at, 20);
+        assert_eq!(answer.created_at_timestamp, 1010);
+        assert_eq!(answer.updated_at_timestamp, 1010);
+
+        let answer_submissions = aggregator_state.answer_submissions(&answer_submissions.info())?;
+        assert_eq!(answer_submissions.get(0)?.updated_at_timestamp, 1000);
+        assert_eq!(answer_submissions.get(1)?.updated_at_timestamp, 1010);
 
         Ok(())
     }
 
     #[test]
-    fn test_add_and_remove_oracle() -> ProgramResult {
+    fn test_signed_values() -> ProgramResult {
         let program_id = Pubkey::new_unique();
 
-        let TAggregator {
-            mut aggregator,
-            mut aggregator_owner,
-            ..
-        } = create_aggregator(&program_id)?;
-        let (mut oracle, _oracle_owner) =
-            create_oracle(&program_id, &mut aggregator, &mut aggregator_owner)?;
+        let mut rent_sysvar = rent_sysvar();
+        let mut aggregator = TAccount::new_rent_exempt(
+            &program_id,
+            borsh_utils::get_account_len::<Aggregator>(),
+            false,
+        );
+        let mut aggregator_owner = TAccount::new(&program_id, true);
+        let mut round_submissions =
+            TAccount::new_rent_exempt(&program_id, Submissions::account_len(MAX_ORACLES), false);
+        let mut answer_submissions =
+            TAccount::new_rent_exempt(&program_id, Submissions::account_len(MAX_ORACLES), false);
+        let mut oracle_registry =
+            TAccount::new_rent_exempt(&program_id, OracleRegistry::ACCOUNT_LEN, false);
+
+        let config = AggregatorConfig {
+            decimals: 4,
+            min_submissions: 2,
+            max_submissions: 3,
+            value_type: ValueType::I64,
+            ..AggregatorConfig::default()
+        };
 
         process(
             &program_id,
-            instruction::Instruction::RemoveOracle {},
+            instruction::Instruction::Initialize {
+                config: config.clone(),
+            },
             vec![
+                (&mut rent_sysvar).into(),
                 (&mut aggregator).into(),
                 (&mut aggregator_owner).into(),
-                (&mut oracle).into(),
+                (&mut round_submissions).into(),
+                (&mut answer_submissions).into(),
+                (&mut oracle_registry).into(),
             ]
             .as_slice(),
         )?;
 
-        // println!("{}", hex::encode(oracle.account.data));
+        let (mut oracle, mut oracle_owner) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+        let (mut oracle2, mut oracle_owner2) = create_oracle(
+            &program_id,
+            &mut aggregator,
+            &mut aggregator_owner,
+            &mut oracle_registry,
+        )?;
+
+        let mut submit = |oracle: &mut TAccount, oracle_owner: &mut TAccount, value: i128| {
+            let mut clock = sysclock(100);
+            process(
+                &program_id,
+                instruction::Instruction::SubmitSigned { round_id: 1, value },
+                vec![
+                    (&mut clock).into(),
+                    (&mut aggregator).into(),
+                    (&mut round_submissions).into(),
+                    (&mut answer_submissions).into(),
+                    oracle.into(),
+                    oracle_owner.into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        // test: value must fit in i64
+        assert_eq!(
+            submit(&mut oracle, &mut oracle_owner, i64::MIN as i128 - 1),
+            Err(Error::ValueOutOfRange)
+        );
+
+        submit(&mut oracle, &mut oracle_owner, -25)?;
+        submit(&mut oracle2, &mut oracle_owner2, -10)?;
+
+        assert_eq!(
+            crate::read_signed_price(&program_id, &aggregator.info())?,
+            (-18, 4)
+        );
+        assert_eq!(
+            crate::read_price(&program_id, &aggregator.info()).map_err(Error::from),
+            Err(Error::ValueTypeMismatch)
+        );
+        assert_eq!(
+            crate::read_median(&program_id, &aggregator.info()).map_err(Error::from),
+            Err(Error::ValueTypeMismatch)
+        );
+        assert_eq!(
+            crate::read_price_checked(&program_id, &aggregator.info(), &Clock::default(), u64::MAX)
+                .map_err(Error::from),
+            Err(Error::ValueTypeMismatch)
+        );
+
+        // test: value type cannot be reconfigured
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::Configure {
+                    config: AggregatorConfig {
+                        value_type: ValueType::U64,
+                        ..config
+                    },
+                },
+                vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ValueTypeImmutable)
+        );
+
         Ok(())
     }
 
     #[test]
-    fn test_add_and_remove_requester() -> ProgramResult {
+    fn test_unsigned_rejects_negative_values() -> ProgramResult {
         let program_id = Pubkey::new_unique();
 
-        let TAggregator {
-            mut aggregator,
-            mut aggregator_owner,
-            ..
-        } = create_aggregator(&program_id)?;
-        let (mut requester, _requester_owner) =
-            create_requester(&program_id, &mut aggregator, &mut aggregator_owner)?;
+        let mut tt = SubmitTestFixture {
+            program_id,
+            t_aggregator: create_aggregator(&program_id)?,
+        };
+        let (mut oracle, mut oracle_owner) = tt.create_oracle()?;
 
-        process(
-            &program_id,
-            instruction::Instruction::RemoveRequester {},
-            vec![
-                (&mut aggregator).into(),
-                (&mut aggregator_owner).into(),
-                (&mut requester).into(),
-            ]
-            .as_slice(),
-        )?;
+        let mut clock = sysclock(100);
+        assert_eq!(
+            process(
+                &program_id,
+                instruction::Instruction::SubmitSigned {
+                    round_id: 0,
+                    value: -1
+                },
+                vec![
+                    (&mut clock).into(),
+                    tt.t_aggregator.aggregator.info(),
+                    tt.t_aggregator.round_submissions.info(),
+                    tt.t_aggregator.answer_submissions.info(),
+                    (&mut oracle).into(),
+                    (&mut oracle_owner).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from),
+            Err(Error::ValueOutOfRange)
+        );
+
+        let agr = tt.submit(&mut oracle, &mut oracle_owner, 100, 0, u64::MAX)?;
+        assert_eq!(agr.round.updated_at, 100);
+        assert_eq!(tt.round_submission(0)?.value, u64::MAX as i128);
 
-        println!("{}", hex::encode(aggregator.account.data));
         Ok(())
     }
 
-    struct SubmitTestFixture {
-        program_id: Pubkey,
-        t_aggregator: TAggregator,
-    }
+    #[test]
+    fn test_weighted_median_aggregation() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut tt = SubmitTestFixture {
+            program_id,
+            t_aggregator: create_aggregator(&program_id)?,
+        };
+
+        let (mut oracle, mut oracle_owner) = tt.create_oracle()?;
+        let (mut oracle2, mut oracle_owner2) = tt.create_oracle()?;
+        let (mut oracle3, mut oracle_owner3) = tt.create_oracle()?;
+        assert_eq!(Oracle::load_initialized(&oracle.info())?.weight, 1);
 
-    impl SubmitTestFixture {
-        fn submit(
-            &mut self,
-            oracle: &mut TAccount,
-            oracle_owner: &mut TAccount,
-            time: u64,
-            round_id: u64,
-            value: u64,
-        ) -> Result<Aggregator, ProgramError> {
-            let mut clock = sysclock(time as i64);
+        let config = AggregatorConfig {
+            min_submissions: 3,
+            max_submissions: 3,
+            aggregation_method: AggregationMethod::WeightedMedian,
+            ..tt.aggregator()?.config
+        };
+        process(
+            &program_id,
+            instruction::Instruction::Configure { config },
+            vec![
+                tt.t_aggregator.aggregator.info(),
+                tt.t_aggregator.aggregator_owner.info(),
+            ]
+            .as_slice(),
+        )?;
 
+        let set_weight = |tt: &mut SubmitTestFixture,
+                          oracle: &mut TAccount,
+                          owner: Option<&mut TAccount>,
+                          weight| {
+            let aggregator = tt.t_aggregator.aggregator.info();
+            let owner = match owner {
+                Some(owner) => owner.info(),
+                None => tt.t_aggregator.aggregator_owner.info(),
+            };
             process(
-                &self.program_id,
-                instruction::Instruction::Submit { round_id, value },
+                &program_id,
+                instruction::Instruction::SetOracleWeight { weight },
                 vec![
-                    (&mut clock).into(),
-                    self.t_aggregator.aggregator.info(),
-                    self.t_aggregator.round_submissions.info(),
-                    self.t_aggregator.answer_submissions.info(),
+                    aggregator,
+                    owner,
                     oracle.into(),
-                    oracle_owner.into(),
                 ]
                 .as_slice(),
-            )?;
-
-            Aggregator::load_initialized(&self.t_aggregator.aggregator.info())
-        }
+            )
+            .map_err(Error::from)
+        };
 
-        fn aggregator(&mut self) -> Result<Aggregator, ProgramError> {
-            Aggregator::load_initialized(&self.t_aggregator.aggregator.info())
-        }
+        // test: only the aggregator owner can set weights
+        assert_eq!(
+            set_weight(&mut tt, &mut oracle3, Some(&mut oracle_owner3), 5),
+            Err(Error::OwnerMismatch)
+        );
 
-        fn create_oracle(&mut self) -> Result<(TAccount, TAccount), ProgramError> {
-            create_oracle(
-                &self.program_id,
-                &mut self.t_aggregator.aggregator,
-                &mut self.t_aggregator.aggregator_owner,
-            )
-        }
+        // test: a weight of 0 is rejected
+        assert_eq!(
+            set_weight(&mut tt, &mut oracle3, None, 0),
+            Err(Error::ZeroOracleWeight)
+        );
 
-        fn round_submission(&mut self, i: usize) -> Result<Submission, ProgramError> {
-            Ok(self.round_submissions()?.data[i])
-        }
+        set_weight(&mut tt, &mut oracle3, None, 5)?;
+        assert_eq!(Oracle::load_initialized(&oracle3.info())?.weight, 5);
 
-        fn round_submissions(&mut self) -> Result<Submissions, ProgramError> {
-            self.aggregator()?
-                .round_submissions(&self.t_aggregator.round_submissions.info())
-        }
+        tt.submit(&mut oracle, &mut oracle_owner, 100, 0, 10)?;
+        tt.submit(&mut oracle2, &mut oracle_owner2, 100, 0, 20)?;
+        tt.submit(&mut oracle3, &mut oracle_owner3, 100, 0, 30)?;
+        assert_eq!(tt.answer_submission(2)?.weight, 5);
 
-        fn answer_submission(&mut self, i: usize) -> Result<Submission, ProgramError> {
-            Ok(self.answer_submissions()?.data[i])
-        }
+        let (answer, _) = crate::read_price(&tt.program_id, &tt.t_aggregator.aggregator.info())?;
+        assert_eq!(answer.median, 30);
+        assert_eq!(answer.method, AggregationMethod::WeightedMedian);
 
-        fn answer_submissions(&mut self) -> Result<Submissions, ProgramError> {
-            self.aggregator()?
-                .answer_submissions(&self.t_aggregator.answer_submissions.info())
-        }
+        Ok(())
     }
 
     #[test]
-    fn test_submit() -> ProgramResult {
+    fn test_outlier_submissions() -> ProgramResult {
         let program_id = Pubkey::new_unique();
 
         let mut tt = SubmitTestFixture {
@@ -954,166 +5584,262 @@ mod tests {
         let (mut oracle2, mut oracle_owner2) = tt.create_oracle()?;
         let (mut oracle3, mut oracle_owner3) = tt.create_oracle()?;
 
-        let time = 100;
-        let agr = tt.submit(&mut oracle, &mut oracle_owner, time, 0, 1)?;
-        let oracle_state = Oracle::load_initialized(&oracle.info())?;
-        let sub = tt.round_submission(0)?;
-        let round = &agr.round;
-        assert_eq!(oracle_state.withdrawable, 10);
-        assert_eq!(round.created_at, time);
-        assert_eq!(round.updated_at, time);
-        assert_eq!(sub.oracle, oracle.pubkey.to_bytes());
-        assert_eq!(sub.value, 1);
-        assert_eq!(sub.updated_at, 100);
-        assert_eq!(agr.answer.is_initialized(), false);
+        let configure = |tt: &mut SubmitTestFixture, outlier_action| {
+            let config = AggregatorConfig {
+                max_submissions: 3,
+                max_answer_deviation: 1000,
+                max_round_deviation: 500,
+                outlier_action,
+                ..tt.aggregator()?.config
+            };
+            process(
+                &program_id,
+                instruction::Instruction::Configure { config },
+                vec![
+                    tt.t_aggregator.aggregator.info(),
+                    tt.t_aggregator.aggregator_owner.info(),
+                ]
+                .as_slice(),
+            )
+        };
+        configure(&mut tt, OutlierAction::Flag)?;
 
-        // test: should fail with repeated submission
+        // test: nothing to deviate from in the first submission of the first round
+        tt.submit(&mut oracle, &mut oracle_owner, 100, 0, 1000)?;
+        assert_eq!(tt.round_submission(0)?.status, SubmissionStatus::Accepted);
+
+        // test: flagged 10% off the in-round median, yet aggregated & rewarded
+        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, 100, 0, 1100)?;
+        assert_eq!(tt.round_submission(1)?.status, SubmissionStatus::Flagged);
+        assert_eq!(agr.answer.median, 1050);
+        assert_eq!(agr.answer.submissions, 2);
+        assert_eq!(Oracle::load_initialized(&oracle2.info())?.withdrawable, 10);
+
+        configure(&mut tt, OutlierAction::Exclude)?;
+
+        // test: excluded 20% off the previous answer, left out & not rewarded
+        let agr = tt.submit(&mut oracle3, &mut oracle_owner3, 100, 0, 1260)?;
+        assert_eq!(tt.round_submission(2)?.status, SubmissionStatus::Excluded);
+        assert_eq!(agr.answer.median, 1050);
+        assert_eq!(agr.answer.submissions, 2);
+        assert_eq!(Oracle::load_initialized(&oracle3.info())?.withdrawable, 0);
+
+        // test: an excluded submission still takes the oracle's turn
         assert_eq!(
-            tt.submit(&mut oracle, &mut oracle_owner, time + 10, 0, 2)
+            tt.submit(&mut oracle3, &mut oracle_owner3, 100, 0, 1050)
                 .map_err(Error::from),
-            Err(Error::OracleAlreadySubmitted),
-            "should fail if oracle submits repeatedly in the same round"
+            Err(Error::OracleAlreadySubmitted)
         );
 
-        let old_time = time;
-        let time = 200;
-        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time, 0, 2)?;
-        let oracle_state = Oracle::load_initialized(&oracle.info())?;
-        let sub = tt.round_submission(1)?;
-        let round = &agr.round;
-        assert_eq!(oracle_state.withdrawable, 10);
-        assert_eq!(round.created_at, old_time);
-        assert_eq!(round.updated_at, time);
-        assert_eq!(sub.oracle, oracle2.pubkey.to_bytes());
-        assert_eq!(sub.value, 2);
-        assert_eq!(sub.updated_at, time);
+        // test: the round cannot resolve from excluded submissions alone
+        tt.submit(&mut oracle2, &mut oracle_owner2, 200, 1, 2000)?;
+        let agr = tt.submit(&mut oracle3, &mut oracle_owner3, 200, 1, 1060)?;
+        assert_eq!(tt.round_submission(0)?.status, SubmissionStatus::Excluded);
+        assert_eq!(tt.round_submission(1)?.status, SubmissionStatus::Accepted);
+        assert_eq!(agr.answer.round_id, 0);
+
+        let agr = tt.submit(&mut oracle, &mut oracle_owner, 200, 1, 1040)?;
+        assert_eq!(agr.answer.round_id, 1);
+        assert_eq!(agr.answer.median, 1050);
+        assert_eq!(agr.answer.submissions, 2);
+
+        // test: a sustained move away from the previous answer still resolves,
+        // as the submissions the round needs are flagged instead of excluded
+        tt.submit(&mut oracle, &mut oracle_owner, 300, 2, 2000)?;
+        assert_eq!(tt.round_submission(0)?.status, SubmissionStatus::Excluded);
+        tt.submit(&mut oracle2, &mut oracle_owner2, 300, 2, 2010)?;
+        assert_eq!(tt.round_submission(1)?.status, SubmissionStatus::Flagged);
+        let agr = tt.submit(&mut oracle3, &mut oracle_owner3, 300, 2, 2020)?;
+        assert_eq!(tt.round_submission(2)?.status, SubmissionStatus::Flagged);
+        assert_eq!(agr.answer.round_id, 2);
+        assert_eq!(agr.answer.median, 2015);
+        assert_eq!(agr.answer.submissions, 2);
 
-        // // test: answer resolved when min_submissions is reached
-        let answer = &agr.answer;
-        assert_eq!(answer.is_initialized(), true);
-        assert_eq!(answer.updated_at, time);
-        assert_eq!(answer.created_at, time);
+        Ok(())
+    }
 
-        let answer_submissions = tt.answer_submissions()?;
-        let round_submissions = tt.round_submissions()?;
-        assert_eq!(answer_submissions, round_submissions);
+    #[test]
+    fn test_max_dispersion() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
 
-        // test: max submission reached
+        let mut tt = SubmitTestFixture {
+            program_id,
+            t_aggregator: create_aggregator(&program_id)?,
+        };
+
+        let (mut oracle, mut oracle_owner) = tt.create_oracle()?;
+        let (mut oracle2, mut oracle_owner2) = tt.create_oracle()?;
+        let (mut oracle3, mut oracle_owner3) = tt.create_oracle()?;
+
+        let config = AggregatorConfig {
+            max_submissions: 3,
+            max_dispersion: 1000,
+            ..tt.aggregator()?.config
+        };
+        process(
+            &program_id,
+            instruction::Instruction::Configure { config },
+            vec![
+                tt.t_aggregator.aggregator.info(),
+                tt.t_aggregator.aggregator_owner.info(),
+            ]
+            .as_slice(),
+        )?;
+
+        // test: mad 15 is more than 10% of the median 115
+        tt.submit(&mut oracle, &mut oracle_owner, 100, 0, 100)?;
+        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, 100, 0, 130)?;
+        assert!(!agr.answer.is_initialized());
         assert_eq!(
-            tt.submit(&mut oracle3, &mut oracle_owner3, time + 10, 0, 2)
+            crate::read_price_with_confidence(&tt.program_id, &tt.t_aggregator.aggregator.info())
                 .map_err(Error::from),
-            Err(Error::MaxSubmissionsReached),
+            Err(Error::NoResolvedAnswer)
         );
 
-        // test: start new round
-        let time = 300;
-        let agr = tt.submit(&mut oracle, &mut oracle_owner, time, 1, 10)?;
-        let oracle_state = Oracle::load_initialized(&oracle.info())?;
-        let sub = tt.round_submission(0)?;
-        let round = &agr.round;
-        assert_eq!(oracle_state.withdrawable, 20);
-        assert_eq!(round.id, 1);
-        assert_eq!(round.created_at, time);
-        assert_eq!(round.updated_at, time);
-        assert_eq!(sub.oracle, oracle.pubkey.to_bytes());
-        assert_eq!(sub.value, 10);
-        assert_eq!(sub.updated_at, time);
+        // test: mad 10 is within 10% of the median 120
+        tt.submit(&mut oracle3, &mut oracle_owner3, 100, 0, 120)?;
         assert_eq!(
-            tt.round_submission(1)?.is_initialized(),
-            false,
-            "other submissions should've been zero after starting a new round"
+            crate::read_price_with_confidence(&tt.program_id, &tt.t_aggregator.aggregator.info())?,
+            (
+                120,
+                Dispersion {
+                    min: 100,
+                    max: 130,
+                    mad: 10,
+                },
+                8
+            )
         );
 
-        // the last round answer should be reserved
-        let answer = &agr.answer;
-        assert_eq!(answer.is_initialized(), true);
-        assert_eq!(answer.round_id, 0);
-        assert_eq!(answer.updated_at, 200);
-        assert_eq!(answer.created_at, 200);
+        Ok(())
+    }
 
-        // test: oracle cannot immediately start a new round
+    #[test]
+    fn test_submit_max_oracles() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
+
+        let mut tt = SubmitTestFixture {
+            program_id,
+            t_aggregator: create_aggregator(&program_id)?,
+        };
+
+        let config = AggregatorConfig {
+            min_submissions: MAX_ORACLES as u8,
+            max_submissions: MAX_ORACLES as u8,
+            ..tt.aggregator()?.config
+        };
+        process(
+            &program_id,
+            instruction::Instruction::Configure { config },
+            vec![
+                tt.t_aggregator.aggregator.info(),
+                tt.t_aggregator.aggregator_owner.info(),
+            ]
+            .as_slice(),
+        )?;
+
+        let mut oracles = (0..MAX_ORACLES)
+            .map(|_| tt.create_oracle())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // test: the oracle registry is full
         assert_eq!(
-            tt.submit(&mut oracle, &mut oracle_owner, time + 10, 2, 2)
-                .map_err(Error::from),
-            Err(Error::OracleNewRoundCooldown),
+            tt.create_oracle().map(|_| ()).map_err(Error::from),
+            Err(Error::MaxOraclesReached)
         );
 
-        // test: resolve a new round
-        let time = 400;
-        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time, 1, 20)?;
-        let sub = tt.round_submission(1)?;
+        for (i, (oracle, oracle_owner)) in oracles.iter_mut().enumerate() {
+            tt.submit(oracle, oracle_owner, 100, 0, i as u64 + 1)?;
+        }
 
-        assert_eq!(sub.oracle, oracle2.pubkey.to_bytes());
-        assert_eq!(sub.value, 20);
-        assert_eq!(sub.updated_at, time);
+        let agr = tt.aggregator()?;
+        assert_eq!(agr.answer.submissions as usize, MAX_ORACLES);
+        assert_eq!(agr.answer.median, (MAX_ORACLES as u64).div_ceil(2));
+        assert_eq!(tt.answer_submissions()?, tt.round_submissions()?);
 
-        let answer = &agr.answer;
-        assert_eq!(answer.is_initialized(), true);
-        assert_eq!(answer.round_id, 1);
-        assert_eq!(answer.updated_at, time);
-        assert_eq!(answer.created_at, time);
-        assert_eq!(answer.median, 15);
+        Ok(())
+    }
 
-        assert_eq!(tt.answer_submission(0)?.value, 10);
-        assert_eq!(tt.answer_submission(1)?.value, 20);
+    #[test]
+    fn test_submissions_capacity() -> ProgramResult {
+        let program_id = Pubkey::new_unique();
 
-        let time = 500;
-        // let oracle 2 start a new round
-        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time, 2, 200)?;
-        let round = &agr.round;
-        assert_eq!(round.id, 2);
+        let initialize = |round_capacity, answer_capacity, max_submissions| {
+            let mut rent_sysvar = rent_sysvar();
+            let mut aggregator = TAccount::new_rent_exempt(
+                &program_id,
+                borsh_utils::get_account_len::<Aggregator>(),
+                false,
+            );
+            let mut aggregator_owner = TAccount::new(&program_id, true);
+            let mut round_submissions = TAccount::new_rent_exempt(
+                &program_id,
+                Submissions::account_len(round_capacity),
+                false,
+            );
+            let mut answer_submissions = TAccount::new_rent_exempt(
+                &program_id,
+                Submissions::account_len(answer_capacity),
+                false,
+            );
+            let mut oracle_registry =
+                TAccount::new_rent_exempt(&program_id, OracleRegistry::ACCOUNT_LEN, false);
 
-        let agr = tt.submit(&mut oracle, &mut oracle_owner, time + 2, 3, 200)?;
-        let round = &agr.round;
-        assert_eq!(round.id, 3);
+            process(
+                &program_id,
+                instruction::Instruction::Initialize {
+                    config: AggregatorConfig {
+                        min_submissions: 1,
+                        max_submissions,
+                        ..AggregatorConfig::default()
+                    },
+                },
+                vec![
+                    (&mut rent_sysvar).into(),
+                    (&mut aggregator).into(),
+                    (&mut aggregator_owner).into(),
+                    (&mut round_submissions).into(),
+                    (&mut answer_submissions).into(),
+                    (&mut oracle_registry).into(),
+                ]
+                .as_slice(),
+            )
+            .map_err(Error::from)?;
 
-        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time + 4, 4, 200)?;
-        let round = &agr.round;
-        assert_eq!(round.id, 4);
+            Ok((aggregator, aggregator_owner))
+        };
 
-        // InvalidRoundID
         assert_eq!(
-            tt.submit(&mut oracle, &mut oracle_owner, time + 6, 10, 1000)
-                .map_err(Error::from),
-            Err(Error::InvalidRoundID),
-            "should only be able to start a round with current_round.id + 1"
+            initialize(2, 2, 3).map(|_| ()),
+            Err(Error::MaxSubmissionsAboveCapacity)
         );
-
         assert_eq!(
-            tt.submit(&mut oracle3, &mut oracle_owner3, time + 8, 3, 1000)
-                .map_err(Error::from),
-            Err(Error::InvalidRoundID),
-            "should not be able to submit answer to previous rounds"
+            initialize(2, 3, 2).map(|_| ()),
+            Err(Error::SubmissionsCapacityMismatch)
         );
 
-        // test: round timeout (for round_timeout = 1 and restart_delay = 1)
-        assert_eq!(tt.t_aggregator.config.round_timeout, 1);
-        assert_eq!(tt.t_aggregator.config.restart_delay, 1);
-        let time = 600;
-        let round_id = 5;
-        let agr = tt.submit(&mut oracle, &mut oracle_owner, time, round_id, 200)?;
-        let round = &agr.round;
-        assert_eq!(round.id, round_id);
-        assert_eq!(round.created_at, time);
-        assert_eq!(
-            tt.submit(&mut oracle, &mut oracle_owner, time + 1, round_id + 1, 200)
-                .map_err(Error::from),
-            Err(Error::OracleNewRoundCooldown),
-            "should wait for one round (restart delay)"
-        );
-        let agr = tt.submit(&mut oracle2, &mut oracle_owner2, time + 1, round_id + 1, 200)?;
-        let round = &agr.round;
-        assert_eq!(round.id, round_id + 1);
-        assert_eq!(
-            tt.submit(&mut oracle, &mut oracle_owner, time + 1, round_id + 2, 200)
-                .map_err(Error::from),
-            Err(Error::OracleRoundTimeout),
-            "should wait for round timeout (time + 2)"
-        );
-        let agr = tt.submit(&mut oracle, &mut oracle_owner, time + 2, round_id + 2, 200)?;
-        let round = &agr.round;
-        assert_eq!(round.id, round_id + 2); 
-        assert_eq!(round.created_at, time + 2); 
+        let (mut aggregator, mut aggregator_owner) = initialize(3, 3, 2)?;
+        let mut configure = |max_submissions| {
+            let config = AggregatorConfig {
+                min_submissions: 1,
+                max_submissions,
+                ..AggregatorConfig::default()
+            };
+            process(
+                &program_id,
+                instruction::Instruction::Configure { config },
+                vec![(&mut aggregator).into(), (&mut aggregator_owner).into()].as_slice(),
+            )
+            .map_err(Error::from)
+        };
+
+        // test: max_submissions can be raised up to the capacity
+        configure(3)?;
+        assert_eq!(configure(4), Err(Error::MaxSubmissionsAboveCapacity));
+
+        let aggregator = Aggregator::load_initialized(&aggregator.info())?;
+        assert_eq!(aggregator.submissions_capacity, 3);
+        assert_eq!(aggregator.config.max_submissions, 3);
 
         Ok(())
     }
@@ -3,16 +3,19 @@ use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 
 use crate::instruction::MAX_ORACLES;
 use crate::{
-    borsh_state::{BorshState, InitBorshState},
+    borsh_state::{AccountType, BorshState, InitBorshState, HEADER_LEN, STATE_VERSION},
+    borsh_utils,
     error::Error,
+    FEED_SEED,
 };
 
 use solana_program::{
-    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
-    program_pack::IsInitialized,
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult,
+    program_error::ProgramError, program_pack::IsInitialized, pubkey::Pubkey, sysvar::rent::Rent,
 };
+use std::{cell::RefCell, rc::Rc};
 
-#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
 pub struct PublicKey(pub [u8; 32]);
 
 impl PublicKey {
@@ -30,7 +33,19 @@ impl<'a> From<&'a AccountInfo<'a>> for PublicKey {
 pub trait Authority {
     fn authority(&self) -> &PublicKey;
 
-    fn authorize(&self, account: &AccountInfo) -> ProgramResult {
+    /// keys acting as the authority instead of `authority`, if set
+    fn multisig(&self) -> Option<&Multisig> {
+        None
+    }
+
+    /// check that `account` is the authority & signed. Under a multisig, enough
+    /// of its keys must have signed instead, among `signers`, which are all the
+    /// accounts of the instruction.
+    fn authorize(&self, account: &AccountInfo, signers: &[AccountInfo]) -> ProgramResult {
+        if let Some(multisig) = self.multisig() {
+            return multisig.authorize(signers);
+        }
+
         if !account.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
@@ -43,6 +58,69 @@ pub trait Authority {
     }
 }
 
+/// Max number of keys of a `Multisig`
+pub const MAX_SIGNERS: usize = 11;
+
+/// M-of-N keys acting as an authority
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub struct Multisig {
+    /// number of keys that must sign. 0 if the multisig is unset
+    pub threshold: u8,
+    /// number of keys
+    pub len: u8,
+    pub keys: [PublicKey; MAX_SIGNERS],
+}
+
+impl Multisig {
+    /// a `threshold` of `keys` multisig. No keys & a 0 threshold unset it.
+    pub fn new(keys: &[PublicKey], threshold: u8) -> Result<Self, ProgramError> {
+        let mut multisig = Multisig::default();
+        if keys.is_empty() && threshold == 0 {
+            return Ok(multisig);
+        }
+
+        if threshold == 0 || threshold as usize > keys.len() || keys.len() > MAX_SIGNERS {
+            return Err(Error::InvalidMultisig)?;
+        }
+
+        for (i, key) in keys.iter().enumerate() {
+            if keys[..i].contains(key) {
+                return Err(Error::DuplicateSigner)?;
+            }
+            multisig.keys[i] = *key;
+        }
+        multisig.len = keys.len() as u8;
+        multisig.threshold = threshold;
+
+        Ok(multisig)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.threshold > 0
+    }
+
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys[..self.len as usize]
+    }
+
+    /// check that at least `threshold` distinct keys signed among `signers`
+    pub fn authorize(&self, signers: &[AccountInfo]) -> ProgramResult {
+        let mut signed = [false; MAX_SIGNERS];
+        for signer in signers.iter().filter(|signer| signer.is_signer) {
+            if let Some(i) = self.keys().iter().position(|key| key.is_account(signer)) {
+                signed[i] = true;
+            }
+        }
+
+        let count = signed.iter().filter(|signed| **signed).count();
+        if count < self.threshold as usize {
+            return Err(Error::NotEnoughSigners)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
 pub struct AggregatorConfig {
     /// description
@@ -51,7 +129,8 @@ pub struct AggregatorConfig {
     /// decimals for this feed
     pub decimals: u8,
 
-    /// number of slots (e.g. 10 is equivalent to ~5 seconds)
+    /// number of slots (e.g. 10 is equivalent to ~5 seconds), or seconds
+    /// depending on `round_timeout_unit`
     pub round_timeout: u32,
 
     /// oracle cannot start a new round until after `restart_relay` rounds
@@ -71,73 +150,621 @@ pub struct AggregatorConfig {
 
     /// SPL token account from which to withdraw rewards
     pub reward_token_account: PublicKey,
+
+    /// unit of `round_timeout`
+    pub round_timeout_unit: TimeUnit,
+
+    /// type of the submitted values, fixed at initialization
+    pub value_type: ValueType,
+
+    /// how the submissions of a round are aggregated into an answer
+    pub aggregation_method: AggregationMethod,
+
+    /// number of lowest and highest values dropped by `AggregationMethod::TrimmedMean`
+    pub trim: u8,
+
+    /// max deviation of a submission from the latest answer, in basis points.
+    /// 0 disables the check.
+    pub max_answer_deviation: u16,
+
+    /// max deviation of a submission from the median of the values accepted so
+    /// far in the round, in basis points. 0 disables the check.
+    pub max_round_deviation: u16,
+
+    /// what happens to a submission out of the deviation bounds
+    pub outlier_action: OutlierAction,
+
+    /// max median absolute deviation of the submissions, in basis points of
+    /// the answer. A round isn't resolved while its submissions are more
+    /// dispersed. 0 disables the check.
+    pub max_dispersion: u16,
+
+    /// max number of oracles of the aggregator, up to `MAX_ORACLES`. 0 only
+    /// caps it at `MAX_ORACLES`. Lowering it below the current number of
+    /// oracles blocks new oracles until enough are removed.
+    pub max_oracles: u8,
+
+    /// number of slots a `Configure`, `AddOracle` or `RemoveOracle` is queued
+    /// for before it can be executed with `ExecutePending`. 0 applies them
+    /// right away.
+    pub timelock_delay: u64,
+}
+
+impl AggregatorConfig {
+    /// check the config invariants. `current` is the config being replaced, if any.
+    pub fn validate(&self, current: Option<&AggregatorConfig>) -> ProgramResult {
+        if self.min_submissions == 0 {
+            return Err(Error::ZeroMinSubmissions)?;
+        }
+
+        if self.min_submissions > self.max_submissions {
+            return Err(Error::MinSubmissionsAboveMax)?;
+        }
+
+        if self.max_submissions as usize > MAX_ORACLES {
+            return Err(Error::MaxSubmissionsAboveMaxOracles)?;
+        }
+
+        if self.max_oracles as usize > MAX_ORACLES {
+            return Err(Error::MaxOraclesAboveMaxOracles)?;
+        }
+
+        if let Some(current) = current {
+            if self.value_type != current.value_type {
+                return Err(Error::ValueTypeImmutable)?;
+            }
+
+            if self.reward_token_account != current.reward_token_account {
+                return Err(Error::RewardTokenAccountImmutable)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// whether submissions aggregated into `value` are too dispersed to resolve a round
+    pub fn exceeds_max_dispersion(&self, value: i128, dispersion: &Dispersion) -> bool {
+        self.max_dispersion > 0 && exceeds_bps(dispersion.mad, value, self.max_dispersion)
+    }
+
+    /// max number of oracles, accounting for `MAX_ORACLES`
+    pub fn oracles_cap(&self) -> usize {
+        match self.max_oracles {
+            0 => MAX_ORACLES,
+            max => (max as usize).min(MAX_ORACLES),
+        }
+    }
+
+    /// aggregation strategy selected by the config
+    pub fn aggregation(&self) -> Box<dyn Aggregate> {
+        match self.aggregation_method {
+            AggregationMethod::Median => Box::new(Median),
+            AggregationMethod::TrimmedMean => Box::new(TrimmedMean { trim: self.trim }),
+            AggregationMethod::WeightedMedian => Box::new(WeightedMedian),
+            AggregationMethod::Mode => Box::new(Mode),
+        }
+    }
 }
 
+/// Partial update of an `AggregatorConfig`, changing only the fields that are
+/// set. `reward_token_account` and `value_type` are fixed at initialization.
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
-pub struct Submissions {
-    pub is_initialized: bool,
-    // can we try using vector to avoid using the stack?
-    pub data: [Submission; MAX_ORACLES],
-    // pub data: Vec<Submission>,
+pub struct AggregatorConfigUpdate {
+    pub description: Option<[u8; 32]>,
+    pub decimals: Option<u8>,
+    pub round_timeout: Option<u32>,
+    pub restart_delay: Option<u8>,
+    pub requester_restart_delay: Option<u8>,
+    pub max_submissions: Option<u8>,
+    pub min_submissions: Option<u8>,
+    pub reward_amount: Option<u64>,
+    pub round_timeout_unit: Option<TimeUnit>,
+    pub aggregation_method: Option<AggregationMethod>,
+    pub trim: Option<u8>,
+    pub max_answer_deviation: Option<u16>,
+    pub max_round_deviation: Option<u16>,
+    pub outlier_action: Option<OutlierAction>,
+    pub max_dispersion: Option<u16>,
+    pub max_oracles: Option<u8>,
+    pub timelock_delay: Option<u64>,
 }
 
-pub struct ResolvedMedian {
-    pub value: u64,
-    pub updated_at: u64,
-    pub created_at: u64,
+impl AggregatorConfigUpdate {
+    /// `config` with the fields of the update applied
+    pub fn apply(&self, config: &AggregatorConfig) -> AggregatorConfig {
+        AggregatorConfig {
+            description: self.description.unwrap_or(config.description),
+            decimals: self.decimals.unwrap_or(config.decimals),
+            round_timeout: self.round_timeout.unwrap_or(config.round_timeout),
+            restart_delay: self.restart_delay.unwrap_or(config.restart_delay),
+            requester_restart_delay: self.requester_restart_delay.unwrap_or(config.requester_restart_delay),
+            max_submissions: self.max_submissions.unwrap_or(config.max_submissions),
+            min_submissions: self.min_submissions.unwrap_or(config.min_submissions),
+            reward_amount: self.reward_amount.unwrap_or(config.reward_amount),
+            round_timeout_unit: self.round_timeout_unit.unwrap_or(config.round_timeout_unit),
+            aggregation_method: self.aggregation_method.unwrap_or(config.aggregation_method),
+            trim: self.trim.unwrap_or(config.trim),
+            max_answer_deviation: self.max_answer_deviation.unwrap_or(config.max_answer_deviation),
+            max_round_deviation: self.max_round_deviation.unwrap_or(config.max_round_deviation),
+            outlier_action: self.outlier_action.unwrap_or(config.outlier_action),
+            max_dispersion: self.max_dispersion.unwrap_or(config.max_dispersion),
+            max_oracles: self.max_oracles.unwrap_or(config.max_oracles),
+            timelock_delay: self.timelock_delay.unwrap_or(config.timelock_delay),
+            ..config.clone()
+        }
+    }
+}
+
+/// Type of the values an aggregator accepts
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub enum ValueType {
+    #[default]
+    U64,
+    I64,
+    I128,
+}
+
+impl ValueType {
+    /// check that a submitted value is representable by this type
+    pub fn check(&self, value: i128) -> ProgramResult {
+        let in_range = match self {
+            ValueType::U64 => value >= 0 && value <= u64::MAX as i128,
+            ValueType::I64 => value >= i64::MIN as i128 && value <= i64::MAX as i128,
+            ValueType::I128 => true,
+        };
+
+        if !in_range {
+            return Err(Error::ValueOutOfRange)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_signed(&self) -> bool {
+        *self != ValueType::U64
+    }
+}
+
+/// Unit of a duration in the aggregator config
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub enum TimeUnit {
+    #[default]
+    Slots,
+    Seconds,
+}
+
+/// Handling of a submission out of the configured deviation bounds
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub enum OutlierAction {
+    /// the submission is aggregated, and marked as `SubmissionStatus::Flagged`
+    #[default]
+    Flag,
+    /// the submission is left out of the answer, and its oracle is not rewarded
+    Exclude,
+}
+
+/// |a - b|, which always fits in u128
+fn abs_diff(a: i128, b: i128) -> u128 {
+    if a > b {
+        (a as u128).wrapping_sub(b as u128)
+    } else {
+        (b as u128).wrapping_sub(a as u128)
+    }
+}
+
+/// Whether `diff` is more than `max_bps` basis points of `reference`. Any
+/// difference from a zero reference exceeds the bound.
+pub fn exceeds_bps(diff: u128, reference: i128, max_bps: u16) -> bool {
+    let reference = abs_diff(reference, 0);
+
+    // floor(reference * max_bps / 10000), split up to prevent overflow. a bound
+    // beyond u128 is never exceeded.
+    let bps = max_bps as u128;
+    (reference / 10_000)
+        .checked_mul(bps)
+        .and_then(|max_diff| max_diff.checked_add(reference % 10_000 * bps / 10_000))
+        .is_some_and(|max_diff| diff > max_diff)
+}
+
+/// Whether `value` deviates from `reference` by more than `max_bps` basis points
+pub fn exceeds_deviation(value: i128, reference: i128, max_bps: u16) -> bool {
+    exceeds_bps(abs_diff(value, reference), reference, max_bps)
 }
 
-impl Submissions {
-    pub fn median(&self) -> Result<u64, ProgramError> {
-        let mut values: Vec<_> = self.data
+/// Strategy aggregating the submissions of a round into an answer
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub enum AggregationMethod {
+    /// see `Median`
+    #[default]
+    Median,
+    /// see `TrimmedMean`
+    TrimmedMean,
+    /// see `WeightedMedian`
+    WeightedMedian,
+    /// see `Mode`
+    Mode,
+}
+
+/// Aggregates submitted values into a single answer
+pub trait Aggregate {
+    /// `submissions` are the initialized submissions of a round, at least one
+    fn aggregate(&self, submissions: &[Submission]) -> Result<i128, ProgramError>;
+}
+
+/// floor of the average of two values, halving each first to prevent overflow.
+/// arithmetic shift rounds negative numbers down as well.
+fn midpoint(a: i128, b: i128) -> i128 {
+    (a >> 1) + (b >> 1) + (a & b & 1)
+}
+
+fn sorted_values(submissions: &[Submission]) -> Vec<i128> {
+    let mut values: Vec<_> = submissions.iter().map(|s| s.value).collect();
+    values.sort_unstable();
+    values
+}
+
+/// Middle value, or the floor of the average of the two middle values
+pub struct Median;
+
+impl Aggregate for Median {
+    fn aggregate(&self, submissions: &[Submission]) -> Result<i128, ProgramError> {
+        let values = sorted_values(submissions);
+        if values.is_empty() {
+            return Err(Error::NoSubmission)?;
+        }
+
+        let i = values.len() / 2;
+        if values.len().is_multiple_of(2) {
+            Ok(midpoint(values[i - 1], values[i]))
+        } else {
+            Ok(values[i])
+        }
+    }
+}
+
+/// Floor of the mean, after dropping the `trim` lowest and `trim` highest
+/// values. At least one value is kept, i.e. `trim` is capped at `(n - 1) / 2`.
+pub struct TrimmedMean {
+    pub trim: u8,
+}
+
+impl Aggregate for TrimmedMean {
+    fn aggregate(&self, submissions: &[Submission]) -> Result<i128, ProgramError> {
+        let values = sorted_values(submissions);
+        if values.is_empty() {
+            return Err(Error::NoSubmission)?;
+        }
+
+        let trim = (self.trim as usize).min((values.len() - 1) / 2);
+        let kept = &values[trim..values.len() - trim];
+
+        // sum the quotients and remainders of `value / n` separately, so that
+        // the running total never leaves the range of the mean.
+        let n = kept.len() as i128;
+        let (mut quotient, mut remainder) = (0i128, 0i128);
+        for value in kept {
+            remainder += value.rem_euclid(n);
+            let carry = remainder / n;
+            remainder %= n;
+            quotient += value.div_euclid(n) + carry;
+        }
+
+        Ok(quotient)
+    }
+}
+
+/// Value at which the cumulative oracle weight reaches half of the total
+/// weight. The two values around an exact half are averaged, so that equal
+/// weights give the same answer as `Median`. Zero weight submissions are ignored.
+pub struct WeightedMedian;
+
+impl Aggregate for WeightedMedian {
+    fn aggregate(&self, submissions: &[Submission]) -> Result<i128, ProgramError> {
+        let mut weighted: Vec<_> = submissions
             .iter()
-            .filter(|s| s.is_initialized())
-            .map(|s| s.value)
+            .filter(|s| s.weight > 0)
+            .map(|s| (s.value, s.weight as u64))
             .collect();
+        if weighted.is_empty() {
+            return Err(Error::NoSubmission)?;
+        }
+        weighted.sort_unstable();
+
+        let total: u64 = weighted.iter().map(|(_, weight)| weight).sum();
+        let mut cumulative = 0;
+        for (i, (value, weight)) in weighted.iter().enumerate() {
+            cumulative += weight;
+            if cumulative * 2 == total {
+                return Ok(midpoint(*value, weighted[i + 1].0));
+            }
+            if cumulative * 2 > total {
+                return Ok(*value);
+            }
+        }
+
+        unreachable!("cumulative weight reaches the total")
+    }
+}
+
+/// Most frequently submitted value, for feeds of discrete values. Ties are
+/// broken by the lowest value.
+pub struct Mode;
+
+impl Aggregate for Mode {
+    fn aggregate(&self, submissions: &[Submission]) -> Result<i128, ProgramError> {
+        let values = sorted_values(submissions);
 
         if values.is_empty() {
             return Err(Error::NoSubmission)?;
         }
 
-        // get median value
-        values.sort();
-
-        let median: u64;
-        let l = values.len();
-        let i = l / 2;
-        if l % 2 == 0 {
-            // take u64 average of two numbers in u128 then cast back, to prevent overflow
-            median = (((values[i] as u128) + (values[i - 1] as u128)) / 2) as u64;
-            // median = values[i].checked_add(values[i - 1]).ok_or(Error::Overflow) / 2;
-        } else {
-            median = values[i];
+        // longest run of equal values, the first (lowest) one wins ties
+        let (mut mode, mut mode_count) = (values[0], 0);
+        let mut start = 0;
+        for i in 1..=values.len() {
+            if i == values.len() || values[i] != values[start] {
+                if i - start > mode_count {
+                    mode = values[start];
+                    mode_count = i - start;
+                }
+                start = i;
+            }
         }
 
-        Ok(median)
+        Ok(mode)
     }
 }
 
-impl IsInitialized for Submissions {
-    fn is_initialized(&self) -> bool {
-        self.is_initialized
+/// Submissions of a round, read & written in place in the account data.
+///
+/// The account holds the header, `is_initialized`, then up to `MAX_ORACLES`
+/// fixed-size `Submission` slots. The number of slots is derived from the
+/// account length, which is chosen when the account gets allocated.
+/// Deserializing the whole array onto the stack is what used to cap
+/// `MAX_ORACLES` at 13; instead one slot is decoded at a time, and lists of
+/// submissions live on the heap.
+pub struct Submissions<'a> {
+    data: Rc<RefCell<&'a mut [u8]>>,
+}
+
+pub struct ResolvedMedian {
+    pub value: u64,
+    pub updated_at: u64,
+    pub created_at: u64,
+}
+
+impl<'a> Submissions<'a> {
+    /// offset of the first slot, after the header and `is_initialized`
+    const SLOTS_OFFSET: usize = HEADER_LEN + 1;
+
+    /// size of a submissions account holding `capacity` submissions
+    pub fn account_len(capacity: usize) -> usize {
+        Self::SLOTS_OFFSET + capacity * SUBMISSION_LEN
+    }
+
+    fn header() -> [u8; HEADER_LEN] {
+        [AccountType::Submissions as u8, STATE_VERSION]
+    }
+
+    fn new(account: &AccountInfo<'a>) -> Result<Self, ProgramError> {
+        let len = (*account.data).borrow().len();
+        let slots_len = len
+            .checked_sub(Self::SLOTS_OFFSET)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        if slots_len % SUBMISSION_LEN != 0 || slots_len / SUBMISSION_LEN > MAX_ORACLES {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Submissions {
+            data: account.data.clone(),
+        })
+    }
+
+    /// initialize a freshly allocated, rent exempt account
+    pub fn init(account: &AccountInfo<'a>, rent: &Rent) -> Result<Self, ProgramError> {
+        let submissions = Self::new(account)?;
+
+        if !rent.is_exempt(account.lamports(), Self::account_len(submissions.capacity())) {
+            // FIXME: return a custom error
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        {
+            let mut data = submissions.data.borrow_mut();
+            let is_blank = data.iter().all(|b| *b == AccountType::Uninitialized as u8);
+            if !is_blank {
+                if data[..HEADER_LEN] != Self::header() {
+                    return Err(Error::AccountTypeMismatch)?;
+                }
+                if data[HEADER_LEN] != 0 {
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+            }
+
+            data[..HEADER_LEN].copy_from_slice(&Self::header());
+            data[HEADER_LEN] = 1;
+        }
+        submissions.clear();
+
+        Ok(submissions)
+    }
+
+    pub fn load_initialized(account: &AccountInfo<'a>) -> Result<Self, ProgramError> {
+        let submissions = Self::new(account)?;
+
+        {
+            let data = submissions.data.borrow();
+            if data[..HEADER_LEN] != Self::header() {
+                return Err(Error::AccountTypeMismatch)?;
+            }
+            if data[HEADER_LEN] == 0 {
+                return Err(ProgramError::UninitializedAccount);
+            }
+        }
+
+        Ok(submissions)
+    }
+
+    /// number of submission slots
+    pub fn capacity(&self) -> usize {
+        (self.data.borrow().len() - Self::SLOTS_OFFSET) / SUBMISSION_LEN
+    }
+
+    fn slot(i: usize) -> std::ops::Range<usize> {
+        let start = Self::SLOTS_OFFSET + i * SUBMISSION_LEN;
+        start..start + SUBMISSION_LEN
+    }
+
+    /// read the submission in slot `i`
+    pub fn get(&self, i: usize) -> Result<Submission, ProgramError> {
+        let data = self.data.borrow();
+        let slot = data.get(Self::slot(i)).ok_or(ProgramError::InvalidArgument)?;
+
+        Submission::try_from_slice(slot).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// write the submission in slot `i`
+    pub fn set(&self, i: usize, submission: &Submission) -> ProgramResult {
+        let mut data = self.data.borrow_mut();
+        let mut slot = data.get_mut(Self::slot(i)).ok_or(ProgramError::InvalidArgument)?;
+
+        submission
+            .serialize(&mut slot)
+            .map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// zero all the slots
+    pub fn clear(&self) {
+        for b in self.data.borrow_mut()[Self::SLOTS_OFFSET..].iter_mut() {
+            *b = 0;
+        }
+    }
+
+    /// overwrite all the slots with the ones of `other`, of the same capacity
+    pub fn copy_from(&self, other: &Submissions) -> ProgramResult {
+        if other.capacity() != self.capacity() {
+            return Err(Error::SubmissionsCapacityMismatch)?;
+        }
+
+        // copied through the heap, in case both are the same account
+        let slots = other.data.borrow()[Self::SLOTS_OFFSET..].to_vec();
+        self.data.borrow_mut()[Self::SLOTS_OFFSET..].copy_from_slice(&slots);
+
+        Ok(())
+    }
+
+    /// initialized submissions, in slot order. Slots are filled in order, so
+    /// the first uninitialized slot ends the list.
+    pub fn submissions(&self) -> Result<Vec<Submission>, ProgramError> {
+        let mut submissions = Vec::new();
+        for i in 0..self.capacity() {
+            let submission = self.get(i)?;
+            if !submission.is_initialized() {
+                break;
+            }
+            submissions.push(submission);
+        }
+
+        Ok(submissions)
     }
 }
-impl BorshState for Submissions {}
-impl InitBorshState for Submissions {}
+
+/// initialized submissions that are not excluded, i.e. which get aggregated
+pub fn accepted(submissions: &[Submission]) -> Vec<Submission> {
+    submissions
+        .iter()
+        .filter(|s| s.is_initialized() && s.status != SubmissionStatus::Excluded)
+        .copied()
+        .collect()
+}
 
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
 pub struct Round {
     pub id: u64,
+    /// slot
     pub created_at: u64,
+    /// slot
     pub updated_at: u64,
+    /// unix timestamp
+    pub created_at_timestamp: i64,
+    /// unix timestamp
+    pub updated_at_timestamp: i64,
 }
 
-#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+impl Round {
+    /// a new round started at the given clock
+    pub fn new(id: u64, clock: &Clock) -> Self {
+        Round {
+            id,
+            created_at: clock.slot,
+            updated_at: 0,
+            created_at_timestamp: clock.unix_timestamp,
+            updated_at_timestamp: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
 pub struct Answer {
     pub round_id: u64,
+    /// median (or whichever aggregate `method` computes) of a `ValueType::U64`
+    /// aggregator, 0 for a signed aggregator
     pub median: u64,
+    /// median (or aggregate) of an aggregator of any `ValueType`
+    pub signed_median: i128,
+    /// slot
     pub created_at: u64,
+    /// slot
     pub updated_at: u64,
+    /// unix timestamp
+    pub created_at_timestamp: i64,
+    /// unix timestamp
+    pub updated_at_timestamp: i64,
+    /// number of submissions the median is taken from
+    pub submissions: u8,
+    /// aggregation method the median is computed with
+    pub method: AggregationMethod,
+    /// spread of the submissions the median is computed from
+    pub dispersion: Dispersion,
+}
+
+/// Spread of the values an answer is aggregated from
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub struct Dispersion {
+    /// lowest value
+    pub min: i128,
+    /// highest value
+    pub max: i128,
+    /// median absolute deviation of the values from their median
+    pub mad: u128,
+}
+
+impl Dispersion {
+    /// spread of the submitted values
+    pub fn of(submissions: &[Submission]) -> Result<Self, ProgramError> {
+        let median = Median.aggregate(submissions)?;
+
+        let mut min = i128::MAX;
+        let mut max = i128::MIN;
+        let mut deviations = Vec::with_capacity(submissions.len());
+        for submission in submissions {
+            min = min.min(submission.value);
+            max = max.max(submission.value);
+            deviations.push(abs_diff(submission.value, median));
+        }
+
+        // floor of the median deviation, like `Median`
+        deviations.sort_unstable();
+        let i = deviations.len() / 2;
+        let mad = if deviations.len() % 2 == 0 {
+            let (a, b) = (deviations[i - 1], deviations[i]);
+            (a >> 1) + (b >> 1) + (a & b & 1)
+        } else {
+            deviations[i]
+        };
+
+        Ok(Dispersion { min, max, mad })
+    }
 }
 
 impl IsInitialized for Answer {
@@ -160,75 +787,666 @@ pub struct Aggregator {
     /// the latest answer resolved
     pub answer: Answer,
     pub answer_submissions: PublicKey, // has_one: Submissions
+    /// bump seed of the program derived reward faucet owner
+    pub faucet_bump_seed: u8,
+    /// optional history of resolved answers, unset if all zeroes
+    pub answer_history: PublicKey, // has_one: AnswerHistory
+    /// number of slots of the round & answer submissions, which bounds `max_submissions`
+    pub submissions_capacity: u8,
+    /// owner proposed by the current owner, pending its acceptance. unset if
+    /// all zeroes
+    pub pending_owner: PublicKey,
+    /// keys of the oracles of the aggregator
+    pub oracle_registry: PublicKey, // has_one: OracleRegistry
+    /// whether the feed is frozen by the owner: no submissions or new rounds
+    /// are accepted, and the answer is not served to consumers
+    pub paused: bool,
+    /// M-of-N keys administering the aggregator in place of `owner`, if set
+    pub admin: Multisig,
+    /// namespaced name of the feed, like `btc:usd`, zero padded. The
+    /// aggregator address is derived from it, see `find_feed_address`. unset
+    /// if all zeroes
+    pub feed_name: [u8; 32],
+    /// bump seed of the aggregator address derived from `feed_name`
+    pub feed_bump_seed: u8,
+    /// owner that created the feed, whose key scopes `feed_name`
+    pub feed_authority: PublicKey,
+    /// number of requesters of the aggregator
+    pub requesters: u32,
+    /// number of changes queued on the aggregator, see `PendingChange`
+    pub pending_changes: u32,
 }
 
 impl Aggregator {
     /// check & return the submissions linked with an aggregator
-    pub fn answer_submissions(&self, account: &AccountInfo) -> Result<Submissions, ProgramError> {
+    pub fn answer_submissions<'a>(
+        &self,
+        account: &AccountInfo<'a>,
+    ) -> Result<Submissions<'a>, ProgramError> {
         if self.answer_submissions.0 != account.key.to_bytes() {
             Err(Error::AggregatorMismatch)?;
         }
         Submissions::load_initialized(account)
     }
 
-    pub fn round_submissions(&self, account: &AccountInfo) -> Result<Submissions, ProgramError> {
+    pub fn round_submissions<'a>(
+        &self,
+        account: &AccountInfo<'a>,
+    ) -> Result<Submissions<'a>, ProgramError> {
         if self.round_submissions.0 != account.key.to_bytes() {
             Err(Error::AggregatorMismatch)?;
         }
         Submissions::load_initialized(account)
     }
-}
 
-impl Authority for Aggregator {
-    fn authority(&self) -> &PublicKey {
-        &self.owner
-    }
-}
-impl IsInitialized for Aggregator {
-    fn is_initialized(&self) -> bool {
-        self.is_initialized
-    }
-}
-impl BorshState for Aggregator {}
-impl InitBorshState for Aggregator {}
+    /// check a value against the deviation bounds, relative to the latest
+    /// answer and to the median of the round's accepted submissions so far.
+    ///
+    /// A value out of the bound from the latest answer alone is flagged rather
+    /// than excluded once excluding it would leave the round short of
+    /// `min_submissions`, so that a genuine move of the price still resolves.
+    pub fn submission_status(&self, value: i128, submissions: &[Submission]) -> SubmissionStatus {
+        let config = &self.config;
+        let accepted = accepted(submissions);
 
-/// Submission data.
-#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
-pub struct Submission {
-    /// submit time
-    pub updated_at: u64,
-    /// value
-    pub value: u64,
-    /// oracle
-    pub oracle: [u8; 32],
-}
+        let from_answer = config.max_answer_deviation > 0
+            && self.answer.is_initialized()
+            && exceeds_deviation(
+                value,
+                self.answer.signed_median,
+                config.max_answer_deviation,
+            );
 
-impl IsInitialized for Submission {
-    fn is_initialized(&self) -> bool {
-        self.updated_at > 0
-    }
-}
+        let from_round = config.max_round_deviation > 0
+            && Median
+                .aggregate(&accepted)
+                .is_ok_and(|median| exceeds_deviation(value, median, config.max_round_deviation));
 
-/// Oracle data.
-#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
-pub struct Oracle {
-    /// is usually the oracle name
-    pub description: [u8; 32],
-    /// is initialized
-    pub is_initialized: bool,
-    /// withdrawable
-    pub withdrawable: u64,
+        if !from_answer && !from_round {
+            return SubmissionStatus::Accepted;
+        }
 
-    /// oracle cannot start a new round until after `restart_relay` rounds
-    pub allow_start_round: u64,
+        // the most submissions the round could still accept without this one
+        let open = (config.max_submissions as usize).saturating_sub(submissions.len() + 1);
+        let resolvable = accepted.len() + open >= config.min_submissions as usize;
 
-    /// aggregator
-    pub aggregator: PublicKey,
-    /// owner
-    pub owner: PublicKey,
-}
+        match config.outlier_action {
+            OutlierAction::Exclude if from_round || resolvable => SubmissionStatus::Excluded,
+            _ => SubmissionStatus::Flagged,
+        }
+    }
 
-impl Oracle {
+    /// replace the config, checking its invariants, the submissions capacity,
+    /// and the fields that are fixed once answers exist
+    pub fn set_config(&mut self, config: AggregatorConfig) -> ProgramResult {
+        config.validate(Some(&self.config))?;
+
+        if config.max_submissions > self.submissions_capacity {
+            return Err(Error::MaxSubmissionsAboveCapacity)?;
+        }
+
+        if self.answer.is_initialized() && config.decimals != self.config.decimals {
+            return Err(Error::DecimalsImmutable)?;
+        }
+
+        self.config = config;
+
+        Ok(())
+    }
+
+    /// whether the current round has been open for `round_timeout`
+    pub fn is_round_timed_out(&self, clock: &Clock) -> bool {
+        let round_timeout = self.config.round_timeout;
+        match self.config.round_timeout_unit {
+            TimeUnit::Slots => clock.slot >= self.round.created_at + round_timeout as u64,
+            TimeUnit::Seconds => {
+                clock.unix_timestamp >= self.round.created_at_timestamp + round_timeout as i64
+            }
+        }
+    }
+
+    /// `feed_name` without its padding, empty if the feed is unnamed
+    pub fn feed_name(&self) -> &[u8] {
+        let len = self
+            .feed_name
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(self.feed_name.len());
+        &self.feed_name[..len]
+    }
+
+    /// check that a named feed is at the address derived from its authority &
+    /// name by `program_id`. An unnamed feed is at any address.
+    pub fn check_feed_address(&self, program_id: &Pubkey, account: &AccountInfo) -> ProgramResult {
+        let feed_name = self.feed_name();
+        if feed_name.is_empty() {
+            return Ok(());
+        }
+
+        let address = Pubkey::create_program_address(
+            &[
+                FEED_SEED,
+                &self.feed_authority.0,
+                feed_name,
+                &[self.feed_bump_seed],
+            ],
+            program_id,
+        )
+        .map_err(|_| Error::FeedAddressMismatch)?;
+        if address != *account.key {
+            return Err(Error::FeedAddressMismatch)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn check_not_paused(&self) -> ProgramResult {
+        if self.paused {
+            return Err(Error::AggregatorPaused)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn has_answer_history(&self) -> bool {
+        self.answer_history != PublicKey::default()
+    }
+
+    pub fn has_reward_faucet(&self) -> bool {
+        self.config.reward_token_account != PublicKey::default()
+    }
+
+    pub fn has_pending_owner(&self) -> bool {
+        self.pending_owner != PublicKey::default()
+    }
+
+    /// whether config & oracle changes are queued, see `timelock_delay`
+    pub fn is_timelocked(&self) -> bool {
+        self.config.timelock_delay > 0
+    }
+
+    /// check & return the oracle registry linked with an aggregator
+    pub fn oracle_registry<'a>(
+        &self,
+        account: &AccountInfo<'a>,
+    ) -> Result<OracleRegistry<'a>, ProgramError> {
+        if !self.oracle_registry.is_account(account) {
+            Err(Error::AggregatorMismatch)?;
+        }
+        OracleRegistry::load_initialized(account)
+    }
+
+    /// check & return the answer history linked with an aggregator
+    pub fn answer_history<'a>(
+        &self,
+        account: &AccountInfo<'a>,
+    ) -> Result<AnswerHistory<'a>, ProgramError> {
+        if !self.has_answer_history() {
+            Err(Error::NoAnswerHistory)?;
+        }
+        if !self.answer_history.is_account(account) {
+            Err(Error::AggregatorMismatch)?;
+        }
+        AnswerHistory::load_initialized(account)
+    }
+}
+
+impl Authority for Aggregator {
+    fn authority(&self) -> &PublicKey {
+        &self.owner
+    }
+
+    fn multisig(&self) -> Option<&Multisig> {
+        if self.admin.is_set() {
+            Some(&self.admin)
+        } else {
+            None
+        }
+    }
+}
+impl IsInitialized for Aggregator {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl BorshState for Aggregator {
+    const ACCOUNT_TYPE: AccountType = AccountType::Aggregator;
+}
+impl InitBorshState for Aggregator {}
+
+/// Number of resolved answers kept by an answer history
+pub const ANSWER_HISTORY_LEN: usize = 32;
+
+/// Length of a packed `Answer`, i.e. of an answer history slot
+pub const ANSWER_LEN: usize = 114;
+
+/// Ring buffer of the latest resolved answers of an aggregator, read & written
+/// in place in the account data like `Submissions`, as the whole buffer would
+/// not fit on the stack.
+///
+/// The account holds the header, `is_initialized`, the aggregator, the slot
+/// the next newly resolved round is written to, the number of answers
+/// recorded, then `ANSWER_HISTORY_LEN` fixed-size `Answer` slots.
+pub struct AnswerHistory<'a> {
+    data: Rc<RefCell<&'a mut [u8]>>,
+}
+
+impl<'a> AnswerHistory<'a> {
+    const AGGREGATOR_OFFSET: usize = HEADER_LEN + 1;
+    const NEXT_OFFSET: usize = Self::AGGREGATOR_OFFSET + 32;
+    const LEN_OFFSET: usize = Self::NEXT_OFFSET + 1;
+    /// offset of the first slot, after the fields above
+    const SLOTS_OFFSET: usize = Self::LEN_OFFSET + 1;
+
+    /// size of an answer history account
+    pub const ACCOUNT_LEN: usize = Self::SLOTS_OFFSET + ANSWER_HISTORY_LEN * ANSWER_LEN;
+
+    fn header() -> [u8; HEADER_LEN] {
+        [AccountType::AnswerHistory as u8, STATE_VERSION]
+    }
+
+    fn new(account: &AccountInfo<'a>) -> Result<Self, ProgramError> {
+        if (*account.data).borrow().len() != Self::ACCOUNT_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(AnswerHistory {
+            data: account.data.clone(),
+        })
+    }
+
+    /// initialize a freshly allocated, rent exempt account for `aggregator`
+    pub fn init(
+        account: &AccountInfo<'a>,
+        rent: &Rent,
+        aggregator: PublicKey,
+    ) -> Result<Self, ProgramError> {
+        let history = Self::new(account)?;
+
+        if !rent.is_exempt(account.lamports(), Self::ACCOUNT_LEN) {
+            // FIXME: return a custom error
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        {
+            let mut data = history.data.borrow_mut();
+            let is_blank = data.iter().all(|b| *b == AccountType::Uninitialized as u8);
+            if !is_blank {
+                if data[..HEADER_LEN] != Self::header() {
+                    return Err(Error::AccountTypeMismatch)?;
+                }
+                if data[HEADER_LEN] != 0 {
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+            }
+
+            for b in data.iter_mut() {
+                *b = 0;
+            }
+            data[..HEADER_LEN].copy_from_slice(&Self::header());
+            data[HEADER_LEN] = 1;
+            data[Self::AGGREGATOR_OFFSET..Self::NEXT_OFFSET].copy_from_slice(&aggregator.0);
+        }
+
+        Ok(history)
+    }
+
+    pub fn load_initialized(account: &AccountInfo<'a>) -> Result<Self, ProgramError> {
+        let history = Self::new(account)?;
+
+        {
+            let data = history.data.borrow();
+            if data[..HEADER_LEN] != Self::header() {
+                return Err(Error::AccountTypeMismatch)?;
+            }
+            if data[HEADER_LEN] == 0 {
+                return Err(ProgramError::UninitializedAccount);
+            }
+        }
+
+        Ok(history)
+    }
+
+    /// aggregator the history belongs to
+    pub fn aggregator(&self) -> PublicKey {
+        let mut key = [0; 32];
+        key.copy_from_slice(&self.data.borrow()[Self::AGGREGATOR_OFFSET..Self::NEXT_OFFSET]);
+        PublicKey(key)
+    }
+
+    /// slot the next newly resolved round is written to
+    fn next(&self) -> usize {
+        self.data.borrow()[Self::NEXT_OFFSET] as usize % ANSWER_HISTORY_LEN
+    }
+
+    /// number of answers recorded, up to `ANSWER_HISTORY_LEN`
+    pub fn len(&self) -> usize {
+        (self.data.borrow()[Self::LEN_OFFSET] as usize).min(ANSWER_HISTORY_LEN)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn slot(i: usize) -> std::ops::Range<usize> {
+        let start = Self::SLOTS_OFFSET + i * ANSWER_LEN;
+        start..start + ANSWER_LEN
+    }
+
+    fn get(&self, i: usize) -> Result<Answer, ProgramError> {
+        let data = self.data.borrow();
+        let slot = data
+            .get(Self::slot(i))
+            .ok_or(ProgramError::InvalidArgument)?;
+
+        Answer::try_from_slice(slot).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    fn set(&self, i: usize, answer: &Answer) -> ProgramResult {
+        let mut data = self.data.borrow_mut();
+        let mut slot = data
+            .get_mut(Self::slot(i))
+            .ok_or(ProgramError::InvalidArgument)?;
+
+        answer
+            .serialize(&mut slot)
+            .map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// record the aggregator's current answer. An answer of the latest recorded
+    /// round (i.e. its median got updated by a later submission) is overwritten.
+    pub fn record(&self, answer: &Answer) -> ProgramResult {
+        let next = self.next();
+        let len = self.len();
+
+        if len > 0 {
+            let latest = (next + ANSWER_HISTORY_LEN - 1) % ANSWER_HISTORY_LEN;
+            if self.get(latest)?.round_id == answer.round_id {
+                return self.set(latest, answer);
+            }
+        }
+
+        self.set(next, answer)?;
+        let mut data = self.data.borrow_mut();
+        data[Self::NEXT_OFFSET] = ((next + 1) % ANSWER_HISTORY_LEN) as u8;
+        data[Self::LEN_OFFSET] = (len + 1).min(ANSWER_HISTORY_LEN) as u8;
+
+        Ok(())
+    }
+
+    /// recorded answers, oldest first
+    pub fn answers(&self) -> Result<Vec<Answer>, ProgramError> {
+        let len = self.len();
+        let start = (self.next() + ANSWER_HISTORY_LEN - len) % ANSWER_HISTORY_LEN;
+
+        (0..len)
+            .map(|i| self.get((start + i) % ANSWER_HISTORY_LEN))
+            .collect()
+    }
+
+    /// the recorded answer of a round, if it is still in the history
+    pub fn answer_at_round(&self, round_id: u64) -> Result<Option<Answer>, ProgramError> {
+        Ok(self.answers()?.into_iter().find(|a| a.round_id == round_id))
+    }
+
+    /// time-weighted average of the answer medians over the `window` seconds
+    /// ending at the unix timestamp `now`, see `twap`
+    pub fn twap(&self, now: i64, window: u64) -> Result<u64, ProgramError> {
+        twap(&self.answers()?, now, window)
+    }
+}
+
+/// time-weighted average of the medians of `answers`, oldest first, over the
+/// `window` seconds ending at the unix timestamp `now`. A round's answer is
+/// considered live from the time it resolved, until the next round resolved.
+pub fn twap(answers: &[Answer], now: i64, window: u64) -> Result<u64, ProgramError> {
+    if answers.is_empty() {
+        Err(Error::NoResolvedAnswer)?;
+    }
+
+    if window == 0 || window > i64::MAX as u64 {
+        Err(Error::TwapWindowNotCovered)?;
+    }
+    let start = now
+        .checked_sub(window as i64)
+        .ok_or(Error::TwapWindowNotCovered)?;
+
+    // the oldest answer must have been live at the start of the window
+    if answers[0].created_at_timestamp > start {
+        Err(Error::TwapWindowNotCovered)?;
+    }
+
+    let mut cumulative: u128 = 0;
+    for (i, answer) in answers.iter().enumerate() {
+        let live_from = answer.created_at_timestamp.max(start);
+        let live_until = answers
+            .get(i + 1)
+            .map_or(now, |next| next.created_at_timestamp)
+            .min(now);
+
+        if live_until > live_from {
+            cumulative += (answer.median as u128) * ((live_until - live_from) as u128);
+        }
+    }
+
+    Ok((cumulative / window as u128) as u64)
+}
+
+/// Keys of the oracle accounts of an aggregator, in no particular order. Read
+/// & written in place in the account data like `Submissions`, as the whole
+/// registry would not fit on the stack.
+///
+/// The account holds the header, `is_initialized`, the aggregator, the number
+/// of registered oracles, then `MAX_ORACLES` key slots.
+pub struct OracleRegistry<'a> {
+    data: Rc<RefCell<&'a mut [u8]>>,
+}
+
+impl<'a> OracleRegistry<'a> {
+    const AGGREGATOR_OFFSET: usize = HEADER_LEN + 1;
+    const LEN_OFFSET: usize = Self::AGGREGATOR_OFFSET + 32;
+    /// offset of the first slot, after the fields above
+    const SLOTS_OFFSET: usize = Self::LEN_OFFSET + 1;
+
+    /// size of an oracle registry account
+    pub const ACCOUNT_LEN: usize = Self::SLOTS_OFFSET + MAX_ORACLES * 32;
+
+    fn header() -> [u8; HEADER_LEN] {
+        [AccountType::OracleRegistry as u8, STATE_VERSION]
+    }
+
+    fn new(account: &AccountInfo<'a>) -> Result<Self, ProgramError> {
+        if (*account.data).borrow().len() != Self::ACCOUNT_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(OracleRegistry {
+            data: account.data.clone(),
+        })
+    }
+
+    /// initialize a freshly allocated, rent exempt account for `aggregator`
+    pub fn init(
+        account: &AccountInfo<'a>,
+        rent: &Rent,
+        aggregator: PublicKey,
+    ) -> Result<Self, ProgramError> {
+        let registry = Self::new(account)?;
+
+        if !rent.is_exempt(account.lamports(), Self::ACCOUNT_LEN) {
+            // FIXME: return a custom error
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        {
+            let mut data = registry.data.borrow_mut();
+            let is_blank = data.iter().all(|b| *b == AccountType::Uninitialized as u8);
+            if !is_blank {
+                if data[..HEADER_LEN] != Self::header() {
+                    return Err(Error::AccountTypeMismatch)?;
+                }
+                if data[HEADER_LEN] != 0 {
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+            }
+
+            for b in data.iter_mut() {
+                *b = 0;
+            }
+            data[..HEADER_LEN].copy_from_slice(&Self::header());
+            data[HEADER_LEN] = 1;
+            data[Self::AGGREGATOR_OFFSET..Self::LEN_OFFSET].copy_from_slice(&aggregator.0);
+        }
+
+        Ok(registry)
+    }
+
+    pub fn load_initialized(account: &AccountInfo<'a>) -> Result<Self, ProgramError> {
+        let registry = Self::new(account)?;
+
+        {
+            let data = registry.data.borrow();
+            if data[..HEADER_LEN] != Self::header() {
+                return Err(Error::AccountTypeMismatch)?;
+            }
+            if data[HEADER_LEN] == 0 {
+                return Err(ProgramError::UninitializedAccount);
+            }
+        }
+
+        Ok(registry)
+    }
+
+    /// aggregator the registry belongs to
+    pub fn aggregator(&self) -> PublicKey {
+        let mut key = [0; 32];
+        key.copy_from_slice(&self.data.borrow()[Self::AGGREGATOR_OFFSET..Self::LEN_OFFSET]);
+        PublicKey(key)
+    }
+
+    /// number of registered oracles, up to `MAX_ORACLES`
+    pub fn len(&self) -> usize {
+        (self.data.borrow()[Self::LEN_OFFSET] as usize).min(MAX_ORACLES)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn slot(i: usize) -> std::ops::Range<usize> {
+        let start = Self::SLOTS_OFFSET + i * 32;
+        start..start + 32
+    }
+
+    fn get(&self, i: usize) -> PublicKey {
+        let mut key = [0; 32];
+        key.copy_from_slice(&self.data.borrow()[Self::slot(i)]);
+        PublicKey(key)
+    }
+
+    fn set(&self, i: usize, key: &PublicKey) {
+        self.data.borrow_mut()[Self::slot(i)].copy_from_slice(&key.0);
+    }
+
+    fn position(&self, oracle: &PublicKey) -> Option<usize> {
+        let data = self.data.borrow();
+        (0..self.len()).find(|i| data[Self::slot(*i)] == oracle.0)
+    }
+
+    /// registered oracles
+    pub fn oracles(&self) -> Vec<PublicKey> {
+        (0..self.len()).map(|i| self.get(i)).collect()
+    }
+
+    /// register an oracle, up to `cap` oracles
+    pub fn add(&self, oracle: PublicKey, cap: usize) -> ProgramResult {
+        let len = self.len();
+        if len >= cap.min(MAX_ORACLES) {
+            return Err(Error::MaxOraclesReached)?;
+        }
+        if self.position(&oracle).is_some() {
+            return Err(Error::OracleAlreadyRegistered)?;
+        }
+
+        self.set(len, &oracle);
+        self.data.borrow_mut()[Self::LEN_OFFSET] = (len + 1) as u8;
+
+        Ok(())
+    }
+
+    /// unregister an oracle, moving the last registered oracle into its place
+    pub fn remove(&self, oracle: &PublicKey) -> ProgramResult {
+        let i = self.position(oracle).ok_or(Error::OracleNotRegistered)?;
+
+        let last = self.len() - 1;
+        self.set(i, &self.get(last));
+        self.set(last, &PublicKey::default());
+        self.data.borrow_mut()[Self::LEN_OFFSET] = last as u8;
+
+        Ok(())
+    }
+}
+
+/// Length of a packed `Submission`, i.e. of a submissions account slot
+pub const SUBMISSION_LEN: usize = 69;
+
+/// Submission data.
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub struct Submission {
+    /// submit time (slot)
+    pub updated_at: u64,
+    /// submit time (unix timestamp)
+    pub updated_at_timestamp: i64,
+    /// value, within the range of the aggregator's `ValueType`
+    pub value: i128,
+    /// oracle
+    pub oracle: [u8; 32],
+    /// weight of the oracle at submit time
+    pub weight: u32,
+    /// whether the value is within the deviation bounds
+    pub status: SubmissionStatus,
+}
+
+/// Outcome of the deviation check of a submission
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub enum SubmissionStatus {
+    #[default]
+    Accepted,
+    /// out of bounds, but aggregated
+    Flagged,
+    /// out of bounds, and left out of the answer
+    Excluded,
+}
+
+impl IsInitialized for Submission {
+    fn is_initialized(&self) -> bool {
+        self.updated_at > 0
+    }
+}
+
+/// Oracle data.
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub struct Oracle {
+    /// is usually the oracle name
+    pub description: [u8; 32],
+    /// is initialized
+    pub is_initialized: bool,
+    /// withdrawable
+    pub withdrawable: u64,
+
+    /// oracle cannot start a new round until after `restart_relay` rounds
+    pub allow_start_round: u64,
+
+    /// aggregator
+    pub aggregator: PublicKey,
+    /// owner
+    pub owner: PublicKey,
+
+    /// weight of the oracle's submissions under `AggregationMethod::WeightedMedian`
+    pub weight: u32,
+}
+
+impl Oracle {
     pub fn check_aggregator(&self, account: &AccountInfo) -> ProgramResult {
         if !self.aggregator.is_account(account) {
             return Err(Error::AggregatorMismatch)?;
@@ -243,7 +1461,9 @@ impl Authority for Oracle {
         &self.owner
     }
 }
-impl BorshState for Oracle {}
+impl BorshState for Oracle {
+    const ACCOUNT_TYPE: AccountType = AccountType::Oracle;
+}
 impl IsInitialized for Oracle {
     fn is_initialized(&self) -> bool {
         self.is_initialized
@@ -251,6 +1471,98 @@ impl IsInitialized for Oracle {
 }
 impl InitBorshState for Oracle {}
 
+/// Change queued on a timelocked aggregator
+#[derive(Clone, Copy, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub enum PendingAction {
+    /// set `PendingChange::config`
+    #[default]
+    Configure,
+    /// add `PendingChange::oracle`, owned by `PendingChange::oracle_owner`
+    AddOracle,
+    /// remove `PendingChange::oracle`
+    RemoveOracle,
+    /// set the weight of `PendingChange::oracle` to `PendingChange::weight`
+    SetOracleWeight,
+    /// apply `PendingChange::update` to the config at execution
+    UpdateConfig,
+}
+
+/// A change of a timelocked aggregator, executable from slot `eta`
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
+pub struct PendingChange {
+    pub is_initialized: bool,
+    pub aggregator: PublicKey,
+    pub action: PendingAction,
+    /// earliest slot at which the change can be executed
+    pub eta: u64,
+
+    /// config set by `PendingAction::Configure`
+    pub config: AggregatorConfig,
+    /// oracle added, removed or weighted
+    pub oracle: PublicKey,
+    /// owner of the oracle added
+    pub oracle_owner: PublicKey,
+    /// description of the oracle added
+    pub description: [u8; 32],
+    /// weight set by `PendingAction::SetOracleWeight`
+    pub weight: u32,
+    /// update applied by `PendingAction::UpdateConfig`
+    pub update: AggregatorConfigUpdate,
+}
+
+impl PendingChange {
+    pub fn check_aggregator(&self, account: &AccountInfo) -> ProgramResult {
+        if !self.aggregator.is_account(account) {
+            return Err(Error::AggregatorMismatch)?;
+        }
+
+        Ok(())
+    }
+
+    /// check that the change can be executed at `slot`
+    pub fn check_ready(&self, slot: u64) -> ProgramResult {
+        if slot < self.eta {
+            return Err(Error::PendingChangeNotReady)?;
+        }
+
+        Ok(())
+    }
+
+    /// check that `account` is the oracle of the change
+    pub fn check_oracle(&self, account: &AccountInfo) -> ProgramResult {
+        if !self.oracle.is_account(account) {
+            return Err(Error::PendingChangeMismatch)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl IsInitialized for PendingChange {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+// `update` packs to fewer bytes than its worst case when some of its fields are
+// unset, so the state is padded to the account length with zeroes.
+impl BorshState for PendingChange {
+    const ACCOUNT_TYPE: AccountType = AccountType::PendingChange;
+
+    fn unpack(mut body: &[u8]) -> Result<Self, ProgramError> {
+        Self::deserialize(&mut body).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    fn pack(&self) -> Result<Vec<u8>, ProgramError> {
+        let mut data = Self::header().to_vec();
+        self.serialize(&mut data)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        data.resize(borsh_utils::get_account_len::<Self>(), 0);
+
+        Ok(data)
+    }
+}
+impl InitBorshState for PendingChange {}
+
 /// Requester data.
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default, PartialEq)]
 pub struct Requester {
@@ -283,7 +1595,9 @@ impl Authority for Requester {
         &self.owner
     }
 }
-impl BorshState for Requester {}
+impl BorshState for Requester {
+    const ACCOUNT_TYPE: AccountType = AccountType::Requester;
+}
 impl IsInitialized for Requester {
     fn is_initialized(&self) -> bool {
         self.is_initialized
@@ -291,16 +1605,18 @@ impl IsInitialized for Requester {
 }
 impl InitBorshState for Requester {}
 
+#[cfg(test)]
 mod tests {
     use crate::borsh_utils;
 
     use super::*;
+    use solana_program::pubkey::Pubkey;
 
     #[test]
     fn test_packed_len() {
         println!(
             "Aggregator len: {}",
-            borsh_utils::get_packed_len::<Aggregator>()
+            borsh_utils::get_account_len::<Aggregator>()
         );
 
         println!(
@@ -310,14 +1626,945 @@ mod tests {
 
         println!(
             "Requester len: {}",
-            borsh_utils::get_packed_len::<Requester>()
+            borsh_utils::get_account_len::<Requester>()
         );
 
         println!(
             "Submissions len: {}",
-            borsh_utils::get_packed_len::<Submissions>()
+            Submissions::account_len(MAX_ORACLES)
+        );
+
+        println!("Oracle len: {}", borsh_utils::get_account_len::<Oracle>());
+
+        println!("AnswerHistory len: {}", AnswerHistory::ACCOUNT_LEN);
+
+        println!("OracleRegistry len: {}", OracleRegistry::ACCOUNT_LEN);
+
+        println!(
+            "PendingChange len: {}",
+            borsh_utils::get_account_len::<PendingChange>()
+        );
+    }
+
+    #[test]
+    fn test_submissions_packed_len() {
+        assert_eq!(borsh_utils::get_packed_len::<Submission>(), SUBMISSION_LEN);
+        assert_eq!(
+            Submission::default().try_to_vec().unwrap().len(),
+            SUBMISSION_LEN
+        );
+        assert_eq!(
+            Submissions::account_len(MAX_ORACLES),
+            HEADER_LEN + 1 + MAX_ORACLES * SUBMISSION_LEN
+        );
+        assert_eq!(Submissions::account_len(0), HEADER_LEN + 1);
+        const _: () = assert!(MAX_ORACLES >= 32);
+    }
+
+    #[test]
+    fn test_submissions_account() -> ProgramResult {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let rent = Rent::default();
+        let mut lamports = rent.minimum_balance(Submissions::account_len(MAX_ORACLES));
+        let mut data = vec![0; Submissions::account_len(MAX_ORACLES)];
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+
+        assert_eq!(
+            Submissions::load_initialized(&account).map(|_| ()).map_err(Error::from),
+            Err(Error::AccountTypeMismatch)
+        );
+
+        let submissions = Submissions::init(&account, &rent)?;
+        assert_eq!(
+            Submissions::init(&account, &rent).map(|_| ()),
+            Err(ProgramError::AccountAlreadyInitialized)
         );
+        assert_eq!(submissions.submissions()?, vec![]);
+
+        // test: slots are written in place, and read back
+        let values: Vec<i128> = (0..MAX_ORACLES as i128).collect();
+        for (i, submission) in submissions_of(&values).iter().enumerate() {
+            submissions.set(i, submission)?;
+        }
+        assert_eq!(submissions.get(3)?.value, 3);
+        assert_eq!(submissions.submissions()?, submissions_of(&values));
+        assert_eq!(
+            submissions.set(MAX_ORACLES, &Submission::default()),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        let loaded = Submissions::load_initialized(&account)?;
+        assert_eq!(loaded.get(MAX_ORACLES - 1)?.value, MAX_ORACLES as i128 - 1);
+
+        // test: the list ends at the first blank slot
+        loaded.set(2, &Submission::default())?;
+        assert_eq!(submissions.submissions()?, submissions_of(&[0, 1]));
+
+        loaded.clear();
+        assert_eq!(submissions.submissions()?, vec![]);
+        assert_eq!((*account.data).borrow()[..HEADER_LEN], Submissions::header());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_submissions_capacity() -> ProgramResult {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let rent = Rent::default();
+        let mut lamports = rent.minimum_balance(Submissions::account_len(3));
+        let mut data = vec![0; Submissions::account_len(3)];
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+
+        let submissions = Submissions::init(&account, &rent)?;
+        assert_eq!(submissions.capacity(), 3);
+
+        for (i, submission) in submissions_of(&[1, 2, 3]).iter().enumerate() {
+            submissions.set(i, submission)?;
+        }
+        assert_eq!(
+            submissions.set(3, &Submission::default()),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(submissions.submissions()?, submissions_of(&[1, 2, 3]));
+
+        // test: only submissions of the same capacity are copied over
+        let mut other_lamports = rent.minimum_balance(Submissions::account_len(4));
+        let mut other_data = vec![0; Submissions::account_len(4)];
+        let other_account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut other_lamports,
+            &mut other_data,
+            &owner,
+            false,
+            0,
+        );
+        let other = Submissions::init(&other_account, &rent)?;
+        assert_eq!(
+            other.copy_from(&submissions).map_err(Error::from),
+            Err(Error::SubmissionsCapacityMismatch)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_submissions_account_checks() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let rent = Rent::default();
+
+        // test: must fit a whole number of slots, up to MAX_ORACLES
+        for len in &[
+            Submissions::account_len(2) - 1,
+            Submissions::account_len(2) + 1,
+            Submissions::account_len(MAX_ORACLES + 1),
+            HEADER_LEN,
+        ] {
+            let mut lamports = rent.minimum_balance(*len);
+            let mut data = vec![0; *len];
+            let account =
+                AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+            assert_eq!(
+                Submissions::init(&account, &rent).map(|_| ()),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+
+        // test: must be rent exempt
+        let mut lamports = rent.minimum_balance(Submissions::account_len(MAX_ORACLES)) - 1;
+        let mut data = vec![0; Submissions::account_len(MAX_ORACLES)];
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(
+            Submissions::init(&account, &rent).map(|_| ()),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // test: must not be another kind of account
+        let mut lamports = rent.minimum_balance(Submissions::account_len(MAX_ORACLES));
+        let mut data = vec![0; Submissions::account_len(MAX_ORACLES)];
+        data[0] = AccountType::Oracle as u8;
+        data[1] = STATE_VERSION;
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(
+            Submissions::init(&account, &rent).map(|_| ()).map_err(Error::from),
+            Err(Error::AccountTypeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_accepted() {
+        let mut submissions = submissions_of(&[1, 2, 3, 4]);
+        submissions[1].status = SubmissionStatus::Flagged;
+        submissions[2].status = SubmissionStatus::Excluded;
+        submissions[3].updated_at = 0;
+
+        assert_eq!(
+            accepted(&submissions),
+            vec![submissions[0], submissions[1]]
+        );
+    }
+
+    fn submissions_of(values: &[i128]) -> Vec<Submission> {
+        values
+            .iter()
+            .map(|value| Submission {
+                updated_at: 1,
+                value: *value,
+                ..Submission::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(
+            Median.aggregate(&submissions_of(&[])).map_err(Error::from),
+            Err(Error::NoSubmission)
+        );
+        assert_eq!(Median.aggregate(&submissions_of(&[3, 1, 2])), Ok(2));
+        assert_eq!(Median.aggregate(&submissions_of(&[4, 1, 3, 2])), Ok(2));
+        assert_eq!(Median.aggregate(&submissions_of(&[10, 20])), Ok(15));
+
+        // test: negative values round down
+        assert_eq!(Median.aggregate(&submissions_of(&[-3, 0])), Ok(-2));
+        assert_eq!(Median.aggregate(&submissions_of(&[-1, -2])), Ok(-2));
+        assert_eq!(Median.aggregate(&submissions_of(&[-10, -20, 5])), Ok(-10));
+        assert_eq!(Median.aggregate(&submissions_of(&[-5, 5])), Ok(0));
+
+        // test: no overflow at the extremes
+        let max = u64::MAX as i128;
+        assert_eq!(Median.aggregate(&submissions_of(&[max, max - 2])), Ok(max - 1));
+        assert_eq!(
+            Median.aggregate(&submissions_of(&[i128::MAX, i128::MAX - 2])),
+            Ok(i128::MAX - 1)
+        );
+        assert_eq!(
+            Median.aggregate(&submissions_of(&[i128::MIN, i128::MIN + 2])),
+            Ok(i128::MIN + 1)
+        );
+        assert_eq!(Median.aggregate(&submissions_of(&[i128::MIN, i128::MAX])), Ok(-1));
+    }
+
+    fn weighted(values: &[(i128, u32)]) -> Vec<Submission> {
+        values
+            .iter()
+            .map(|(value, weight)| Submission {
+                updated_at: 1,
+                value: *value,
+                weight: *weight,
+                ..Submission::default()
+            })
+            .collect()
+    }
+
+    fn unweighted(values: &[i128]) -> Vec<Submission> {
+        weighted(&values.iter().map(|v| (*v, 1)).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_aggregate_median() {
+        let method = Median;
+        assert_eq!(
+            method.aggregate(&[]).map_err(Error::from),
+            Err(Error::NoSubmission)
+        );
+        assert_eq!(method.aggregate(&unweighted(&[7])), Ok(7));
+        assert_eq!(method.aggregate(&unweighted(&[3, 1, 2])), Ok(2));
+        assert_eq!(method.aggregate(&unweighted(&[4, 1, 3, 2])), Ok(2));
+        assert_eq!(method.aggregate(&unweighted(&[-3, 0])), Ok(-2));
+
+        // test: weights are ignored
+        assert_eq!(method.aggregate(&weighted(&[(1, 100), (2, 1), (3, 1)])), Ok(2));
+    }
+
+    #[test]
+    fn test_config_aggregation() {
+        let values = [1, 2, 2, 10, 100];
+        let submissions = weighted(&[(1, 1), (2, 1), (2, 1), (10, 1), (100, 5)]);
+        let aggregate = |aggregation_method, trim| {
+            let config = AggregatorConfig {
+                aggregation_method,
+                trim,
+                ..AggregatorConfig::default()
+            };
+            config.aggregation().aggregate(&submissions)
+        };
+
+        assert_eq!(AggregatorConfig::default().aggregation_method, AggregationMethod::Median);
+        assert_eq!(aggregate(AggregationMethod::Median, 0), Median.aggregate(&unweighted(&values)));
+        assert_eq!(aggregate(AggregationMethod::Median, 0), Ok(2));
+        assert_eq!(aggregate(AggregationMethod::TrimmedMean, 0), Ok(23));
+        assert_eq!(aggregate(AggregationMethod::TrimmedMean, 1), Ok(4));
+        assert_eq!(aggregate(AggregationMethod::WeightedMedian, 0), Ok(100));
+        assert_eq!(aggregate(AggregationMethod::Mode, 0), Ok(2));
+    }
+
+    #[test]
+    fn test_aggregate_trimmed_mean() {
+        let trimmed = |trim| TrimmedMean { trim };
+        assert_eq!(
+            trimmed(1).aggregate(&[]).map_err(Error::from),
+            Err(Error::NoSubmission)
+        );
+
+        // test: no trim is the plain mean, rounded down
+        assert_eq!(trimmed(0).aggregate(&unweighted(&[1, 2, 4])), Ok(2));
+        assert_eq!(trimmed(0).aggregate(&unweighted(&[1, 2, 3])), Ok(2));
+        assert_eq!(trimmed(0).aggregate(&unweighted(&[-1, -2, -4])), Ok(-3));
+
+        // test: drops the outliers on both ends
+        assert_eq!(trimmed(1).aggregate(&unweighted(&[100, 10, 12, 0, 14])), Ok(12));
+        assert_eq!(trimmed(2).aggregate(&unweighted(&[100, 10, 12, 0, 14, -50])), Ok(11));
+
+        // test: trim is capped to keep at least one value
+        assert_eq!(trimmed(5).aggregate(&unweighted(&[1, 2, 9])), Ok(2));
+        assert_eq!(trimmed(5).aggregate(&unweighted(&[1, 2, 4, 9])), Ok(3));
+        assert_eq!(trimmed(5).aggregate(&unweighted(&[7])), Ok(7));
+
+        // test: no overflow at the extremes
+        assert_eq!(
+            trimmed(0).aggregate(&unweighted(&[i128::MAX, i128::MAX, i128::MAX])),
+            Ok(i128::MAX)
+        );
+        assert_eq!(
+            trimmed(0).aggregate(&unweighted(&[i128::MIN, i128::MIN, i128::MIN])),
+            Ok(i128::MIN)
+        );
+        assert_eq!(
+            trimmed(0).aggregate(&unweighted(&[i128::MIN, i128::MAX])),
+            Ok(-1)
+        );
+    }
+
+    #[test]
+    fn test_aggregate_weighted_median() {
+        let method = WeightedMedian;
+        assert_eq!(
+            method.aggregate(&[]).map_err(Error::from),
+            Err(Error::NoSubmission)
+        );
+
+        // test: equal weights match the median
+        for values in &[vec![3, 1, 2], vec![4, 1, 3, 2], vec![10, 20], vec![-3, 0]] {
+            assert_eq!(
+                method.aggregate(&weighted(
+                    &values.iter().map(|v| (*v, 5)).collect::<Vec<_>>()
+                )),
+                Median.aggregate(&unweighted(values))
+            );
+        }
+
+        // test: a heavy oracle pulls the answer
+        assert_eq!(method.aggregate(&weighted(&[(1, 3), (2, 1), (3, 1)])), Ok(1));
+        assert_eq!(method.aggregate(&weighted(&[(1, 1), (2, 1), (30, 3)])), Ok(30));
+        assert_eq!(method.aggregate(&weighted(&[(1, 2), (2, 1), (3, 1)])), Ok(1));
+
+        // test: the values around an exact half are averaged
+        assert_eq!(method.aggregate(&weighted(&[(10, 2), (20, 1), (30, 1)])), Ok(15));
+
+        // test: zero weights are ignored
+        assert_eq!(method.aggregate(&weighted(&[(1, 0), (2, 0), (3, 1)])), Ok(3));
+        assert_eq!(
+            method
+                .aggregate(&weighted(&[(1, 0), (2, 0)]))
+                .map_err(Error::from),
+            Err(Error::NoSubmission)
+        );
+
+        // test: no overflow at the extremes
+        assert_eq!(
+            method.aggregate(&weighted(&[(i128::MIN, u32::MAX), (i128::MAX, u32::MAX)])),
+            Ok(-1)
+        );
+    }
+
+    #[test]
+    fn test_aggregate_mode() {
+        let method = Mode;
+        assert_eq!(
+            method.aggregate(&[]).map_err(Error::from),
+            Err(Error::NoSubmission)
+        );
+        assert_eq!(method.aggregate(&unweighted(&[4])), Ok(4));
+        assert_eq!(method.aggregate(&unweighted(&[1, 2, 2, 3])), Ok(2));
+        assert_eq!(method.aggregate(&unweighted(&[3, 1, 3, 1, 3])), Ok(3));
+        assert_eq!(method.aggregate(&unweighted(&[-1, 5, -1])), Ok(-1));
+
+        // test: ties are broken by the lowest value
+        assert_eq!(method.aggregate(&unweighted(&[3, 2, 1])), Ok(1));
+        assert_eq!(method.aggregate(&unweighted(&[5, 5, 2, 2, 9])), Ok(2));
+
+        // test: the run at the end counts
+        assert_eq!(method.aggregate(&unweighted(&[1, 9, 9])), Ok(9));
+
+        // test: weights are ignored
+        assert_eq!(method.aggregate(&weighted(&[(1, 100), (2, 1), (2, 1)])), Ok(2));
+    }
+
+    #[test]
+    fn test_exceeds_deviation() {
+        // test: 10% = 1000 bps, inclusive
+        assert!(!exceeds_deviation(110, 100, 1000));
+        assert!(exceeds_deviation(111, 100, 1000));
+        assert!(!exceeds_deviation(90, 100, 1000));
+        assert!(exceeds_deviation(89, 100, 1000));
+
+        // test: negative references
+        assert!(!exceeds_deviation(-110, -100, 1000));
+        assert!(exceeds_deviation(-111, -100, 1000));
+        assert!(exceeds_deviation(10, -100, 1000));
 
-        println!("Oracle len: {}", borsh_utils::get_packed_len::<Oracle>());
+        // test: rounds the bound down
+        assert!(exceeds_deviation(1, 0, 1000));
+        assert!(!exceeds_deviation(0, 0, 1000));
+        assert!(exceeds_deviation(10_001, 10_000, 0));
+        assert!(!exceeds_deviation(10_001, 10_000, 1));
+        assert!(exceeds_deviation(9_999, 19_999, 5000));
+        assert!(!exceeds_deviation(10_000, 19_999, 5000));
+
+        // test: no overflow at the extremes
+        assert!(exceeds_deviation(i128::MAX, i128::MIN, 10_000));
+        assert!(!exceeds_deviation(i128::MAX, i128::MIN, 20_000));
+        assert!(!exceeds_deviation(i128::MIN, i128::MAX, u16::MAX));
+        assert!(exceeds_deviation(i128::MIN, 1, u16::MAX));
+    }
+
+    #[test]
+    fn test_submission_status() {
+        let mut aggregator = Aggregator::default();
+        let round = submissions_of(&[100, 102, 104]);
+
+        // test: no bounds configured
+        assert_eq!(aggregator.submission_status(1_000, &round), SubmissionStatus::Accepted);
+
+        aggregator.config.max_round_deviation = 500;
+        assert_eq!(aggregator.submission_status(107, &round), SubmissionStatus::Accepted);
+        assert_eq!(aggregator.submission_status(108, &round), SubmissionStatus::Flagged);
+        assert_eq!(
+            aggregator.submission_status(108, &[]),
+            SubmissionStatus::Accepted
+        );
+
+        aggregator.config.max_round_deviation = 0;
+        aggregator.config.max_answer_deviation = 1000;
+        aggregator.config.outlier_action = OutlierAction::Exclude;
+
+        // test: no answer to deviate from yet
+        assert_eq!(aggregator.submission_status(1_000, &round), SubmissionStatus::Accepted);
+
+        aggregator.answer = answer(1, 50);
+        aggregator.answer.signed_median = 50;
+        assert_eq!(aggregator.submission_status(55, &round), SubmissionStatus::Accepted);
+        assert_eq!(aggregator.submission_status(56, &round), SubmissionStatus::Excluded);
+        assert_eq!(aggregator.submission_status(44, &round), SubmissionStatus::Excluded);
+
+        // test: flagged instead once excluding would leave the round short of
+        // min_submissions
+        aggregator.config.min_submissions = 6;
+        aggregator.config.max_submissions = 7;
+        assert_eq!(aggregator.submission_status(56, &round), SubmissionStatus::Excluded);
+        aggregator.config.max_submissions = 6;
+        assert_eq!(aggregator.submission_status(56, &round), SubmissionStatus::Flagged);
+
+        // test: still excluded when off the round's median as well
+        aggregator.config.max_round_deviation = 500;
+        assert_eq!(aggregator.submission_status(56, &round), SubmissionStatus::Excluded);
+    }
+
+    #[test]
+    fn test_dispersion() {
+        assert_eq!(
+            Dispersion::of(&submissions_of(&[])).map_err(Error::from),
+            Err(Error::NoSubmission)
+        );
+        assert_eq!(
+            Dispersion::of(&submissions_of(&[7])),
+            Ok(Dispersion { min: 7, max: 7, mad: 0 })
+        );
+
+        // test: deviations from the median 4 are [3, 2, 0, 1, 96]
+        assert_eq!(
+            Dispersion::of(&submissions_of(&[1, 2, 4, 5, 100])),
+            Ok(Dispersion { min: 1, max: 100, mad: 2 })
+        );
+
+        // test: deviations from the median -15 are [25, 5, 5, 15]
+        assert_eq!(
+            Dispersion::of(&submissions_of(&[-40, -20, -10, 0])),
+            Ok(Dispersion { min: -40, max: 0, mad: 10 })
+        );
+
+        // test: deviations from the median 3 are [2, 1, 1, 5], mad rounds down
+        assert_eq!(
+            Dispersion::of(&submissions_of(&[1, 2, 4, 8])),
+            Ok(Dispersion { min: 1, max: 8, mad: 1 })
+        );
+
+        // test: no overflow at the extremes
+        assert_eq!(
+            Dispersion::of(&submissions_of(&[i128::MIN, i128::MAX, i128::MAX])),
+            Ok(Dispersion { min: i128::MIN, max: i128::MAX, mad: 0 })
+        );
+        assert_eq!(
+            Dispersion::of(&submissions_of(&[i128::MIN, i128::MAX])),
+            Ok(Dispersion { min: i128::MIN, max: i128::MAX, mad: (1 << 127) - 1 })
+        );
+    }
+
+    #[test]
+    fn test_exceeds_max_dispersion() {
+        let mut config = AggregatorConfig::default();
+        let dispersion = Dispersion { min: 90, max: 110, mad: 5 };
+        assert!(!config.exceeds_max_dispersion(100, &dispersion));
+
+        config.max_dispersion = 500;
+        assert!(!config.exceeds_max_dispersion(100, &dispersion));
+        assert!(config.exceeds_max_dispersion(99, &dispersion));
+        assert!(config.exceeds_max_dispersion(-99, &dispersion));
+        assert!(!config.exceeds_max_dispersion(0, &Dispersion::default()));
+    }
+
+    #[test]
+    fn test_multisig_new() {
+        let key = |i: u8| PublicKey([i; 32]);
+        let keys: Vec<PublicKey> = (1..=MAX_SIGNERS as u8).map(key).collect();
+
+        let multisig = Multisig::new(&keys[..3], 2).unwrap();
+        assert!(multisig.is_set());
+        assert_eq!(multisig.keys(), &keys[..3]);
+
+        let multisig = Multisig::new(&keys, MAX_SIGNERS as u8).unwrap();
+        assert_eq!(multisig.keys(), keys.as_slice());
+
+        // test: no keys unset the multisig
+        let multisig = Multisig::new(&[], 0).unwrap();
+        assert!(!multisig.is_set());
+        assert_eq!(multisig, Multisig::default());
+
+        let invalid =
+            |keys: &[PublicKey], threshold| Multisig::new(keys, threshold).map_err(Error::from);
+        assert_eq!(invalid(&keys[..3], 0), Err(Error::InvalidMultisig));
+        assert_eq!(invalid(&keys[..3], 4), Err(Error::InvalidMultisig));
+        assert_eq!(invalid(&[], 1), Err(Error::InvalidMultisig));
+        let too_many: Vec<PublicKey> = (1..=MAX_SIGNERS as u8 + 1).map(key).collect();
+        assert_eq!(invalid(&too_many, 1), Err(Error::InvalidMultisig));
+        assert_eq!(
+            invalid(&[key(1), key(2), key(1)], 2),
+            Err(Error::DuplicateSigner)
+        );
+    }
+
+    #[test]
+    fn test_oracle_registry() -> ProgramResult {
+        let key = |i: u8| PublicKey([i; 32]);
+        let account_key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let rent = Rent::default();
+        let mut lamports = rent.minimum_balance(OracleRegistry::ACCOUNT_LEN);
+        let mut data = vec![0; OracleRegistry::ACCOUNT_LEN];
+        let account = AccountInfo::new(
+            &account_key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+
+        assert_eq!(
+            OracleRegistry::load_initialized(&account)
+                .map(|_| ())
+                .map_err(Error::from),
+            Err(Error::AccountTypeMismatch)
+        );
+
+        let registry = OracleRegistry::init(&account, &rent, key(7))?;
+        assert_eq!(
+            OracleRegistry::init(&account, &rent, key(7)).map(|_| ()),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+        assert_eq!(registry.aggregator(), key(7));
+        assert!(registry.is_empty());
+
+        registry.add(key(1), 3)?;
+        registry.add(key(2), 3)?;
+        assert_eq!(
+            registry.add(key(1), 3).map_err(Error::from),
+            Err(Error::OracleAlreadyRegistered)
+        );
+        registry.add(key(3), 3)?;
+        assert_eq!(registry.oracles(), vec![key(1), key(2), key(3)]);
+
+        // test: the registry is read back from the account data
+        let registry = OracleRegistry::load_initialized(&account)?;
+        assert_eq!(registry.oracles(), vec![key(1), key(2), key(3)]);
+
+        // test: the cap is enforced
+        assert_eq!(
+            registry.add(key(4), 3).map_err(Error::from),
+            Err(Error::MaxOraclesReached)
+        );
+
+        // test: the last oracle takes the place of a removed one
+        registry.remove(&key(1))?;
+        assert_eq!(registry.oracles(), vec![key(3), key(2)]);
+        assert_eq!(
+            registry.remove(&key(1)).map_err(Error::from),
+            Err(Error::OracleNotRegistered)
+        );
+        registry.remove(&key(2))?;
+        registry.remove(&key(3))?;
+        assert!(registry.oracles().is_empty());
+
+        // test: removed slots are cleared
+        assert!((*account.data).borrow()[OracleRegistry::SLOTS_OFFSET..]
+            .iter()
+            .all(|b| *b == 0));
+
+        // test: MAX_ORACLES bounds the cap
+        for i in 0..MAX_ORACLES {
+            registry.add(key(i as u8 + 1), usize::MAX)?;
+        }
+        assert_eq!(
+            registry.add(key(0xff), usize::MAX).map_err(Error::from),
+            Err(Error::MaxOraclesReached)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_oracles_cap() {
+        let config = |max_oracles| AggregatorConfig {
+            max_oracles,
+            ..AggregatorConfig::default()
+        };
+        assert_eq!(config(0).oracles_cap(), MAX_ORACLES);
+        assert_eq!(config(5).oracles_cap(), 5);
+        assert_eq!(config(MAX_ORACLES as u8).oracles_cap(), MAX_ORACLES);
+    }
+
+    #[test]
+    fn test_config_update_apply() {
+        let config = AggregatorConfig {
+            description: [1; 32],
+            decimals: 8,
+            min_submissions: 2,
+            max_submissions: 3,
+            reward_amount: 10,
+            value_type: ValueType::I64,
+            ..AggregatorConfig::default()
+        };
+
+        assert_eq!(AggregatorConfigUpdate::default().apply(&config), config);
+
+        let update = AggregatorConfigUpdate {
+            reward_amount: Some(20),
+            outlier_action: Some(OutlierAction::Exclude),
+            ..AggregatorConfigUpdate::default()
+        };
+        assert_eq!(
+            update.apply(&config),
+            AggregatorConfig {
+                reward_amount: 20,
+                outlier_action: OutlierAction::Exclude,
+                ..config
+            }
+        );
+    }
+
+    #[test]
+    fn test_config_validate() {
+        let valid = AggregatorConfig {
+            min_submissions: 2,
+            max_submissions: 3,
+            reward_token_account: PublicKey([1; 32]),
+            ..AggregatorConfig::default()
+        };
+
+        let cases = [
+            ("valid", valid.clone(), Ok(())),
+            (
+                "min == max",
+                AggregatorConfig {
+                    min_submissions: 3,
+                    ..valid.clone()
+                },
+                Ok(()),
+            ),
+            (
+                "max == MAX_ORACLES",
+                AggregatorConfig {
+                    max_submissions: MAX_ORACLES as u8,
+                    ..valid.clone()
+                },
+                Ok(()),
+            ),
+            (
+                "min == 0",
+                AggregatorConfig {
+                    min_submissions: 0,
+                    ..valid.clone()
+                },
+                Err(Error::ZeroMinSubmissions),
+            ),
+            (
+                "min > max",
+                AggregatorConfig {
+                    min_submissions: 4,
+                    ..valid.clone()
+                },
+                Err(Error::MinSubmissionsAboveMax),
+            ),
+            (
+                "max > MAX_ORACLES",
+                AggregatorConfig {
+                    max_submissions: MAX_ORACLES as u8 + 1,
+                    ..valid.clone()
+                },
+                Err(Error::MaxSubmissionsAboveMaxOracles),
+            ),
+            (
+                "max oracles == MAX_ORACLES",
+                AggregatorConfig {
+                    max_oracles: MAX_ORACLES as u8,
+                    ..valid.clone()
+                },
+                Ok(()),
+            ),
+            (
+                "max oracles > MAX_ORACLES",
+                AggregatorConfig {
+                    max_oracles: MAX_ORACLES as u8 + 1,
+                    ..valid.clone()
+                },
+                Err(Error::MaxOraclesAboveMaxOracles),
+            ),
+        ];
+
+        for (name, config, expected) in cases.iter() {
+            assert_eq!(
+                config.validate(None).map_err(Error::from),
+                *expected,
+                "initialize: {}",
+                name
+            );
+            assert_eq!(
+                config.validate(Some(&valid)).map_err(Error::from),
+                *expected,
+                "configure: {}",
+                name
+            );
+        }
+
+        let updates = [
+            (
+                "changed reward_amount",
+                AggregatorConfig {
+                    reward_amount: 100,
+                    ..valid.clone()
+                },
+                Ok(()),
+            ),
+            (
+                "changed reward_token_account",
+                AggregatorConfig {
+                    reward_token_account: PublicKey([2; 32]),
+                    ..valid.clone()
+                },
+                Err(Error::RewardTokenAccountImmutable),
+            ),
+            (
+                "changed value_type",
+                AggregatorConfig {
+                    value_type: ValueType::I64,
+                    ..valid.clone()
+                },
+                Err(Error::ValueTypeImmutable),
+            ),
+        ];
+
+        for (name, config, expected) in updates.iter() {
+            assert_eq!(config.validate(None), Ok(()), "initialize: {}", name);
+            assert_eq!(
+                config.validate(Some(&valid)).map_err(Error::from),
+                *expected,
+                "configure: {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_value_type_check() {
+        assert!(ValueType::U64.check(0).is_ok());
+        assert!(ValueType::U64.check(u64::MAX as i128).is_ok());
+        assert!(ValueType::U64.check(-1).is_err());
+        assert!(ValueType::U64.check(u64::MAX as i128 + 1).is_err());
+
+        assert!(ValueType::I64.check(i64::MIN as i128).is_ok());
+        assert!(ValueType::I64.check(i64::MAX as i128).is_ok());
+        assert!(ValueType::I64.check(i64::MIN as i128 - 1).is_err());
+        assert!(ValueType::I64.check(i64::MAX as i128 + 1).is_err());
+
+        assert!(ValueType::I128.check(i128::MIN).is_ok());
+        assert!(ValueType::I128.check(i128::MAX).is_ok());
+    }
+
+    fn answer(round_id: u64, median: u64) -> Answer {
+        Answer {
+            round_id,
+            median,
+            signed_median: median as i128,
+            created_at: round_id + 1,
+            updated_at: round_id + 1,
+            created_at_timestamp: round_id as i64 + 1,
+            updated_at_timestamp: round_id as i64 + 1,
+            submissions: 1,
+            method: AggregationMethod::Median,
+            dispersion: Dispersion::default(),
+        }
+    }
+
+    #[test]
+    fn test_answer_history_packed_len() {
+        assert_eq!(borsh_utils::get_packed_len::<Answer>(), ANSWER_LEN);
+        assert_eq!(Answer::default().try_to_vec().unwrap().len(), ANSWER_LEN);
+        assert_eq!(
+            AnswerHistory::ACCOUNT_LEN,
+            HEADER_LEN + 1 + 32 + 1 + 1 + ANSWER_HISTORY_LEN * ANSWER_LEN
+        );
+    }
+
+    #[test]
+    fn test_answer_history() -> ProgramResult {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let rent = Rent::default();
+        let mut lamports = rent.minimum_balance(AnswerHistory::ACCOUNT_LEN);
+        let mut data = vec![0; AnswerHistory::ACCOUNT_LEN];
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+
+        assert_eq!(
+            AnswerHistory::load_initialized(&account)
+                .map(|_| ())
+                .map_err(Error::from),
+            Err(Error::AccountTypeMismatch)
+        );
+
+        let aggregator = PublicKey([7; 32]);
+        let history = AnswerHistory::init(&account, &rent, aggregator)?;
+        assert_eq!(
+            AnswerHistory::init(&account, &rent, aggregator).map(|_| ()),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+        assert_eq!(history.aggregator(), aggregator);
+        assert_eq!(history.answers()?, vec![]);
+
+        history.record(&answer(1, 10))?;
+        history.record(&answer(2, 20))?;
+        assert_eq!(history.answers()?, vec![answer(1, 10), answer(2, 20)]);
+
+        // test: updating the median of the latest round overwrites it
+        history.record(&answer(2, 25))?;
+        assert_eq!(history.answers()?, vec![answer(1, 10), answer(2, 25)]);
+        assert_eq!(history.answer_at_round(2)?, Some(answer(2, 25)));
+        assert_eq!(history.answer_at_round(3)?, None);
+
+        // test: oldest answers are dropped when wrapping around
+        let n = ANSWER_HISTORY_LEN as u64;
+        for round_id in 3..(n + 5) {
+            history.record(&answer(round_id, round_id * 10))?;
+        }
+        let answers = history.answers()?;
+        assert_eq!(answers.len(), ANSWER_HISTORY_LEN);
+        assert_eq!(answers[0], answer(5, 50));
+        assert_eq!(answers[ANSWER_HISTORY_LEN - 1], answer(n + 4, (n + 4) * 10));
+        assert_eq!(history.answer_at_round(4)?, None);
+        assert_eq!(history.answer_at_round(5)?, Some(answer(5, 50)));
+
+        // test: the answers are written in place, and read back
+        let loaded = AnswerHistory::load_initialized(&account)?;
+        assert_eq!(loaded.answers()?, answers);
+
+        Ok(())
+    }
+
+    fn history_of(answers: &[(i64, u64)]) -> Vec<Answer> {
+        answers
+            .iter()
+            .enumerate()
+            .map(|(round_id, (created_at, median))| Answer {
+                round_id: round_id as u64,
+                median: *median,
+                signed_median: *median as i128,
+                created_at_timestamp: *created_at,
+                updated_at_timestamp: *created_at,
+                submissions: 1,
+                ..Answer::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_twap() {
+        // (created_at, median)
+        let history = history_of(&[(100, 10), (200, 20), (250, 40)]);
+
+        // test: window within the latest answer
+        assert_eq!(twap(&history, 300, 50), Ok(40));
+
+        // test: window spanning all answers
+        // (10 * 50 + 20 * 50 + 40 * 50) / 150
+        assert_eq!(twap(&history, 300, 150), Ok(23));
+
+        // test: window starting exactly when the oldest answer resolved
+        // (10 * 100 + 20 * 50 + 40 * 50) / 200
+        assert_eq!(twap(&history, 300, 200), Ok(20));
+
+        // test: a spike that is live briefly barely moves the average
+        let history = history_of(&[(100, 100), (190, 1000), (191, 100)]);
+        assert_eq!(twap(&history, 200, 100), Ok(109));
+
+        // test: constant price
+        let history = history_of(&[(10, 42), (20, 42), (30, 42)]);
+        assert_eq!(twap(&history, 1000, 990), Ok(42));
+    }
+
+    #[test]
+    fn test_twap_window_not_covered() {
+        let history = history_of(&[(100, 10), (200, 20)]);
+
+        assert_eq!(
+            twap(&history, 300, 201).map_err(Error::from),
+            Err(Error::TwapWindowNotCovered)
+        );
+        assert_eq!(
+            twap(&history, 300, 0).map_err(Error::from),
+            Err(Error::TwapWindowNotCovered)
+        );
+        assert_eq!(
+            twap(&history, 50, 100).map_err(Error::from),
+            Err(Error::TwapWindowNotCovered)
+        );
+        assert_eq!(
+            twap(&[], 300, 100).map_err(Error::from),
+            Err(Error::NoResolvedAnswer)
+        );
     }
 }
@@ -0,0 +1,148 @@
+//! Closing an aggregator empties & closes its reward faucet, through the token
+//! program.
+
+mod common;
+
+use common::{process_instruction, program_account, send};
+use flux_aggregator::{
+    borsh_utils, find_faucet_owner_address, instruction,
+    state::{Aggregator, AggregatorConfig, OracleRegistry, PublicKey, Submissions},
+};
+use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+
+fn token_account<T: Pack>(state: T) -> Account {
+    let mut account = program_account(&spl_token::id(), T::LEN);
+    state.pack_into_slice(&mut account.data);
+    account
+}
+
+#[tokio::test]
+async fn test_close_aggregator_faucet() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "flux_aggregator",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let aggregator = Pubkey::new_unique();
+    let aggregator_owner = Keypair::new();
+    let round_submissions = Pubkey::new_unique();
+    let answer_submissions = Pubkey::new_unique();
+    let oracle_registry = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+
+    let accounts = [
+        (aggregator, borsh_utils::get_account_len::<Aggregator>()),
+        (round_submissions, Submissions::account_len(1)),
+        (answer_submissions, Submissions::account_len(1)),
+        (oracle_registry, OracleRegistry::ACCOUNT_LEN),
+    ];
+    let mut lamports = 0;
+    for (address, len) in accounts.iter() {
+        let account = program_account(&program_id, *len);
+        lamports += account.lamports;
+        program_test.add_account(*address, account);
+    }
+
+    // a faucet owned by the aggregator, holding 500 tokens
+    let mint = Pubkey::new_unique();
+    let faucet = Pubkey::new_unique();
+    let receiver = Pubkey::new_unique();
+    let (faucet_owner, _) = find_faucet_owner_address(&program_id, &aggregator);
+    program_test.add_account(
+        mint,
+        token_account(spl_token::state::Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: 500,
+            is_initialized: true,
+            ..spl_token::state::Mint::default()
+        }),
+    );
+    let faucet_account = token_account(spl_token::state::Account {
+        mint,
+        owner: faucet_owner,
+        amount: 500,
+        state: spl_token::state::AccountState::Initialized,
+        ..spl_token::state::Account::default()
+    });
+    lamports += faucet_account.lamports;
+    program_test.add_account(faucet, faucet_account);
+    program_test.add_account(
+        receiver,
+        token_account(spl_token::state::Account {
+            mint,
+            owner: Pubkey::new_unique(),
+            state: spl_token::state::AccountState::Initialized,
+            ..spl_token::state::Account::default()
+        }),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    send(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        instruction::initialize(
+            &program_id,
+            &aggregator,
+            &aggregator_owner.pubkey(),
+            &round_submissions,
+            &answer_submissions,
+            &oracle_registry,
+            AggregatorConfig {
+                min_submissions: 1,
+                max_submissions: 1,
+                reward_token_account: PublicKey(faucet.to_bytes()),
+                ..AggregatorConfig::default()
+            },
+        )
+        .unwrap(),
+        &aggregator_owner,
+    )
+    .await;
+
+    send(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        instruction::close_aggregator(
+            &program_id,
+            &aggregator,
+            &aggregator_owner.pubkey(),
+            &round_submissions,
+            &answer_submissions,
+            &oracle_registry,
+            &destination,
+            None,
+            Some((&faucet, &receiver)),
+        )
+        .unwrap(),
+        &aggregator_owner,
+    )
+    .await;
+
+    // the remaining tokens are paid out, and the faucet closed along with the
+    // aggregator accounts
+    let receiver = banks_client.get_account(receiver).await.unwrap().unwrap();
+    assert_eq!(
+        spl_token::state::Account::unpack(&receiver.data)
+            .unwrap()
+            .amount,
+        500
+    );
+    for (address, _) in accounts.iter() {
+        assert_eq!(banks_client.get_account(*address).await.unwrap(), None);
+    }
+    assert_eq!(banks_client.get_account(faucet).await.unwrap(), None);
+    assert_eq!(
+        banks_client.get_balance(destination).await.unwrap(),
+        lamports
+    );
+}
@@ -0,0 +1,54 @@
+//! Helpers shared by the program tests
+
+use flux_aggregator::processor::Processor;
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+use solana_program_test::BanksClient;
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::Instruction,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+// `Processor::process` takes the account infos for as long as the accounts they
+// borrow, which the native processor of `ProgramTest` doesn't guarantee.
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    Processor::process(program_id, accounts, instruction_data)
+}
+
+/// a rent exempt account of `len` zeroed bytes, owned by `owner`
+pub fn program_account(owner: &Pubkey, len: usize) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(len),
+        data: vec![0; len],
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// process `instruction`, signed by `signer`, returning the compute units it used
+pub async fn send(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: Hash,
+    instruction: Instruction,
+    signer: &Keypair,
+) -> u64 {
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[payer, signer], recent_blockhash);
+    let result = banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    result.result.unwrap();
+
+    result.metadata.unwrap().compute_units_consumed
+}
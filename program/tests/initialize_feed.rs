@@ -0,0 +1,113 @@
+//! A named feed is created at its derived address through the system program.
+
+mod common;
+
+use common::{process_instruction, program_account, send};
+use flux_aggregator::{
+    borsh_state::InitBorshState,
+    borsh_utils, find_feed_address, instruction,
+    state::{Aggregator, AggregatorConfig, OracleRegistry, Submissions},
+};
+use solana_program::{account_info::IntoAccountInfo, pubkey::Pubkey, system_program};
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::{
+    account::Account,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+
+#[tokio::test]
+async fn test_initialize_feed() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "flux_aggregator",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let aggregator_owner = Keypair::new();
+    let feed_names = ["btc:usd", "eth:usd"];
+
+    // the accounts of each feed, but its aggregator
+    let accounts: Vec<[Pubkey; 3]> = feed_names
+        .iter()
+        .map(|_| {
+            [
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            ]
+        })
+        .collect();
+    for [round_submissions, answer_submissions, oracle_registry] in accounts.iter() {
+        program_test.add_account(
+            *round_submissions,
+            program_account(&program_id, Submissions::account_len(1)),
+        );
+        program_test.add_account(
+            *answer_submissions,
+            program_account(&program_id, Submissions::account_len(1)),
+        );
+        program_test.add_account(
+            *oracle_registry,
+            program_account(&program_id, OracleRegistry::ACCOUNT_LEN),
+        );
+    }
+
+    // someone sent lamports to the address of the second feed ahead of time
+    let (funded_feed, _) =
+        find_feed_address(&program_id, &aggregator_owner.pubkey(), feed_names[1]).unwrap();
+    program_test.add_account(
+        funded_feed,
+        Account {
+            lamports: 1000,
+            owner: system_program::id(),
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    for (feed_name, [round_submissions, answer_submissions, oracle_registry]) in
+        feed_names.iter().zip(accounts.iter())
+    {
+        send(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            instruction::initialize_feed(
+                &program_id,
+                &payer.pubkey(),
+                &aggregator_owner.pubkey(),
+                round_submissions,
+                answer_submissions,
+                oracle_registry,
+                feed_name,
+                AggregatorConfig {
+                    min_submissions: 1,
+                    max_submissions: 1,
+                    ..AggregatorConfig::default()
+                },
+            )
+            .unwrap(),
+            &aggregator_owner,
+        )
+        .await;
+
+        // the aggregator is a rent exempt account of the program, paid by the
+        // payer, which tops up the lamports already at the address
+        let (address, bump_seed) =
+            find_feed_address(&program_id, &aggregator_owner.pubkey(), feed_name).unwrap();
+        let mut account = banks_client.get_account(address).await.unwrap().unwrap();
+        let len = borsh_utils::get_account_len::<Aggregator>();
+        assert_eq!(account.owner, program_id);
+        assert_eq!(account.data.len(), len);
+        assert_eq!(account.lamports, Rent::default().minimum_balance(len));
+
+        let aggregator =
+            Aggregator::load_initialized(&(&address, &mut account).into_account_info()).unwrap();
+        assert_eq!(aggregator.feed_name(), feed_name.as_bytes());
+        assert_eq!(aggregator.feed_bump_seed, bump_seed);
+        assert_eq!(aggregator.owner.0, aggregator_owner.pubkey().to_bytes());
+    }
+}
@@ -0,0 +1,155 @@
+//! Compute budget of a full round. `cargo test-bpf` runs it against the BPF
+//! build of the program, which is metered like on chain; `cargo test` runs the
+//! same round against the native processor.
+
+mod common;
+
+use common::{process_instruction, program_account, send};
+use flux_aggregator::{
+    borsh_state::InitBorshState,
+    borsh_utils, instruction,
+    instruction::MAX_ORACLES,
+    state::{Aggregator, AggregatorConfig, Oracle, OracleRegistry, Submissions},
+};
+use solana_program::{account_info::IntoAccountInfo, pubkey::Pubkey};
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::signature::{Keypair, Signer};
+
+/// The compute budget of an instruction
+const MAX_COMPUTE_UNITS: u64 = 200_000;
+
+/// A round of `MAX_ORACLES` submissions, the last of which resolves the answer
+/// over all of them, must fit in the default compute budget of an instruction.
+#[tokio::test]
+async fn test_submit_max_oracles_compute() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "flux_aggregator",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let aggregator = Pubkey::new_unique();
+    let aggregator_owner = Keypair::new();
+    let round_submissions = Pubkey::new_unique();
+    let answer_submissions = Pubkey::new_unique();
+    let oracle_registry = Pubkey::new_unique();
+
+    program_test.add_account(
+        aggregator,
+        program_account(&program_id, borsh_utils::get_account_len::<Aggregator>()),
+    );
+    program_test.add_account(
+        round_submissions,
+        program_account(&program_id, Submissions::account_len(MAX_ORACLES)),
+    );
+    program_test.add_account(
+        answer_submissions,
+        program_account(&program_id, Submissions::account_len(MAX_ORACLES)),
+    );
+    program_test.add_account(
+        oracle_registry,
+        program_account(&program_id, OracleRegistry::ACCOUNT_LEN),
+    );
+
+    let oracles: Vec<(Pubkey, Keypair)> = (0..MAX_ORACLES)
+        .map(|_| (Pubkey::new_unique(), Keypair::new()))
+        .collect();
+    for (oracle, _) in oracles.iter() {
+        program_test.add_account(
+            *oracle,
+            program_account(&program_id, borsh_utils::get_account_len::<Oracle>()),
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    send(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        instruction::initialize(
+            &program_id,
+            &aggregator,
+            &aggregator_owner.pubkey(),
+            &round_submissions,
+            &answer_submissions,
+            &oracle_registry,
+            AggregatorConfig {
+                decimals: 8,
+                min_submissions: MAX_ORACLES as u8,
+                max_submissions: MAX_ORACLES as u8,
+                round_timeout: 1,
+                reward_amount: 10,
+                ..AggregatorConfig::default()
+            },
+        )
+        .unwrap(),
+        &aggregator_owner,
+    )
+    .await;
+
+    for (oracle, oracle_owner) in oracles.iter() {
+        send(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            instruction::add_oracle(
+                &program_id,
+                &aggregator,
+                &aggregator_owner.pubkey(),
+                oracle,
+                &oracle_owner.pubkey(),
+                &oracle_registry,
+                [0xab; 32],
+            )
+            .unwrap(),
+            &aggregator_owner,
+        )
+        .await;
+    }
+
+    let mut compute_units = Vec::new();
+    for (i, (oracle, oracle_owner)) in oracles.iter().enumerate() {
+        let units = send(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            instruction::submit(
+                &program_id,
+                &aggregator,
+                &round_submissions,
+                &answer_submissions,
+                oracle,
+                &oracle_owner.pubkey(),
+                None,
+                0,
+                100 + i as u64,
+            )
+            .unwrap(),
+            oracle_owner,
+        )
+        .await;
+        compute_units.push(units);
+    }
+
+    let mut account = banks_client.get_account(aggregator).await.unwrap().unwrap();
+    let aggregator =
+        Aggregator::load_initialized(&(&aggregator, &mut account).into_account_info()).unwrap();
+    assert_eq!(aggregator.answer.submissions, MAX_ORACLES as u8);
+    // the mean of the two middle submissions, rounded down
+    assert_eq!(aggregator.answer.median, 100 + (MAX_ORACLES as u64 - 1) / 2);
+
+    // the last submission resolves the answer over all `MAX_ORACLES` of them
+    let resolve_units = *compute_units.last().unwrap();
+    println!(
+        "compute units of a {} oracle round: {:?}",
+        MAX_ORACLES, compute_units
+    );
+    assert!(
+        resolve_units <= MAX_COMPUTE_UNITS,
+        "resolving a round of {} submissions used {} compute units",
+        MAX_ORACLES,
+        resolve_units
+    );
+}
@@ -5,7 +5,6 @@ import path from "path"
 import {
   Account,
   BPFLoader,
-  ProgramAccount,
   PublicKey,
   SPLToken,
   Wallet,
@@ -37,7 +36,6 @@ interface FaucetInfo {
   pubkey: PublicKey
   // program account public key
   owner: PublicKey
-  ownerSeed: Buffer
 }
 
 interface AggregatorDeployInfo {
@@ -163,22 +161,19 @@ export class Deployer {
       return aggregatorInfo.faucet
     }
 
-    const seed = Buffer.from(aggregatorInfo.config.description)
-
-    const faucetOwner = await ProgramAccount.forSeeds([seed], this.state.programID)
+    const faucetOwner = await this.program.faucetOwner(aggregatorInfo.pubkey)
 
     const spltoken = new SPLToken(this.wallet)
 
     const faucet = await spltoken.initializeAccount({
       // TODO: check if rewardTokenAccount is null
       token: aggregatorInfo.config.rewardTokenAccount,
-      owner: faucetOwner.pubkey,
+      owner: faucetOwner,
     })
 
     aggregatorInfo.faucet = {
       pubkey: faucet.publicKey,
-      owner: faucetOwner.pubkey,
-      ownerSeed: seed,
+      owner: faucetOwner,
     }
 
     return aggregatorInfo.faucet
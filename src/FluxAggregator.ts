@@ -49,10 +49,10 @@ interface RemoveOracleParams {
   oracle: PublicKey
   // To prove you are the aggregator owner
   authority?: Account
+  // receives the lamports of the oracle account, defaults to the wallet
+  destination?: PublicKey
 }
 
-interface RemoveOracleInstructionParams extends RemoveOracleParams {}
-
 interface AddRequesterParams {
   aggregator: PublicKey
   aggregatorOwner: Account
@@ -61,6 +61,15 @@ interface AddRequesterParams {
   description: string
 }
 
+interface RemoveRequesterParams {
+  aggregator: PublicKey
+  requester: PublicKey
+  // To prove you are the aggregator owner
+  authority?: Account
+  // receives the lamports of the requester account, defaults to the wallet
+  destination?: PublicKey
+}
+
 interface RequestRoundParams {
   accounts: {
     aggregator: { write: PublicKey }
@@ -77,28 +86,31 @@ interface SubmitParams {
     answerSubmissions: { write: PublicKey }
     oracle: { write: PublicKey }
     oracle_owner: Account
+    // required if the aggregator has an answer history
+    answerHistory?: { write: PublicKey }
   }
 
   round_id: BN
   value: BN
 }
 
+// the faucet owner is derived from the aggregator
 interface WithdrawParams {
   accounts: {
     aggregator: PublicKey
 
     faucet: { write: PublicKey },
-    faucetOwner: PublicKey,
     oracle: { write: PublicKey },
     oracleOwner: Account,
     receiver: { write: PublicKey },
   }
-
-  faucetOwnerSeed: Buffer
 }
 
 interface WithdrawInstructionParams extends WithdrawParams {}
 
+// seed of the program derived address owning the reward faucet of an aggregator
+const FAUCET_OWNER_SEED = "faucet_owner"
+
 export default class FluxAggregator extends BaseProgram {
   private sys: System
   constructor(wallet: Wallet, programID: PublicKey) {
@@ -110,6 +122,7 @@ export default class FluxAggregator extends BaseProgram {
     const aggregator = new Account()
     const answer_submissions = new Account()
     const round_submissions = new Account()
+    const oracle_registry = new Account()
 
     const input = encoding.Initialize.serialize({
       config: new AggregatorConfig(params.config),
@@ -132,12 +145,18 @@ export default class FluxAggregator extends BaseProgram {
           space: encoding.Submissions.size,
           programID: this.programID,
         }),
+        await this.sys.createRentFreeAccountInstruction({
+          newPubicKey: oracle_registry.publicKey,
+          space: encoding.OracleRegistry.size,
+          programID: this.programID,
+        }),
         this.instruction(input, [
           SYSVAR_RENT_PUBKEY,
           { write: aggregator },
           params.owner, // signed
           { write: round_submissions },
           { write: answer_submissions },
+          { write: oracle_registry },
         ]),
       ],
       [
@@ -146,6 +165,7 @@ export default class FluxAggregator extends BaseProgram {
         params.owner,
         round_submissions,
         answer_submissions,
+        oracle_registry,
       ]
     )
 
@@ -153,8 +173,10 @@ export default class FluxAggregator extends BaseProgram {
   }
 
   public async configureAggregator(params: ConfigureParams) {
+    // settings left out of `params.config` keep their current value
+    const aggregator = await encoding.Aggregator.load(params.aggregator)
     const input = encoding.Configure.serialize({
-      config: new AggregatorConfig(params.config),
+      config: new AggregatorConfig({ ...aggregator.config, ...params.config }),
     })
 
     await this.sendTx(
@@ -165,6 +187,7 @@ export default class FluxAggregator extends BaseProgram {
 
   public async addOracle(params: AddOracleParams): Promise<Account> {
     const oracle = new Account()
+    const aggregator = await encoding.Aggregator.load(params.aggregator)
 
     const input = encoding.AddOracle.serialize({
       description: params.description,
@@ -179,10 +202,11 @@ export default class FluxAggregator extends BaseProgram {
         }),
         this.instruction(input, [
           SYSVAR_RENT_PUBKEY,
-          params.aggregator,
+          { write: params.aggregator },
           params.aggregatorOwner, // signed
-          oracle.publicKey,
+          { write: oracle.publicKey },
           params.oracleOwner,
+          { write: aggregator.oracleRegistry },
         ]),
       ],
       [this.account, oracle, params.aggregatorOwner]
@@ -207,9 +231,9 @@ export default class FluxAggregator extends BaseProgram {
         }),
         this.instruction(input, [
           SYSVAR_RENT_PUBKEY,
-          params.aggregator,
+          { write: params.aggregator },
           params.aggregatorOwner, // signed
-          requester.publicKey,
+          { write: requester.publicKey },
           params.requesterOwner,
         ]),
       ],
@@ -219,6 +243,45 @@ export default class FluxAggregator extends BaseProgram {
     return requester
   }
 
+  // the oracle must have no outstanding rewards
+  public async removeOracle(params: RemoveOracleParams): Promise<void> {
+    const authority = params.authority || this.wallet.account
+    const aggregator = await encoding.Aggregator.load(params.aggregator)
+
+    const input = encoding.RemoveOracle.serialize({})
+
+    await this.sendTx(
+      [
+        this.instruction(input, [
+          { write: params.aggregator },
+          authority, // signed
+          { write: params.oracle },
+          { write: aggregator.oracleRegistry },
+          { write: params.destination || this.account.publicKey },
+        ]),
+      ],
+      [this.account, authority]
+    )
+  }
+
+  public async removeRequester(params: RemoveRequesterParams): Promise<void> {
+    const authority = params.authority || this.wallet.account
+
+    const input = encoding.RemoveRequester.serialize({})
+
+    await this.sendTx(
+      [
+        this.instruction(input, [
+          { write: params.aggregator },
+          authority, // signed
+          { write: params.requester },
+          { write: params.destination || this.account.publicKey },
+        ]),
+      ],
+      [this.account, authority]
+    )
+  }
+
   public async requestRound(params: RequestRoundParams): Promise<void> {
     const input = encoding.RequestRound.serialize(params)
 
@@ -235,37 +298,6 @@ export default class FluxAggregator extends BaseProgram {
   //   return decodeOracleInfo(info)
   // }
 
-  // public async removeOracle(params: RemoveOracleParams): Promise<void> {
-  //   await this.sendTx(
-  //     [this.removeOracleInstruction(params)],
-  //     [this.account, params.authority || this.wallet.account]
-  //   )
-  // }
-
-  // private removeOracleInstruction(
-  //   params: RemoveOracleInstructionParams
-  // ): TransactionInstruction {
-  //   const { authority, aggregator, oracle } = params
-
-  //   const layout = BufferLayout.struct([
-  //     BufferLayout.u8("instruction"),
-  //     BufferLayout.blob(32, "oracle"),
-  //   ])
-
-  //   return this.instructionEncode(
-  //     layout,
-  //     {
-  //       instruction: 2, // remove oracle instruction
-  //       oracle: oracle.toBuffer(),
-  //     },
-  //     [
-  //       //
-  //       { write: aggregator },
-  //       authority || this.wallet.account,
-  //     ]
-  //   )
-  // }
-
   public async submit(params: SubmitParams): Promise<void> {
     const input = encoding.Submit.serialize(params)
 
@@ -277,14 +309,38 @@ export default class FluxAggregator extends BaseProgram {
     )
   }
 
-  public async withdraw(params: WithdrawParams): Promise<void> {
-    const input = encoding.Withdraw.serialize(params)
+  public async faucetOwner(aggregator: PublicKey): Promise<PublicKey> {
+    const [owner] = await PublicKey.findProgramAddress(
+      [aggregator.toBuffer(), Buffer.from(FAUCET_OWNER_SEED)],
+      this.programID
+    )
 
-    let auths = [SPLToken.programID, ...Object.values(params.accounts)]
+    return owner
+  }
+
+  public async withdraw(params: WithdrawParams): Promise<void> {
+    const input = encoding.Withdraw.serialize({})
+
+    const {
+      aggregator,
+      faucet,
+      oracle,
+      oracleOwner,
+      receiver,
+    } = params.accounts
+    let auths = [
+      SPLToken.programID,
+      aggregator,
+      faucet,
+      await this.faucetOwner(aggregator),
+      oracle,
+      oracleOwner,
+      receiver,
+    ]
 
     await this.sendTx(
       [this.instruction(input, auths)],
-      [this.account, params.accounts.oracleOwner]
+      [this.account, oracleOwner]
     )
   }
 
@@ -266,6 +266,9 @@ export class Submitter {
           answerSubmissions: { write: this.aggregator.answerSubmissions },
           oracle: { write: this.oraclePK },
           oracle_owner: this.oracleOwnerWallet.account,
+          ...(this.aggregator.hasAnswerHistory()
+            ? { answerHistory: { write: this.aggregator.answerHistory } }
+            : {}),
         },
         round_id: roundID,
         value,
@@ -4,7 +4,22 @@ import { deserialize, serialize } from "borsh"
 import { conn } from "./context"
 import { jsonReplacer } from "./json"
 
-const MAX_ORACLES = 13
+const MAX_ORACLES = 32
+const MAX_SIGNERS = 11
+
+// state accounts are prefixed with a [account type, layout version] header
+const HEADER_LEN = 2
+const STATE_VERSION = 1
+
+export enum AccountType {
+  Aggregator = 1,
+  Oracle = 2,
+  Requester = 3,
+  Submissions = 4,
+  AnswerHistory = 5,
+  OracleRegistry = 6,
+  PendingChange = 7,
+}
 
 const boolMapper = {
   encode: boolToInt,
@@ -39,6 +54,47 @@ const str32Mapper = {
   },
 }
 
+// signed integers are encoded as their two's complement
+const i64Mapper = {
+  encode: (n: BN) => n.toTwos(64),
+  decode: (n: BN) => n.fromTwos(64),
+}
+
+const i128Mapper = {
+  encode: (n: BN) => n.toTwos(128),
+  decode: (n: BN) => n.fromTwos(128),
+}
+
+// fixed size array of `len` keys
+const pubkeysMapper = (len: number) => ({
+  encode: (keys: PublicKey[]) => {
+    const buf = Buffer.alloc(len * 32)
+    keys.forEach((key, i) => key.toBuffer().copy(buf, i * 32))
+    return buf
+  },
+
+  decode: (buf: Uint8Array) => {
+    const keys: PublicKey[] = []
+    for (let i = 0; i < len; i++) {
+      keys.push(new PublicKey(buf.slice(i * 32, (i + 1) * 32)))
+    }
+    return keys
+  },
+})
+
+// zero padded feed name, empty if unset
+const feedNameMapper = {
+  encode: (name: string) => {
+    const buf = Buffer.alloc(32)
+    Buffer.from(name, "utf8").copy(buf, 0, 0, 32)
+    return buf
+  },
+
+  decode: (bytes: Uint8Array) => {
+    return Buffer.from(bytes).toString("utf8").replace(/\0+$/, "")
+  },
+}
+
 const u64Date = {
   encode: (date: Date) => {
     return new BN(Math.floor(date.getTime() / 1000))
@@ -50,8 +106,11 @@ const u64Date = {
 }
 
 export abstract class Serialization {
+  // type of the state account, checked against the account header
+  public static accountType?: AccountType
+
   public static async load<T>(
-    this: { new (data: any): T },
+    this: { new (data: any): T; accountType?: AccountType },
     key: PublicKey
   ): Promise<T> {
     const info = await conn.getAccountInfo(key, "recent")
@@ -59,11 +118,14 @@ export abstract class Serialization {
       throw new Error("account does not exist")
     }
 
-    return deserialize(schema, this, info.data)
+    return deserialize(schema, this, stripHeader(this.accountType, info.data))
   }
 
-  public static deserialize<T>(this: { new (data: any): T }, data: Buffer): T {
-    return deserialize(schema, this, data)
+  public static deserialize<T>(
+    this: { new (data: any): T; accountType?: AccountType },
+    data: Buffer
+  ): T {
+    return deserialize(schema, this, stripHeader(this.accountType, data))
   }
 
   public static serialize<T extends Serialization>(
@@ -97,17 +159,34 @@ export abstract class Serialization {
   }
 }
 
+function stripHeader(accountType: AccountType | undefined, data: Buffer) {
+  if (accountType === undefined) {
+    throw new Error("not a state account")
+  }
+  if (data[0] != accountType || data[1] != STATE_VERSION) {
+    throw new Error("wrong account type or version")
+  }
+
+  return data.slice(HEADER_LEN)
+}
+
 class Submission {
   public updatedAt!: BN
+  public updatedAtTimestamp!: BN
   public value!: BN
   public oracle!: PublicKey
+  public weight!: number
+  public status!: number
 
   public static schema = {
     kind: "struct",
     fields: [
       ["updatedAt", "u64"],
-      ["value", "u64"],
+      ["updatedAtTimestamp", "u64", i64Mapper],
+      ["value", "u128", i128Mapper],
       ["oracle", [32], pubkeyMapper],
+      ["weight", "u32"],
+      ["status", "u8"],
     ],
   }
 
@@ -116,6 +195,29 @@ class Submission {
   }
 }
 
+export enum TimeUnit {
+  Slots = 0,
+  Seconds = 1,
+}
+
+export enum ValueType {
+  U64 = 0,
+  I64 = 1,
+  I128 = 2,
+}
+
+export enum AggregationMethod {
+  Median = 0,
+  TrimmedMean = 1,
+  WeightedMedian = 2,
+  Mode = 3,
+}
+
+export enum OutlierAction {
+  Flag = 0,
+  Exclude = 1,
+}
+
 export interface IAggregatorConfig {
   decimals: number
   description: string
@@ -126,6 +228,18 @@ export interface IAggregatorConfig {
   maxSubmissions: number
   minSubmissions: number
   rewardTokenAccount: PublicKey
+
+  // see `AggregatorConfig` of the program. 0 if not set.
+  roundTimeoutUnit?: TimeUnit
+  valueType?: ValueType
+  aggregationMethod?: AggregationMethod
+  trim?: number
+  maxAnswerDeviation?: number
+  maxRoundDeviation?: number
+  outlierAction?: OutlierAction
+  maxDispersion?: number
+  maxOracles?: number
+  timelockDelay?: number
 }
 
 export class AggregatorConfig
@@ -140,8 +254,18 @@ export class AggregatorConfig
   public maxSubmissions!: number
   public minSubmissions!: number
   public rewardTokenAccount!: PublicKey
-
-  public static size = 81
+  public roundTimeoutUnit!: TimeUnit
+  public valueType!: ValueType
+  public aggregationMethod!: AggregationMethod
+  public trim!: number
+  public maxAnswerDeviation!: number
+  public maxRoundDeviation!: number
+  public outlierAction!: OutlierAction
+  public maxDispersion!: number
+  public maxOracles!: number
+  public timelockDelay!: number
+
+  public static size = 101
   public static schema = {
     kind: "struct",
     fields: [
@@ -154,15 +278,44 @@ export class AggregatorConfig
       ["minSubmissions", "u8"],
       ["rewardAmount", "u64"],
       ["rewardTokenAccount", [32], pubkeyMapper],
+      ["roundTimeoutUnit", "u8"],
+      ["valueType", "u8"],
+      ["aggregationMethod", "u8"],
+      ["trim", "u8"],
+      ["maxAnswerDeviation", "u16"],
+      ["maxRoundDeviation", "u16"],
+      ["outlierAction", "u8"],
+      ["maxDispersion", "u16"],
+      ["maxOracles", "u8"],
+      ["timelockDelay", "u64"],
     ],
   }
+
+  constructor(data: IAggregatorConfig) {
+    super({
+      roundTimeoutUnit: TimeUnit.Slots,
+      valueType: ValueType.U64,
+      aggregationMethod: AggregationMethod.Median,
+      trim: 0,
+      maxAnswerDeviation: 0,
+      maxRoundDeviation: 0,
+      outlierAction: OutlierAction.Flag,
+      maxDispersion: 0,
+      maxOracles: 0,
+      timelockDelay: 0,
+      ...data,
+    })
+  }
 }
 
+// submissions accounts allocated by the client hold MAX_ORACLES submissions,
+// which is the capacity decoded here
 export class Submissions extends Serialization {
   public isInitialized!: boolean
   public submissions!: Submission[]
 
-  public static size = 625
+  public static accountType = AccountType.Submissions
+  public static size = 2211
   public static schema = {
     kind: "struct",
     fields: [
@@ -195,6 +348,8 @@ export class Round extends Serialization {
   public id!: BN
   public createdAt!: BN
   public updatedAt!: BN
+  public createdAtTimestamp!: BN
+  public updatedAtTimestamp!: BN
 
   public static schema = {
     kind: "struct",
@@ -202,35 +357,93 @@ export class Round extends Serialization {
       ["id", "u64"],
       ["createdAt", "u64"],
       ["updatedAt", "u64"],
+      ["createdAtTimestamp", "u64", i64Mapper],
+      ["updatedAtTimestamp", "u64", i64Mapper],
+    ],
+  }
+}
+
+export class Dispersion extends Serialization {
+  public min!: BN
+  public max!: BN
+  public mad!: BN
+
+  public static schema = {
+    kind: "struct",
+    fields: [
+      ["min", "u128", i128Mapper],
+      ["max", "u128", i128Mapper],
+      ["mad", "u128"],
     ],
   }
 }
 
 export class Answer extends Serialization {
   public roundID!: BN
+  // 0 for an aggregator of signed values, see `signedMedian`
   public median!: BN
+  public signedMedian!: BN
   public createdAt!: BN
   public updatedAt!: BN
+  public createdAtTimestamp!: BN
+  public updatedAtTimestamp!: BN
+  public submissions!: number
+  public method!: AggregationMethod
+  public dispersion!: Dispersion
 
   public static schema = {
     kind: "struct",
     fields: [
       ["roundID", "u64"],
       ["median", "u64"],
+      ["signedMedian", "u128", i128Mapper],
       ["createdAt", "u64"],
       ["updatedAt", "u64"],
+      ["createdAtTimestamp", "u64", i64Mapper],
+      ["updatedAtTimestamp", "u64", i64Mapper],
+      ["submissions", "u8"],
+      ["method", "u8"],
+      ["dispersion", Dispersion],
+    ],
+  }
+}
+
+export class Multisig extends Serialization {
+  // 0 if the multisig is unset
+  public threshold!: number
+  public len!: number
+  public keys!: PublicKey[]
+
+  public static schema = {
+    kind: "struct",
+    fields: [
+      ["threshold", "u8"],
+      ["len", "u8"],
+      ["keys", [MAX_SIGNERS * 32], pubkeysMapper(MAX_SIGNERS)],
     ],
   }
 }
 
 export class Aggregator extends Serialization {
-  public static size = 234
+  public static accountType = AccountType.Aggregator
+  public static size = 880
 
   public config!: AggregatorConfig
+  public owner!: PublicKey
   public roundSubmissions!: PublicKey
   public answerSubmissions!: PublicKey
   public answer!: Answer
   public round!: Round
+  public answerHistory!: PublicKey
+  public submissionsCapacity!: number
+  public pendingOwner!: PublicKey
+  public oracleRegistry!: PublicKey
+  public paused!: boolean
+  public admin!: Multisig
+  public feedName!: string
+  public feedAuthority!: PublicKey
+  public requesters!: number
+  public pendingChanges!: number
 
   public static schema = {
     kind: "struct",
@@ -242,8 +455,47 @@ export class Aggregator extends Serialization {
       ["roundSubmissions", [32], pubkeyMapper],
       ["answer", Answer],
       ["answerSubmissions", [32], pubkeyMapper],
+      ["faucetBumpSeed", "u8"],
+      ["answerHistory", [32], pubkeyMapper],
+      ["submissionsCapacity", "u8"],
+      ["pendingOwner", [32], pubkeyMapper],
+      ["oracleRegistry", [32], pubkeyMapper],
+      ["paused", "u8", boolMapper],
+      ["admin", Multisig],
+      ["feedName", [32], feedNameMapper],
+      ["feedBumpSeed", "u8"],
+      ["feedAuthority", [32], pubkeyMapper],
+      ["requesters", "u32"],
+      ["pendingChanges", "u32"],
     ],
   }
+
+  // the answer history is unset if all zeroes
+  public hasAnswerHistory(): boolean {
+    return this.answerHistory.toBuffer().some((b) => b != 0)
+  }
+}
+
+export class OracleRegistry extends Serialization {
+  public static accountType = AccountType.OracleRegistry
+  public static size = 1060
+
+  public len!: number
+  public keys!: PublicKey[]
+
+  public static schema = {
+    kind: "struct",
+    fields: [
+      ["isInitialized", "u8", boolMapper],
+      ["aggregator", [32], pubkeyMapper],
+      ["len", "u8"],
+      ["keys", [MAX_ORACLES * 32], pubkeysMapper(MAX_ORACLES)],
+    ],
+  }
+
+  public get oracles(): PublicKey[] {
+    return this.keys.slice(0, this.len)
+  }
 }
 
 abstract class InstructionSerialization extends Serialization {
@@ -311,7 +563,7 @@ export class RequestRound extends InstructionSerialization {
 export class Withdraw extends InstructionSerialization {
   public static schema = {
     kind: "struct",
-    fields: [["faucetOwnerSeed", ["u8"]]],
+    fields: [],
   }
 }
 
@@ -325,6 +577,146 @@ export class Submit extends InstructionSerialization {
   }
 }
 
+export class InitializeHistory extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class SubmitSigned extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [
+      ["round_id", "u64"],
+      ["value", "u128", i128Mapper],
+    ],
+  }
+}
+
+export class SetOracleWeight extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [["weight", "u32"]],
+  }
+}
+
+// fields left unset keep their current value
+export class AggregatorConfigUpdate extends Serialization {
+  public static schema = {
+    kind: "struct",
+    fields: [
+      ["description", { kind: "option", type: [32] }],
+      ["decimals", { kind: "option", type: "u8" }],
+      ["roundTimeout", { kind: "option", type: "u32" }],
+      ["restartDelay", { kind: "option", type: "u8" }],
+      ["requesterRestartDelay", { kind: "option", type: "u8" }],
+      ["maxSubmissions", { kind: "option", type: "u8" }],
+      ["minSubmissions", { kind: "option", type: "u8" }],
+      ["rewardAmount", { kind: "option", type: "u64" }],
+      ["roundTimeoutUnit", { kind: "option", type: "u8" }],
+      ["aggregationMethod", { kind: "option", type: "u8" }],
+      ["trim", { kind: "option", type: "u8" }],
+      ["maxAnswerDeviation", { kind: "option", type: "u16" }],
+      ["maxRoundDeviation", { kind: "option", type: "u16" }],
+      ["outlierAction", { kind: "option", type: "u8" }],
+      ["maxDispersion", { kind: "option", type: "u16" }],
+      ["maxOracles", { kind: "option", type: "u8" }],
+      ["timelockDelay", { kind: "option", type: "u64" }],
+    ],
+  }
+}
+
+export class UpdateConfig extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [["update", AggregatorConfigUpdate]],
+  }
+}
+
+export class ProposeOwner extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class AcceptOwnership extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class CancelOwnershipTransfer extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class SetOracleOwner extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class CloseAggregator extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class Pause extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class Unpause extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class SetAdminMultisig extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [
+      ["threshold", "u8"],
+      ["keys", [[32]]],
+    ],
+  }
+}
+
+export class ExecutePending extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class CancelPending extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [],
+  }
+}
+
+export class InitializeFeed extends InstructionSerialization {
+  public static schema = {
+    kind: "struct",
+    fields: [
+      ["feedName", "string"],
+      ["config", AggregatorConfig],
+    ],
+  }
+}
+
 export class Instruction extends Serialization {
   public enum!: string
 
@@ -340,6 +732,22 @@ export class Instruction extends Serialization {
       [RemoveRequester.name, RemoveRequester],
       [RequestRound.name, RequestRound],
       [Submit.name, Submit],
+      [Withdraw.name, Withdraw],
+      [InitializeHistory.name, InitializeHistory],
+      [SubmitSigned.name, SubmitSigned],
+      [SetOracleWeight.name, SetOracleWeight],
+      [UpdateConfig.name, UpdateConfig],
+      [ProposeOwner.name, ProposeOwner],
+      [AcceptOwnership.name, AcceptOwnership],
+      [CancelOwnershipTransfer.name, CancelOwnershipTransfer],
+      [SetOracleOwner.name, SetOracleOwner],
+      [CloseAggregator.name, CloseAggregator],
+      [Pause.name, Pause],
+      [Unpause.name, Unpause],
+      [SetAdminMultisig.name, SetAdminMultisig],
+      [ExecutePending.name, ExecutePending],
+      [CancelPending.name, CancelPending],
+      [InitializeFeed.name, InitializeFeed],
     ],
   }
 
@@ -380,9 +788,11 @@ function boolToInt(t: boolean) {
 }
 
 export class Oracle extends Serialization {
-  public static size = 113
+  public static accountType = AccountType.Oracle
+  public static size = 119
   public allowStartRound!: BN
   public withdrawable!: BN
+  public weight!: number
 
   public static schema = {
     kind: "struct",
@@ -393,6 +803,7 @@ export class Oracle extends Serialization {
       ["allowStartRound", "u64"],
       ["aggregator", [32], pubkeyMapper],
       ["owner", [32], pubkeyMapper],
+      ["weight", "u32"],
     ],
   }
 
@@ -402,7 +813,8 @@ export class Oracle extends Serialization {
 }
 
 export class Requester extends Serialization {
-  public static size = 105 
+  public static accountType = AccountType.Requester
+  public static size = 107
   public allowStartRound!: BN
 
   public static schema = {
@@ -431,6 +843,9 @@ export const schema = new Map([
   [Requester, Requester.schema],
   [Round, Round.schema],
   [Answer, Answer.schema],
+  [Dispersion, Dispersion.schema],
+  [Multisig, Multisig.schema],
+  [OracleRegistry, OracleRegistry.schema],
   [AggregatorConfig, AggregatorConfig.schema],
   [Submissions, Submissions.schema],
   [Submission, Submission.schema],
@@ -439,11 +854,29 @@ export const schema = new Map([
   [Initialize, Initialize.schema],
   [Configure, Configure.schema],
   [AddOracle, AddOracle.schema],
+  [RemoveOracle, RemoveOracle.schema],
 
   [AddRequester, AddRequester.schema],
   [RemoveRequester, RemoveRequester.schema],
   [RequestRound, RequestRound.schema],
 
   [Submit, Submit.schema],
+  [Withdraw, Withdraw.schema],
+  [InitializeHistory, InitializeHistory.schema],
+  [SubmitSigned, SubmitSigned.schema],
+  [SetOracleWeight, SetOracleWeight.schema],
+  [AggregatorConfigUpdate, AggregatorConfigUpdate.schema],
+  [UpdateConfig, UpdateConfig.schema],
+  [ProposeOwner, ProposeOwner.schema],
+  [AcceptOwnership, AcceptOwnership.schema],
+  [CancelOwnershipTransfer, CancelOwnershipTransfer.schema],
+  [SetOracleOwner, SetOracleOwner.schema],
+  [CloseAggregator, CloseAggregator.schema],
+  [Pause, Pause.schema],
+  [Unpause, Unpause.schema],
+  [SetAdminMultisig, SetAdminMultisig.schema],
+  [ExecutePending, ExecutePending.schema],
+  [CancelPending, CancelPending.schema],
+  [InitializeFeed, InitializeFeed.schema],
 
 ] as any) as any